@@ -0,0 +1,143 @@
+//! Files attached to notes. Each file is stored once in the database, keyed by
+//! the SHA-256 of its contents, and notes refer to it in markdown as
+//! `![name](attachment:<hash>)`.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::db::AttachmentEntry;
+use crate::utils;
+
+pub const SCHEME: &str = "attachment:";
+
+/// Hex SHA-256 of `data`.
+pub fn hash(data: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, data)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Markdown that refers to an attachment.
+pub fn reference(name: &str, hash: &str) -> String {
+    format!("![{}]({}{})", name, SCHEME, hash)
+}
+
+/// True if `attachment` is the one `selector` names, by file name or hash prefix.
+pub fn matches(attachment: &AttachmentEntry, selector: &str) -> bool {
+    attachment.name == selector || (selector.len() >= 4 && attachment.hash.starts_with(selector))
+}
+
+/// `text` with attachment references replaced by a short inline placeholder
+/// for the preview. `attachments` is keyed by hash; references may use a
+/// hash prefix.
+pub fn placeholders<'a>(text: &'a str, attachments: &HashMap<String, AttachmentEntry>) -> Cow<'a, str> {
+    let marker = format!("]({}", SCHEME);
+    if !text.contains(&marker) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find(&marker) {
+        let line_start = rest[..at].rfind('\n').map_or(0, |i| i + 1);
+        let (Some(open), Some(close)) = (rest[line_start..at].rfind('['), rest[at..].find(')')) else {
+            out.push_str(&rest[..at + marker.len()]);
+            rest = &rest[at + marker.len()..];
+            continue;
+        };
+        let open = line_start + open;
+        let start = if open > 0 && rest.as_bytes()[open - 1] == b'!' { open - 1 } else { open };
+        let alt = &rest[open + 1..at];
+        let hash = &rest[at + marker.len()..at + close];
+
+        let found = attachments.get(hash).or_else(|| {
+            let mut candidates = attachments.values().filter(|a| !hash.is_empty() && a.hash.starts_with(hash));
+            candidates.next().filter(|_| candidates.next().is_none())
+        });
+        let label = match found {
+            Some(a) if alt.is_empty() => format!("{}, {}", a.name, utils::format_size(a.size)),
+            Some(a) => format!("{}, {}", alt, utils::format_size(a.size)),
+            None => "missing".to_string(),
+        };
+        out.push_str(&rest[..start]);
+        out.push_str(&format!("`[attachment: {}]`", label));
+        rest = &rest[at + close + 1..];
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABC: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    fn attachment(name: &str, hash: &str, size: usize) -> AttachmentEntry {
+        AttachmentEntry {
+            name: name.to_string(),
+            hash: hash.to_string(),
+            size,
+            added_at: String::new(),
+        }
+    }
+
+    fn by_hash(attachments: &[AttachmentEntry]) -> HashMap<String, AttachmentEntry> {
+        attachments.iter().map(|a| (a.hash.clone(), a.clone())).collect()
+    }
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(hash(b"abc"), ABC);
+        assert_eq!(hash(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    }
+
+    #[test]
+    fn test_reference() {
+        assert_eq!(reference("cat.png", ABC), format!("![cat.png](attachment:{})", ABC));
+    }
+
+    #[test]
+    fn test_matches() {
+        let a = attachment("cat.png", ABC, 3);
+        assert!(matches(&a, "cat.png"));
+        assert!(matches(&a, "ba78"));
+        assert!(matches(&a, ABC));
+        // Too short to be a hash prefix, or not one
+        assert!(!matches(&a, "ba7"));
+        assert!(!matches(&a, "beef"));
+        assert!(!matches(&a, "cat"));
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let attachments = by_hash(&[attachment("cat.png", ABC, 2048)]);
+        let text = format!("see ![](attachment:{}) and ![photo](attachment:ba78).\n", ABC);
+        assert_eq!(
+            placeholders(&text, &attachments),
+            "see `[attachment: cat.png, 2.0 KB]` and `[attachment: photo, 2.0 KB]`.\n"
+        );
+        assert_eq!(
+            placeholders("![x](attachment:dead)", &attachments),
+            "`[attachment: missing]`"
+        );
+    }
+
+    #[test]
+    fn test_placeholders_ambiguous_prefix() {
+        let other = "ba78ffff";
+        let attachments = by_hash(&[attachment("a", ABC, 1), attachment("b", other, 1)]);
+        assert_eq!(placeholders("![](attachment:ba78)", &attachments), "`[attachment: missing]`");
+        assert_eq!(placeholders("![](attachment:ba78f)", &attachments), "`[attachment: b, 1 B]`");
+    }
+
+    #[test]
+    fn test_placeholders_leave_other_text_alone() {
+        let attachments = HashMap::new();
+        assert!(matches!(placeholders("![img](https://x/y.png)", &attachments), Cow::Borrowed(_)));
+        // No opening bracket on the line
+        assert_eq!(placeholders("text](attachment:ab)", &attachments), "text](attachment:ab)");
+    }
+}
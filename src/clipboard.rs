@@ -0,0 +1,67 @@
+//! The system clipboard.
+//!
+//! On Linux the X11 and Wayland clipboards are served by the process that set
+//! them, so text copied by a short-lived command would vanish when it exits.
+//! There `copy` hands the text to a background `scrap serve-clipboard`, which
+//! keeps serving it until something else is copied.
+
+use anyhow::{Context, Result};
+use std::io::Read;
+
+/// Hidden subcommand that holds copied text on Linux.
+pub const SERVE_COMMAND: &str = "serve-clipboard";
+
+const UNAVAILABLE: &str = "Clipboard unavailable";
+
+#[cfg(target_os = "linux")]
+pub fn copy(text: &str) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    // Fail here rather than silently in the background when there is no clipboard
+    arboard::Clipboard::new().context(UNAVAILABLE)?;
+    let mut child = Command::new(std::env::current_exe()?)
+        .arg(SERVE_COMMAND)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()
+        .context(UNAVAILABLE)?;
+    child
+        .stdin
+        .take()
+        .context(UNAVAILABLE)?
+        .write_all(text.as_bytes())?;
+    // Reap it when it exits, in case this process outlives it
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn copy(text: &str) -> Result<()> {
+    arboard::Clipboard::new()
+        .and_then(|mut c| c.set_text(text))
+        .context(UNAVAILABLE)
+}
+
+pub fn paste() -> Result<String> {
+    arboard::Clipboard::new()
+        .and_then(|mut c| c.get_text())
+        .context(UNAVAILABLE)
+}
+
+/// Put stdin on the clipboard and keep serving it until it's replaced.
+pub fn serve() -> Result<()> {
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text)?;
+    #[cfg(target_os = "linux")]
+    {
+        use arboard::SetExtLinux;
+        arboard::Clipboard::new()?.set().wait().text(text)?;
+        Ok(())
+    }
+    #[cfg(not(target_os = "linux"))]
+    copy(&text)
+}
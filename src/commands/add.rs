@@ -1,6 +1,7 @@
 use anyhow::{bail, Result};
 
 use crate::db;
+use crate::session::{self, Target};
 use crate::utils;
 
 pub fn run(name: &str, tags: &[String]) -> Result<()> {
@@ -13,8 +14,9 @@ pub fn run(name: &str, tags: &[String]) -> Result<()> {
         bail!("Note '{}' already exists. Use 'open' to edit it.", name);
     }
 
-    let contents = utils::get_user_input(name)?;
+    let (contents, edit) = session::edit(&Target::new_note(name, tags), "")?;
     db::insert_note(&conn, name, &contents, tags)?;
+    edit.finish();
     println!("Note '{}' created.", name);
     Ok(())
 }
@@ -1,14 +1,16 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 use std::io::Read;
 
+use crate::crypto;
 use crate::db;
+use crate::output;
 
 pub fn run(name: &str) -> Result<()> {
     let conn = db::get_db()?;
 
     let (id, existing, _tags) = match db::get_note(&conn, name)? {
         Some(row) => row,
-        None => bail!("Note '{}' not found", name),
+        None => return Err(output::not_found(name)),
     };
 
     let mut new_content = String::new();
@@ -16,8 +18,8 @@ pub fn run(name: &str) -> Result<()> {
         .read_to_string(&mut new_content)
         .context("Failed to read from stdin")?;
 
-    let combined = format!("{existing}\n{new_content}");
-    db::update_note(&conn, id, &combined)?;
+    crypto::unlock(&existing, name)?;
+    db::append_note(&conn, id, &new_content)?;
     db::mark_summary_stale(&conn, id)?;
 
     Ok(())
@@ -0,0 +1,35 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::attachments;
+use crate::crypto;
+use crate::db;
+use crate::output;
+use crate::utils;
+
+pub fn run(name: &str, files: &[String], link: bool) -> Result<()> {
+    let conn = db::get_db()?;
+    let (id, contents, _tags) = db::get_note(&conn, name)?.ok_or_else(|| output::not_found(name))?;
+    if link {
+        crypto::unlock(&contents, name)?;
+    }
+
+    let mut references = Vec::new();
+    for file in files {
+        let data = std::fs::read(file).with_context(|| format!("Failed to read file: {}", file))?;
+        let file_name = Path::new(file)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| file.clone());
+        let hash = db::add_attachment(&conn, id, &file_name, &data)?;
+        let reference = attachments::reference(&file_name, &hash);
+        println!("Attached '{}' ({}) to '{}': {}", file_name, utils::format_size(data.len()), name, reference);
+        references.push(reference);
+    }
+
+    if link {
+        db::append_note(&conn, id, &references.join("\n"))?;
+        db::mark_summary_stale(&conn, id)?;
+    }
+    Ok(())
+}
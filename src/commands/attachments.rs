@@ -0,0 +1,47 @@
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+use crate::attachments;
+use crate::db;
+use crate::output::{self, OutputFormat};
+
+/// List a note's attachments, or with `extract` write them into that directory.
+pub fn run(name: &str, which: &[String], extract: Option<&str>, format: OutputFormat) -> Result<()> {
+    let conn = db::get_db()?;
+    let (id, _tags) = db::get_tags_and_id(&conn, name)?
+        .ok_or_else(|| output::not_found(name))?;
+
+    let mut list = db::note_attachments(&conn, id)?;
+    if !which.is_empty() {
+        for selector in which {
+            if !list.iter().any(|a| attachments::matches(a, selector)) {
+                bail!("'{}' has no attachment '{}'.", name, selector);
+            }
+        }
+        list.retain(|a| which.iter().any(|s| attachments::matches(a, s)));
+    }
+
+    let Some(dir) = extract else {
+        return output::print_attachments(format, &list);
+    };
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create directory: {}", dir))?;
+    let mut extracted = 0;
+    for attachment in &list {
+        // Names come from the database, so keep them inside `dir`
+        let file_name = match Path::new(&attachment.name).file_name() {
+            Some(n) => n.to_owned(),
+            None => attachment.hash.clone().into(),
+        };
+        let path = Path::new(dir).join(file_name);
+        if path.exists() {
+            eprintln!("Skipped '{}': {} already exists.", attachment.name, path.display());
+            continue;
+        }
+        let data = db::attachment_data(&conn, &attachment.hash)?
+            .with_context(|| format!("Attachment '{}' is missing its data", attachment.name))?;
+        std::fs::write(&path, data).with_context(|| format!("Failed to write file: {}", path.display()))?;
+        extracted += 1;
+    }
+    println!("Extracted {} attachments to {}", extracted, dir);
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use anyhow::{bail, Context, Result};
+use std::io::{IsTerminal, Read};
+
+use crate::db;
+use crate::utils;
+
+/// Tag marking captured notes that haven't been triaged yet.
+pub const INBOX_TAG: &str = "inbox";
+
+pub fn run(text: &[String], tags: &[String]) -> Result<()> {
+    utils::validate_tags(tags)?;
+
+    let contents = if text.is_empty() {
+        if std::io::stdin().is_terminal() {
+            eprintln!("Type the note, then press Ctrl-D:");
+        }
+        let mut content = String::new();
+        std::io::stdin()
+            .read_to_string(&mut content)
+            .context("Failed to read from stdin")?;
+        content
+    } else {
+        text.join(" ")
+    };
+    if contents.trim().is_empty() {
+        bail!("Nothing to capture.");
+    }
+
+    let conn = db::get_db()?;
+    let title = match utils::title_from(&contents) {
+        Some(title) => title,
+        None => db::local_timestamp(&conn)?,
+    };
+    let title = db::unique_title(&conn, &title)?;
+
+    let mut all_tags = vec![INBOX_TAG.to_string()];
+    all_tags.extend(tags.iter().filter(|t| *t != INBOX_TAG).cloned());
+    db::insert_note(&conn, &title, &contents, &all_tags)?;
+    println!("Captured '{}'.", title);
+    Ok(())
+}
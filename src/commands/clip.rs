@@ -0,0 +1,15 @@
+use anyhow::Result;
+
+use crate::clipboard;
+use crate::crypto;
+use crate::db;
+use crate::output;
+
+pub fn run(name: &str) -> Result<()> {
+    let conn = db::get_db()?;
+    let (_id, contents, _tags) = db::get_note(&conn, name)?.ok_or_else(|| output::not_found(name))?;
+
+    clipboard::copy(&crypto::unlock(&contents, name)?)?;
+    println!("Copied '{}' to the clipboard.", name);
+    Ok(())
+}
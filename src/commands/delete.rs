@@ -1,16 +1,16 @@
 use anyhow::Result;
 
 use crate::db;
+use crate::output;
 use crate::utils;
 
 pub fn run(name: &str) -> Result<()> {
     utils::validate_name(name)?;
 
     let conn = db::get_db()?;
-    if db::delete_note(&conn, name)? {
-        println!("Note '{}' deleted.", name);
-    } else {
-        println!("Note '{}' not found.", name);
+    if !db::delete_note(&conn, name)? {
+        return Err(output::not_found(name));
     }
+    println!("Note '{}' moved to trash.", name);
     Ok(())
 }
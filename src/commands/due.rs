@@ -0,0 +1,44 @@
+use anyhow::Result;
+
+use crate::db;
+use crate::output::{self, DueItem, OutputFormat};
+use crate::utils;
+
+/// Reminders that have come due, then open tasks due today or earlier.
+pub fn run(include_archived: bool, format: OutputFormat) -> Result<()> {
+    let conn = db::get_db()?;
+    let mut items = Vec::new();
+
+    let mut notes = db::list_notes(&conn)?;
+    notes.retain(|note| note.reminder_due() && (include_archived || !note.archived));
+    notes.sort_by(|a, b| a.remind_at.cmp(&b.remind_at));
+    for note in notes {
+        let at = note.remind_at.unwrap_or_default();
+        items.push(DueItem {
+            kind: "reminder",
+            due: db::local_time(&conn, &at)?,
+            when: utils::relative_time(&at),
+            title: note.title,
+            line: None,
+            text: None,
+        });
+    }
+
+    let today = db::local_timestamp(&conn)?[..10].to_string();
+    for task in db::list_tasks(&conn)? {
+        let Some(due) = task.due else { continue };
+        if task.done || due > today || (task.archived && !include_archived) {
+            continue;
+        }
+        items.push(DueItem {
+            kind: "task",
+            when: if task.overdue { "overdue" } else { "today" }.to_string(),
+            due,
+            title: task.title,
+            line: Some(task.line),
+            text: Some(task.text),
+        });
+    }
+
+    output::print_due(format, &items)
+}
@@ -1,6 +1,7 @@
 use anyhow::{bail, Result};
 
 use crate::db;
+use crate::output;
 use crate::utils;
 
 pub fn run(name: &str, tags: &[String], add: bool, delete: bool) -> Result<()> {
@@ -19,7 +20,7 @@ pub fn run(name: &str, tags: &[String], add: bool, delete: bool) -> Result<()> {
 
     let conn = db::get_db()?;
     let (id, mut existing_tags) = db::get_tags_and_id(&conn, name)?
-        .ok_or_else(|| anyhow::anyhow!("Note '{}' not found.", name))?;
+        .ok_or_else(|| output::not_found(name))?;
 
     if add {
         for tag in tags {
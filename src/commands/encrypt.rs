@@ -0,0 +1,38 @@
+use anyhow::{bail, Result};
+
+use crate::crypto;
+use crate::db;
+use crate::output;
+
+pub fn run(name: &str, encrypt: bool) -> Result<()> {
+    let conn = db::get_db()?;
+    let (id, contents, _tags) = db::get_note(&conn, name)?.ok_or_else(|| output::not_found(name))?;
+
+    if encrypt {
+        if crypto::is_encrypted(&contents) {
+            bail!("Note '{}' is already encrypted.", name);
+        }
+        let passphrase = crypto::new_passphrase()?;
+        // The TUI unlocks one passphrase at a time
+        match db::any_encrypted_note(&conn)? {
+            Some((title, other)) if crypto::try_passphrase(&passphrase, &other, &title).is_err() => {
+                eprintln!("Other encrypted notes use a different passphrase; the TUI unlocks one passphrase at a time.");
+                crypto::set_passphrase(&passphrase)?;
+            }
+            Some(_) => {}
+            None => crypto::set_passphrase(&passphrase)?,
+        }
+        db::store_contents(&conn, id, &crypto::seal(&contents, name)?)?;
+        println!("Note '{}' encrypted.", name);
+        if !db::note_attachments(&conn, id)?.is_empty() {
+            eprintln!("Its attachments are not encrypted.");
+        }
+    } else {
+        if !crypto::is_encrypted(&contents) {
+            bail!("Note '{}' is not encrypted.", name);
+        }
+        db::store_contents(&conn, id, &crypto::unlock(&contents, name)?)?;
+        println!("Note '{}' decrypted.", name);
+    }
+    Ok(())
+}
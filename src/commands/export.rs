@@ -1,4 +1,6 @@
 use anyhow::{Context, Result};
+use base64::Engine;
+use rusqlite::Connection;
 use serde::Serialize;
 use std::fs::File;
 use std::io::Write;
@@ -12,6 +14,23 @@ struct ExportNote {
     tags: Vec<String>,
     created_at: String,
     updated_at: String,
+    pinned: bool,
+    archived: bool,
+    attachments: Vec<ExportNoteAttachment>,
+}
+
+#[derive(Serialize)]
+struct ExportNoteAttachment {
+    name: String,
+    hash: String,
+    added_at: String,
+}
+
+/// A stored file, base64-encoded. Written once however many notes use it.
+#[derive(Serialize)]
+struct ExportBlob {
+    hash: String,
+    data: String,
 }
 
 #[derive(Serialize)]
@@ -19,38 +38,66 @@ struct ExportData {
     version: u32,
     exported_at: String,
     notes: Vec<ExportNote>,
+    attachments: Vec<ExportBlob>,
 }
 
 pub fn run(path: &str) -> Result<()> {
     let conn = db::get_db()?;
+    let count = export_to(&conn, path)?;
+    println!("Exported {} notes to {}", count, path);
+    Ok(())
+}
 
+/// Write every note outside the trash to `path` as JSON. Returns the number of notes written.
+pub fn export_to(conn: &Connection, path: &str) -> Result<usize> {
     let mut stmt = conn.prepare(
-        "SELECT title, note, tags, created_at, updated_at FROM notes ORDER BY id"
+        "SELECT title, note, tags, created_at, updated_at, pinned, archived, id FROM notes WHERE deleted_at IS NULL ORDER BY id"
     )?;
 
     let rows = stmt.query_map([], |row| {
         let tags_json: String = row.get(2)?;
         let tags: Vec<String> = serde_json::from_str(&tags_json).unwrap_or_default();
-        Ok(ExportNote {
+        let note = ExportNote {
             title: row.get(0)?,
             note: row.get(1)?,
             tags,
             created_at: row.get(3)?,
             updated_at: row.get(4)?,
-        })
+            pinned: row.get::<_, i64>(5)? != 0,
+            archived: row.get::<_, i64>(6)? != 0,
+            attachments: Vec::new(),
+        };
+        Ok((row.get::<_, i64>(7)?, note))
     })?;
 
     let mut notes = Vec::new();
+    let mut blobs: Vec<ExportBlob> = Vec::new();
     for row in rows {
-        notes.push(row?);
+        let (id, mut note) = row?;
+        for attachment in db::note_attachments(conn, id)? {
+            if !blobs.iter().any(|b| b.hash == attachment.hash) {
+                let data = db::attachment_data(conn, &attachment.hash)?.unwrap_or_default();
+                blobs.push(ExportBlob {
+                    hash: attachment.hash.clone(),
+                    data: base64::engine::general_purpose::STANDARD.encode(data),
+                });
+            }
+            note.attachments.push(ExportNoteAttachment {
+                name: attachment.name,
+                hash: attachment.hash,
+                added_at: attachment.added_at,
+            });
+        }
+        notes.push(note);
     }
 
     let count = notes.len();
 
     let export = ExportData {
-        version: 1,
+        version: 2,
         exported_at: current_timestamp(),
         notes,
+        attachments: blobs,
     };
 
     let json = serde_json::to_string_pretty(&export)?;
@@ -60,8 +107,7 @@ pub fn run(path: &str) -> Result<()> {
 
     file.write_all(json.as_bytes())?;
 
-    println!("Exported {} notes to {}", count, path);
-    Ok(())
+    Ok(count)
 }
 
 fn current_timestamp() -> String {
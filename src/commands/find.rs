@@ -1,20 +1,196 @@
 use anyhow::Result;
-use std::process::Command;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+};
 
-use crate::utils;
+use crate::config;
+use crate::db::{self, NoteEntry};
+use crate::tui;
+use crate::tui::theme::Theme;
 
-pub fn run(query: Option<&str>) -> Result<()> {
-    let script = utils::find_explorer_script()?;
+/// Exit code when the picker is cancelled, matching fzf.
+const EXIT_CANCELLED: i32 = 130;
 
-    let mut cmd = Command::new("bash");
-    cmd.arg(&script);
-    if let Some(q) = query {
-        cmd.arg(q);
+pub fn run(query: Option<&str>, tags: &[String], print: bool) -> Result<()> {
+    if !print {
+        return tui::run(query, tags);
     }
 
-    let status = cmd.status()?;
-    if !status.success() {
-        std::process::exit(status.code().unwrap_or(1));
+    let theme = Theme::load(&config::load()?)?;
+    let conn = db::get_db()?;
+    let notes: Vec<NoteEntry> = db::list_notes(&conn)?
+        .into_iter()
+        .filter(|n| !n.archived && n.has_any_tag(tags))
+        .collect();
+
+    match pick(notes, query.unwrap_or_default(), theme)? {
+        Some(title) => {
+            println!("{}", title);
+            Ok(())
+        }
+        None => std::process::exit(EXIT_CANCELLED),
+    }
+}
+
+struct Picker {
+    notes: Vec<NoteEntry>,
+    query: String,
+    filtered: Vec<usize>,
+    selected: usize,
+    theme: Theme,
+}
+
+impl Picker {
+    fn apply_filter(&mut self) {
+        let query = self.query.to_lowercase();
+        self.filtered = self
+            .notes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.matches_query(&query))
+            .map(|(i, _)| i)
+            .collect();
+        if self.selected >= self.filtered.len() {
+            self.selected = 0;
+        }
+    }
+
+    fn move_selection(&mut self, delta: i32) {
+        let len = self.filtered.len();
+        if len == 0 {
+            return;
+        }
+        self.selected = ((self.selected as i32 + delta).rem_euclid(len as i32)) as usize;
     }
-    Ok(())
+
+    fn selected_note(&self) -> Option<&NoteEntry> {
+        self.filtered.get(self.selected).map(|&i| &self.notes[i])
+    }
+}
+
+/// Interactive picker drawn on stderr so stdout stays free for the selected title.
+/// Returns `None` if the user cancels.
+fn pick(notes: Vec<NoteEntry>, query: &str, theme: Theme) -> Result<Option<String>> {
+    let mut picker = Picker {
+        notes,
+        query: query.to_string(),
+        filtered: Vec::new(),
+        selected: 0,
+        theme,
+    };
+    picker.apply_filter();
+
+    enable_raw_mode()?;
+    let mut stderr = std::io::stderr();
+    execute!(stderr, EnterAlternateScreen)?;
+    let backend = CrosstermBackend::new(stderr);
+    let mut terminal = Terminal::new(backend)?;
+
+    let result = pick_loop(&mut picker, &mut terminal);
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    result
+}
+
+fn pick_loop(
+    picker: &mut Picker,
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stderr>>,
+) -> Result<Option<String>> {
+    loop {
+        terminal.draw(|f| draw(f, picker))?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c') if ctrl => return Ok(None),
+            KeyCode::Enter => return Ok(picker.selected_note().map(|n| n.title.clone())),
+            KeyCode::Down => picker.move_selection(1),
+            KeyCode::Up => picker.move_selection(-1),
+            KeyCode::Char('n') if ctrl => picker.move_selection(1),
+            KeyCode::Char('p') if ctrl => picker.move_selection(-1),
+            KeyCode::Backspace => {
+                picker.query.pop();
+                picker.apply_filter();
+            }
+            KeyCode::Char(c) => {
+                picker.query.push(c);
+                picker.apply_filter();
+                picker.selected = 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn draw(f: &mut Frame, picker: &Picker) {
+    let theme = &picker.theme;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(1)])
+        .split(f.area());
+
+    let main_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(chunks[1]);
+
+    let input = Paragraph::new(format!("> {}", picker.query)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" Select a note ({}/{}) ", picker.filtered.len(), picker.notes.len()))
+            .border_style(theme.fg(theme.highlight)),
+    );
+    f.render_widget(input, chunks[0]);
+
+    let items: Vec<ListItem> = picker
+        .filtered
+        .iter()
+        .map(|&i| {
+            let note = &picker.notes[i];
+            let mut spans = vec![Span::raw(note.title.clone())];
+            if !note.tags.is_empty() {
+                spans.push(Span::styled(format!("  [{}]", note.tags.join(", ")), theme.muted()));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Notes"))
+        .highlight_style(theme.badge(theme.accent).add_modifier(Modifier::BOLD));
+
+    let mut state = ListState::default();
+    if !picker.filtered.is_empty() {
+        state.select(Some(picker.selected));
+    }
+    f.render_stateful_widget(list, main_chunks[0], &mut state);
+
+    let (title, lines) = match picker.selected_note() {
+        // The picker never asks for the passphrase
+        Some(note) if note.is_locked() => (
+            note.title.clone(),
+            vec![Line::from(Span::styled("This note is encrypted.", theme.fg(theme.danger)))],
+        ),
+        Some(note) => (note.title.clone(), theme.markdown(&note.note)),
+        None => ("Preview".to_string(), vec![Line::from("No matching notes")]),
+    };
+    let preview = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .wrap(Wrap { trim: false });
+    f.render_widget(preview, main_chunks[1]);
 }
@@ -1,8 +1,10 @@
-use anyhow::{Context, Result};
-use rusqlite::params;
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use rusqlite::{params, Connection};
 use serde::Deserialize;
 use std::fs;
 
+use crate::attachments;
 use crate::db;
 
 #[derive(Deserialize)]
@@ -12,6 +14,25 @@ struct ImportNote {
     tags: Vec<String>,
     created_at: String,
     updated_at: String,
+    #[serde(default)]
+    pinned: bool,
+    #[serde(default)]
+    archived: bool,
+    #[serde(default)]
+    attachments: Vec<ImportNoteAttachment>,
+}
+
+#[derive(Deserialize)]
+struct ImportNoteAttachment {
+    name: String,
+    hash: String,
+    added_at: String,
+}
+
+#[derive(Deserialize)]
+struct ImportBlob {
+    hash: String,
+    data: String,
 }
 
 #[derive(Deserialize)]
@@ -21,20 +42,56 @@ struct ImportData {
     #[allow(dead_code)]
     exported_at: String,
     notes: Vec<ImportNote>,
+    /// Added in version 2
+    #[serde(default)]
+    attachments: Vec<ImportBlob>,
 }
 
 pub fn run(path: &str, overwrite: bool) -> Result<()> {
-    let conn = db::get_db()?;
+    let mut conn = db::get_db()?;
+    let (imported, skipped) = import_from(&mut conn, path, overwrite)?;
+
+    if overwrite {
+        println!("Cleared existing notes.");
+        println!("Imported {} notes from {}", imported, path);
+    } else {
+        println!("Imported {} notes, skipped {} duplicates from {}", imported, skipped, path);
+    }
 
+    Ok(())
+}
+
+/// Read notes exported to `path`. Returns the number imported and the number
+/// skipped because a note with that title already exists.
+pub fn import_from(conn: &mut Connection, path: &str, overwrite: bool) -> Result<(usize, usize)> {
     let contents = fs::read_to_string(path)
         .with_context(|| format!("Failed to read file: {}", path))?;
 
     let data: ImportData = serde_json::from_str(&contents)
         .with_context(|| "Failed to parse export file. Is it a valid scrap export?")?;
 
+    // Check every file before changing anything
+    let mut blobs = Vec::new();
+    for blob in &data.attachments {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(&blob.data)
+            .with_context(|| format!("Attachment {} is not valid base64", blob.hash))?;
+        if attachments::hash(&bytes) != blob.hash {
+            bail!("Attachment {} is corrupt: its contents don't match its hash.", blob.hash);
+        }
+        blobs.push((&blob.hash, bytes));
+    }
+
+    // All or nothing, so a failure partway never leaves a cleared or half-imported database
+    let tx = conn.transaction()?;
     if overwrite {
-        conn.execute("DELETE FROM notes", [])?;
-        println!("Cleared existing notes.");
+        tx.execute("DELETE FROM notes", [])?;
+    }
+    for (hash, bytes) in &blobs {
+        tx.execute(
+            "INSERT OR IGNORE INTO attachments (hash, data) VALUES (?1, ?2)",
+            params![hash, bytes],
+        )?;
     }
 
     let mut imported = 0;
@@ -44,15 +101,16 @@ pub fn run(path: &str, overwrite: bool) -> Result<()> {
         let tags_json = serde_json::to_string(&note.tags)?;
 
         if overwrite {
-            conn.execute(
-                "INSERT INTO notes (title, note, tags, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)",
-                params![note.title, note.note, tags_json, note.created_at, note.updated_at],
+            tx.execute(
+                "INSERT INTO notes (title, note, tags, created_at, updated_at, pinned, archived) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![note.title, note.note, tags_json, note.created_at, note.updated_at, note.pinned, note.archived],
             )?;
+            link_attachments(&tx, &note.attachments)?;
             imported += 1;
         } else {
             // Check if note with this title already exists
-            let exists: bool = conn.query_row(
-                "SELECT 1 FROM notes WHERE title = ?1",
+            let exists: bool = tx.query_row(
+                "SELECT 1 FROM notes WHERE title = ?1 AND deleted_at IS NULL",
                 params![note.title],
                 |_| Ok(true),
             ).unwrap_or(false);
@@ -60,20 +118,73 @@ pub fn run(path: &str, overwrite: bool) -> Result<()> {
             if exists {
                 skipped += 1;
             } else {
-                conn.execute(
-                    "INSERT INTO notes (title, note, tags, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)",
-                    params![note.title, note.note, tags_json, note.created_at, note.updated_at],
+                tx.execute(
+                    "INSERT INTO notes (title, note, tags, created_at, updated_at, pinned, archived) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![note.title, note.note, tags_json, note.created_at, note.updated_at, note.pinned, note.archived],
                 )?;
+                link_attachments(&tx, &note.attachments)?;
                 imported += 1;
             }
         }
     }
 
-    if overwrite {
-        println!("Imported {} notes from {}", imported, path);
-    } else {
-        println!("Imported {} notes, skipped {} duplicates from {}", imported, skipped, path);
-    }
+    // Files whose notes were all skipped
+    tx.execute(
+        "DELETE FROM attachments WHERE hash NOT IN (SELECT hash FROM note_attachments)",
+        [],
+    )?;
+    tx.commit()?;
+    Ok((imported, skipped))
+}
 
+/// Attach the imported files to the note just inserted. Files missing from
+/// the export are left out.
+fn link_attachments(conn: &Connection, attachments: &[ImportNoteAttachment]) -> Result<()> {
+    let id = conn.last_insert_rowid();
+    for attachment in attachments {
+        conn.execute(
+            "INSERT OR IGNORE INTO note_attachments (note_id, hash, name, added_at)
+             SELECT ?1, hash, ?3, ?4 FROM attachments WHERE hash = ?2",
+            params![id, attachment.hash, attachment.name, attachment.added_at],
+        )?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::export;
+
+    #[test]
+    fn test_round_trip_with_attachments() {
+        let source = db::open_in_memory().unwrap();
+        let id = db::insert_note(&source, "cat", "", &[]).unwrap();
+        let cat = db::add_attachment(&source, id, "cat.png", b"meow").unwrap();
+        let id = db::insert_note(&source, "dog", "", &[]).unwrap();
+        let dog = db::add_attachment(&source, id, "dog.png", b"woof").unwrap();
+        let path = std::env::temp_dir().join(format!("scrap-import-test-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert_eq!(export::export_to(&source, path).unwrap(), 2);
+
+        // "dog" is already there, so its file is left behind as an orphan and cleaned up
+        let mut dest = db::open_in_memory().unwrap();
+        db::insert_note(&dest, "dog", "mine\n", &[]).unwrap();
+        assert_eq!(import_from(&mut dest, path, false).unwrap(), (1, 1));
+        let (id, _, _) = db::get_note(&dest, "cat").unwrap().unwrap();
+        let attached = db::note_attachments(&dest, id).unwrap();
+        assert_eq!(attached.len(), 1);
+        assert_eq!((attached[0].name.as_str(), attached[0].hash.as_str()), ("cat.png", cat.as_str()));
+        assert_eq!(db::attachment_data(&dest, &cat).unwrap().unwrap(), b"meow");
+        assert!(db::attachment_data(&dest, &dog).unwrap().is_none());
+
+        // Overwriting brings everything across
+        assert_eq!(import_from(&mut dest, path, true).unwrap(), (2, 0));
+        let (id, note, _) = db::get_note(&dest, "dog").unwrap().unwrap();
+        assert_eq!(note, "");
+        assert_eq!(db::note_attachments(&dest, id).unwrap()[0].hash, dog);
+        assert_eq!(db::attachment_data(&dest, &dog).unwrap().unwrap(), b"woof");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,19 +1,24 @@
 use anyhow::Result;
 
 use crate::db;
+use crate::output::{self, OutputFormat};
+use crate::sort::{self, SortKey};
 
-pub fn run(tag: Option<&str>) -> Result<()> {
+pub fn run(
+    tag: Option<&str>,
+    include_archived: bool,
+    sort_key: SortKey,
+    reverse: bool,
+    format: OutputFormat,
+) -> Result<()> {
     let conn = db::get_db()?;
-    let notes = db::list_notes(&conn)?;
+    let mut notes = db::list_notes(&conn)?;
 
-    for note in notes {
-        if let Some(filter) = tag {
-            if !note.tags.iter().any(|t| t == filter) {
-                continue;
-            }
-        }
-        println!("{}", note.title);
+    notes.retain(|note| include_archived || !note.archived);
+    if let Some(filter) = tag {
+        notes.retain(|note| note.tags.iter().any(|t| t == filter));
     }
+    sort::sort_notes(&mut notes, sort_key, reverse);
 
-    Ok(())
+    output::print_notes(format, &notes)
 }
@@ -0,0 +1,123 @@
+use anyhow::{bail, Result};
+
+use crate::db::{self, NoteEntry};
+use crate::output;
+use crate::utils;
+
+pub fn run(names: &[String], into: &str, archive: bool) -> Result<()> {
+    utils::validate_name(into)?;
+
+    let mut conn = db::get_db()?;
+    let mut sources: Vec<NoteEntry> = Vec::new();
+    for name in names {
+        if name == into {
+            continue;
+        }
+        let note = db::get_note_entry(&conn, name)?.ok_or_else(|| output::not_found(name))?;
+        if note.encrypted {
+            bail!("Note '{}' is encrypted; decrypt it before merging.", name);
+        }
+        if !sources.iter().any(|s| s.id == note.id) {
+            sources.push(note);
+        }
+    }
+    if sources.is_empty() {
+        bail!("Nothing to merge into '{}'.", into);
+    }
+
+    // Merging into an existing note appends the sources to it
+    let target = db::get_note_entry(&conn, into)?;
+    if target.as_ref().is_some_and(|t| t.encrypted) {
+        bail!("Note '{}' is encrypted; decrypt it before merging into it.", into);
+    }
+    let (contents, tags) = combine(target.as_ref(), &sources);
+
+    // All or nothing, so a failure partway never leaves notes both merged and in place
+    let tx = conn.transaction()?;
+    let target_id = match &target {
+        Some(target) => {
+            db::update_note(&tx, target.id, &contents)?;
+            db::update_tags(&tx, target.id, &tags)?;
+            db::mark_summary_stale(&tx, target.id)?;
+            target.id
+        }
+        None => db::insert_note(&tx, into, &contents, &tags)?,
+    };
+    for source in &sources {
+        db::copy_attachments(&tx, source.id, target_id, &source.note)?;
+        if archive {
+            db::set_archived(&tx, source.id, true)?;
+        } else {
+            db::delete_note_by_id(&tx, source.id)?;
+        }
+    }
+    tx.commit()?;
+
+    let fate = if archive { "archived" } else { "moved to trash" };
+    println!("Merged {} notes into '{}'; the originals were {}.", sources.len(), into, fate);
+    Ok(())
+}
+
+/// The merged note's contents and tags: `target`'s, then each source under a
+/// heading with its title, with the tags of all of them.
+fn combine(target: Option<&NoteEntry>, sources: &[NoteEntry]) -> (String, Vec<String>) {
+    let mut contents = target.map(|t| t.note.clone()).unwrap_or_default();
+    let mut tags = target.map(|t| t.tags.clone()).unwrap_or_default();
+    for source in sources {
+        if !contents.trim().is_empty() {
+            if !contents.ends_with('\n') {
+                contents.push('\n');
+            }
+            contents.push('\n');
+        }
+        contents.push_str(&format!("# {}\n", source.title));
+        let body = source.note.trim_end();
+        if !body.is_empty() {
+            contents.push_str(&format!("\n{}\n", body));
+        }
+        for tag in &source.tags {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+    }
+    (contents, tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(title: &str, note: &str, tags: &[&str]) -> NoteEntry {
+        NoteEntry {
+            title: title.to_string(),
+            note: note.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_combine_into_new_note() {
+        let sources = [note("a", "first\n", &["x", "y"]), note("b", "second", &["y", "z"])];
+        let (contents, tags) = combine(None, &sources);
+        assert_eq!(contents, "# a\n\nfirst\n\n# b\n\nsecond\n");
+        assert_eq!(tags, ["x", "y", "z"]);
+    }
+
+    #[test]
+    fn test_combine_into_existing_note() {
+        let target = note("t", "intro", &["z"]);
+        let sources = [note("a", "", &["z", "x"]), note("b", "body\n\n", &[])];
+        let (contents, tags) = combine(Some(&target), &sources);
+        assert_eq!(contents, "intro\n\n# a\n\n# b\n\nbody\n");
+        assert_eq!(tags, ["z", "x"]);
+    }
+
+    #[test]
+    fn test_combine_into_empty_note() {
+        let target = note("t", "\n", &[]);
+        let (contents, _) = combine(Some(&target), &[note("a", "text", &[])]);
+        assert_eq!(contents, "\n# a\n\ntext\n");
+    }
+}
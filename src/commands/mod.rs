@@ -1,10 +1,28 @@
 pub mod add;
 pub mod append;
+pub mod archive;
+pub mod attach;
+pub mod attachments;
+pub mod capture;
+pub mod clip;
 pub mod delete;
+pub mod due;
 pub mod edit_tag;
+pub mod encrypt;
 pub mod export;
+pub mod find;
 pub mod import;
 pub mod list;
+pub mod merge;
 pub mod open;
+pub mod paste;
+pub mod pin;
 pub mod read;
+pub mod recover;
+pub mod remind;
+pub mod search;
+pub mod split;
+pub mod tags;
+pub mod tasks;
+pub mod trash;
 pub mod write;
@@ -1,6 +1,9 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 use crate::db;
+use crate::merge;
+use crate::output;
+use crate::session::{self, Target};
 use crate::utils;
 
 pub fn run(name: &str) -> Result<()> {
@@ -8,17 +11,66 @@ pub fn run(name: &str) -> Result<()> {
 
     let conn = db::get_db()?;
 
-    let (id, contents, _tags) = db::get_note(&conn, name)?
-        .ok_or_else(|| anyhow::anyhow!("Note '{}' not found.", name))?;
+    let note = db::get_note_entry(&conn, name)?.ok_or_else(|| output::not_found(name))?;
+    if note.encrypted {
+        bail!(
+            "Note '{}' is encrypted. Edit it with the TUI's built-in editor (e), which never writes it to disk, or decrypt it first.",
+            name
+        );
+    }
 
-    let new_contents = utils::get_user_input_with_contents(name, &contents)?;
+    // The version the edit is based on; moves forward after a merge
+    let mut target = Target::existing(&note);
+    let (mut new_contents, mut edit) = session::edit(&target, &target.base)?;
 
-    if new_contents == contents {
-        println!("No changes made.");
-        return Ok(());
-    }
+    let message = loop {
+        if new_contents == target.base {
+            break "No changes made.".to_string();
+        }
+
+        if db::update_note_if_unchanged(&conn, note.id, &new_contents, target.revision)? {
+            break format!("Note '{}' updated.", name);
+        }
+
+        let Some(theirs) = db::get_note_by_id(&conn, note.id)? else {
+            let title = db::copy_title(&conn, name)?;
+            db::insert_note(&conn, &title, &new_contents, &note.tags)?;
+            break format!("Note '{}' was deleted while you were editing. Saved your version as '{}'.", name, title);
+        };
+
+        eprintln!("Note '{}' was changed by another process while you were editing.", name);
+        let choice = utils::prompt_choice("[m]erge, save as [c]opy, [o]verwrite, or [d]iscard your changes?", &['m', 'c', 'o', 'd'])?;
+        match choice {
+            'm' => {
+                let merged = merge::merge3(&target.base, &new_contents, &theirs.note);
+                target = Target::existing(&theirs);
+                if merged.conflicts == 0 {
+                    println!("Merged with the other changes.");
+                    new_contents = merged.text;
+                } else {
+                    eprintln!(
+                        "{} conflicting section(s) are marked with <<<<<<< and >>>>>>>. Resolve them and save.",
+                        merged.conflicts
+                    );
+                    let (resolved, next) = session::edit(&target, &merged.text)?;
+                    std::mem::replace(&mut edit, next).finish();
+                    new_contents = resolved;
+                }
+            }
+            'c' => {
+                let title = db::copy_title(&conn, name)?;
+                db::insert_note(&conn, &title, &new_contents, &theirs.tags)?;
+                break format!("Saved your version as '{}'.", title);
+            }
+            'o' => {
+                db::update_note(&conn, note.id, &new_contents)?;
+                break format!("Note '{}' updated.", name);
+            }
+            _ => break "Discarded your changes.".to_string(),
+        }
+    };
 
-    db::update_note(&conn, id, &new_contents)?;
-    println!("Note '{}' updated.", name);
+    edit.finish();
+    println!("{}", message);
     Ok(())
 }
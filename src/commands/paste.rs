@@ -0,0 +1,21 @@
+use anyhow::{bail, Result};
+
+use crate::clipboard;
+use crate::crypto;
+use crate::db;
+use crate::output;
+
+pub fn run(name: &str) -> Result<()> {
+    let conn = db::get_db()?;
+    let (id, contents, _tags) = db::get_note(&conn, name)?.ok_or_else(|| output::not_found(name))?;
+
+    let text = clipboard::paste()?;
+    if text.is_empty() {
+        bail!("The clipboard is empty.");
+    }
+    crypto::unlock(&contents, name)?;
+    db::append_note(&conn, id, &text)?;
+    db::mark_summary_stale(&conn, id)?;
+    println!("Pasted {} lines into '{}'.", text.lines().count(), name);
+    Ok(())
+}
@@ -0,0 +1,18 @@
+use anyhow::Result;
+
+use crate::db;
+use crate::output;
+
+pub fn run(name: &str, pinned: bool) -> Result<()> {
+    let conn = db::get_db()?;
+    let (id, _tags) = db::get_tags_and_id(&conn, name)?
+        .ok_or_else(|| output::not_found(name))?;
+
+    db::set_pinned(&conn, id, pinned)?;
+    if pinned {
+        println!("Note '{}' pinned.", name);
+    } else {
+        println!("Note '{}' unpinned.", name);
+    }
+    Ok(())
+}
@@ -1,15 +1,17 @@
-use anyhow::{bail, Result};
+use anyhow::Result;
 
+use crate::crypto;
 use crate::db;
+use crate::output::{self, OutputFormat};
 
-pub fn run(name: &str) -> Result<()> {
+pub fn run(name: &str, format: OutputFormat) -> Result<()> {
     let conn = db::get_db()?;
 
-    match db::get_note(&conn, name)? {
-        Some((_id, content, _tags)) => {
-            print!("{content}");
-            Ok(())
+    match db::get_note_entry(&conn, name)? {
+        Some(mut note) => {
+            note.note = crypto::unlock(&note.note, &note.title)?;
+            output::print_note(format, &note)
         }
-        None => bail!("Note '{}' not found", name),
+        None => Err(output::not_found(name)),
     }
 }
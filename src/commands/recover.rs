@@ -0,0 +1,51 @@
+use anyhow::{bail, Result};
+
+use crate::db;
+use crate::session::{self, Orphan};
+use crate::utils;
+
+pub fn list() -> Result<()> {
+    let orphans = session::orphans()?;
+    if orphans.is_empty() {
+        println!("No unsaved edits to recover.");
+        return Ok(());
+    }
+
+    for (i, orphan) in orphans.iter().enumerate() {
+        println!(
+            "{}  {}  ({}, {}, {} lines)",
+            i + 1,
+            orphan.title,
+            orphan.kind(),
+            utils::relative_secs(orphan.modified),
+            orphan.contents.lines().count()
+        );
+    }
+    Ok(())
+}
+
+pub fn restore(number: usize) -> Result<()> {
+    let orphan = take(number)?;
+    let conn = db::get_db()?;
+    println!("{}.", session::restore(&conn, orphan)?);
+    Ok(())
+}
+
+pub fn discard(number: usize) -> Result<()> {
+    let orphan = take(number)?;
+    println!("Discarded unsaved edit of '{}'.", orphan.title);
+    session::discard(orphan);
+    Ok(())
+}
+
+/// The edit numbered `number` in `scrap recover list`.
+fn take(number: usize) -> Result<Orphan> {
+    let mut orphans = session::orphans()?;
+    if number == 0 || number > orphans.len() {
+        bail!(
+            "No unsaved edit number {}. Run 'scrap recover list' to see them.",
+            number
+        );
+    }
+    Ok(orphans.swap_remove(number - 1))
+}
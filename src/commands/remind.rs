@@ -0,0 +1,27 @@
+use anyhow::Result;
+
+use crate::db;
+use crate::output;
+use crate::remind;
+use crate::utils;
+
+pub fn run(name: &str, when: &[String], clear: bool) -> Result<()> {
+    let conn = db::get_db()?;
+    let (id, _tags) = db::get_tags_and_id(&conn, name)?
+        .ok_or_else(|| output::not_found(name))?;
+
+    if clear {
+        db::set_reminder(&conn, id, None)?;
+        println!("Reminder for '{}' cleared.", name);
+        return Ok(());
+    }
+    let at = remind::resolve(&conn, &when.join(" "))?;
+    db::set_reminder(&conn, id, Some(&at))?;
+    println!(
+        "Will remind you about '{}' at {} ({}).",
+        name,
+        db::local_time(&conn, &at)?,
+        utils::relative_time(&at)
+    );
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use anyhow::Result;
+
+use crate::db;
+use crate::output::{self, OutputFormat};
+
+pub fn run(
+    query: &str,
+    tag: Option<&str>,
+    include_archived: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let conn = db::get_db()?;
+    let mut notes = db::list_notes(&conn)?;
+
+    let query = query.to_lowercase();
+    let tags: Vec<String> = tag.map(|t| t.to_string()).into_iter().collect();
+    notes.retain(|note| {
+        (include_archived || !note.archived)
+            && note.has_any_tag(&tags)
+            && note.matches_query(&query)
+    });
+
+    output::print_notes(format, &notes)
+}
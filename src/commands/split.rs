@@ -0,0 +1,129 @@
+use anyhow::{bail, Result};
+
+use crate::db;
+use crate::markdown;
+use crate::output;
+use crate::utils;
+
+/// A section to split out: the heading's text and the rows of its body.
+struct Section {
+    heading: String,
+    start: usize,
+    end: usize,
+}
+
+pub fn run(name: &str, level: usize, keep: bool) -> Result<()> {
+    let mut conn = db::get_db()?;
+    let note = db::get_note_entry(&conn, name)?.ok_or_else(|| output::not_found(name))?;
+    if note.encrypted {
+        bail!("Note '{}' is encrypted; decrypt it before splitting.", name);
+    }
+
+    let lines: Vec<String> = note.note.lines().map(|l| l.to_string()).collect();
+    let sections = sections(&lines, level);
+    if sections.is_empty() {
+        bail!("'{}' has no level {} headings to split on.", name, level);
+    }
+
+    // All or nothing, so a failure partway never leaves a note half split
+    let tx = conn.transaction()?;
+
+    // Text outside the sections stays behind in the original
+    let mut rest: Vec<&str> = Vec::new();
+    let mut next = 0;
+    let mut titles = Vec::new();
+    for section in &sections {
+        rest.extend(lines[next..section.start].iter().map(|l| l.as_str()));
+        next = section.end;
+
+        let body = lines[section.start + 1..section.end].join("\n");
+        let body = body.trim_matches('\n');
+        let contents = if body.is_empty() { String::new() } else { format!("{}\n", body) };
+        let title = utils::title_from(&section.heading).unwrap_or_else(|| "Untitled".to_string());
+        let title = db::unique_title(&tx, &title)?;
+        let id = db::insert_note(&tx, &title, &contents, &note.tags)?;
+        db::copy_attachments(&tx, note.id, id, &contents)?;
+        titles.push(title);
+    }
+    rest.extend(lines[next..].iter().map(|l| l.as_str()));
+
+    let rest = rest.join("\n");
+    let rest = rest.trim_end();
+    let fate = if keep {
+        "kept as it was"
+    } else if rest.trim().is_empty() {
+        db::delete_note_by_id(&tx, note.id)?;
+        "moved to trash"
+    } else {
+        db::update_note(&tx, note.id, &format!("{}\n", rest))?;
+        db::mark_summary_stale(&tx, note.id)?;
+        "left with the text outside those sections"
+    };
+    tx.commit()?;
+
+    println!("Split '{}' into {} notes; the original was {}:", name, titles.len(), fate);
+    for title in titles {
+        println!("  {}", title);
+    }
+    Ok(())
+}
+
+/// Sections under headings of `level`, each running until the next heading
+/// of the same or a higher level. Deeper headings stay inside.
+fn sections(lines: &[String], level: usize) -> Vec<Section> {
+    let outline = markdown::outline(lines);
+    let mut sections = Vec::new();
+    for (i, (row, heading_level, text)) in outline.iter().enumerate() {
+        if *heading_level != level {
+            continue;
+        }
+        let end = outline[i + 1..]
+            .iter()
+            .find(|(_, l, _)| *l <= level)
+            .map_or(lines.len(), |(r, _, _)| *r);
+        sections.push(Section {
+            heading: text.clone(),
+            start: *row,
+            end,
+        });
+    }
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(|l| l.to_string()).collect()
+    }
+
+    fn spans(lines: &[String], level: usize) -> Vec<(String, usize, usize)> {
+        sections(lines, level).into_iter().map(|s| (s.heading, s.start, s.end)).collect()
+    }
+
+    #[test]
+    fn test_text_before_first_heading_is_left_out() {
+        let lines = lines("intro\n\n# One\na\n# Two\nb");
+        assert_eq!(spans(&lines, 1), [("One".to_string(), 2, 4), ("Two".to_string(), 4, 6)]);
+    }
+
+    #[test]
+    fn test_nested_levels() {
+        let lines = lines("# Top\n## A\na\n### Deep\nd\n## B\nb\n# Next\n## C");
+        // Deeper headings stay inside; a higher one ends the section
+        assert_eq!(
+            spans(&lines, 2),
+            [("A".to_string(), 1, 5), ("B".to_string(), 5, 7), ("C".to_string(), 8, 9)]
+        );
+        assert_eq!(spans(&lines, 1), [("Top".to_string(), 0, 7), ("Next".to_string(), 7, 9)]);
+        assert!(spans(&lines, 4).is_empty());
+    }
+
+    #[test]
+    fn test_no_headings() {
+        assert!(spans(&lines("just text\n#not a heading"), 1).is_empty());
+        assert!(spans(&lines("```\n# in code\n```"), 1).is_empty());
+        assert!(spans(&[], 1).is_empty());
+    }
+}
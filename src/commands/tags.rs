@@ -0,0 +1,25 @@
+use anyhow::Result;
+use std::collections::HashMap;
+
+use crate::db;
+use crate::output::{self, OutputFormat, TagCount};
+
+pub fn run(format: OutputFormat) -> Result<()> {
+    let conn = db::get_db()?;
+    let notes = db::list_notes(&conn)?;
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for note in notes.iter().filter(|n| !n.archived) {
+        for tag in &note.tags {
+            *counts.entry(tag.clone()).or_insert(0) += 1;
+        }
+    }
+
+    let mut tags: Vec<TagCount> = counts
+        .into_iter()
+        .map(|(name, count)| TagCount { name, count })
+        .collect();
+    tags.sort_by(|a, b| b.count.cmp(&a.count).then(a.name.cmp(&b.name)));
+
+    output::print_tags(format, &tags)
+}
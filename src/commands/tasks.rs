@@ -0,0 +1,32 @@
+use anyhow::Result;
+
+use crate::db;
+use crate::output::{self, OutputFormat};
+
+pub fn run(
+    open: bool,
+    done: bool,
+    overdue: bool,
+    tag: Option<&str>,
+    include_archived: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let conn = db::get_db()?;
+    let mut tasks = db::list_tasks(&conn)?;
+
+    tasks.retain(|task| include_archived || !task.archived);
+    if let Some(filter) = tag {
+        tasks.retain(|task| task.tags.iter().any(|t| t == filter));
+    }
+    if open {
+        tasks.retain(|task| !task.done);
+    }
+    if done {
+        tasks.retain(|task| task.done);
+    }
+    if overdue {
+        tasks.retain(|task| task.overdue);
+    }
+
+    output::print_tasks(format, &tasks)
+}
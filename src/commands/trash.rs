@@ -0,0 +1,54 @@
+use anyhow::{bail, Result};
+
+use crate::config;
+use crate::db;
+use crate::output::{self, OutputFormat};
+
+pub fn list(format: OutputFormat) -> Result<()> {
+    let conn = db::get_db()?;
+    db::purge_trash(&conn, config::load()?.trash_retention_days)?;
+    let notes = db::list_trash(&conn)?;
+
+    match format {
+        OutputFormat::Text => {
+            for note in &notes {
+                println!(
+                    "{}  (deleted {})",
+                    note.title,
+                    note.deleted_at.as_deref().unwrap_or_default()
+                );
+            }
+            Ok(())
+        }
+        _ => output::print_notes(format, &notes),
+    }
+}
+
+pub fn restore(name: &str) -> Result<()> {
+    let conn = db::get_db()?;
+    db::purge_trash(&conn, config::load()?.trash_retention_days)?;
+
+    // Most recently deleted note with this title
+    let note = db::list_trash(&conn)?
+        .into_iter()
+        .find(|n| n.title == name)
+        .ok_or_else(|| output::not_found(name))?;
+
+    if db::get_note(&conn, name)?.is_some() {
+        bail!(
+            "A note named '{}' already exists. Rename or delete it before restoring.",
+            name
+        );
+    }
+
+    db::restore_note_by_id(&conn, note.id)?;
+    println!("Note '{}' restored.", name);
+    Ok(())
+}
+
+pub fn empty() -> Result<()> {
+    let conn = db::get_db()?;
+    let count = db::empty_trash(&conn)?;
+    println!("Permanently deleted {} notes.", count);
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use anyhow::{Context, Result};
 use std::io::Read;
 
+use crate::crypto;
 use crate::db;
 
 pub fn run(name: &str, tags: &[String]) -> Result<()> {
@@ -12,7 +13,9 @@ pub fn run(name: &str, tags: &[String]) -> Result<()> {
     let conn = db::get_db()?;
 
     match db::get_note(&conn, name)? {
-        Some((id, _existing, _tags)) => {
+        Some((id, existing, _tags)) => {
+            // Only replace an encrypted note given its passphrase
+            crypto::unlock(&existing, name)?;
             db::update_note(&conn, id, &content)?;
             if !tags.is_empty() {
                 db::update_tags(&conn, id, tags)?;
@@ -0,0 +1,65 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// User configuration loaded from `~/.scrap/config.json`. Every field is optional.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Days to keep notes in the trash before purging them. Zero keeps them forever.
+    pub trash_retention_days: u32,
+    /// Show metadata columns in the TUI note list at startup.
+    pub note_list_details: bool,
+    /// Columns shown next to the title when note list details are on.
+    pub note_list_columns: Vec<NoteColumn>,
+    /// Show a metadata header above the note in the TUI preview.
+    pub preview_metadata: bool,
+    /// TUI key bindings per mode: key sequence to action name.
+    pub keymap: BTreeMap<String, BTreeMap<String, String>>,
+    /// TUI color theme: a built-in name or one defined under `themes`.
+    pub theme: String,
+    /// User themes: color slot to color, plus an optional built-in `base`.
+    pub themes: BTreeMap<String, BTreeMap<String, String>>,
+    /// Capture the mouse in the TUI. Turn off to use the terminal's own text selection.
+    pub mouse: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteColumn {
+    Tags,
+    Updated,
+    Words,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            trash_retention_days: 30,
+            note_list_details: false,
+            note_list_columns: vec![NoteColumn::Tags, NoteColumn::Updated, NoteColumn::Words],
+            preview_metadata: true,
+            keymap: BTreeMap::new(),
+            theme: "dark".to_string(),
+            themes: BTreeMap::new(),
+            mouse: true,
+        }
+    }
+}
+
+fn config_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Could not determine home directory")?;
+    Ok(home.join(".scrap").join("config.json"))
+}
+
+pub fn load() -> Result<Config> {
+    let path = config_path()?;
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read config: {}", path.display()))?;
+    serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse config: {}", path.display()))
+}
@@ -0,0 +1,285 @@
+//! Encrypted notes. An encrypted note's body is stored as `PREFIX` followed by
+//! the base64 of a salt, a nonce and the ChaCha20-Poly1305 ciphertext, with the
+//! key derived from a passphrase by PBKDF2-HMAC-SHA256. Titles and tags stay
+//! readable. The ciphertext is authenticated together with the note's title,
+//! so a body moved to another note no longer decrypts, and renaming an
+//! encrypted note encrypts its body again.
+//!
+//! The passphrase is asked for once per run of scrap and kept in memory only,
+//! so the TUI asks once per session. Notes read through `db` while it is known
+//! come back decrypted, and writes to an encrypted note are encrypted again.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::Mutex;
+
+pub const PREFIX: &str = "scrap-encrypted:v1:";
+
+/// Environment variable to give the passphrase without a prompt, for scripts.
+pub const PASSPHRASE_ENV: &str = "SCRAP_PASSPHRASE";
+
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const ITERATIONS: u32 = 600_000;
+
+/// The passphrase of this run, with keys derived from it so far by salt.
+/// Everything encrypted during the run shares one salt, so saving doesn't
+/// pay for key derivation again.
+struct Session {
+    passphrase: String,
+    salt: [u8; SALT_LEN],
+    keys: HashMap<[u8; SALT_LEN], [u8; KEY_LEN]>,
+}
+
+impl Session {
+    fn key(&mut self, salt: [u8; SALT_LEN]) -> LessSafeKey {
+        let passphrase = &self.passphrase;
+        let key = self.keys.entry(salt).or_insert_with(|| {
+            let mut key = [0u8; KEY_LEN];
+            let iterations = NonZeroU32::new(ITERATIONS).expect("iterations are not zero");
+            ring::pbkdf2::derive(ring::pbkdf2::PBKDF2_HMAC_SHA256, iterations, &salt, passphrase.as_bytes(), &mut key);
+            key
+        });
+        LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, key).expect("key has the right length"))
+    }
+}
+
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+pub fn is_encrypted(text: &str) -> bool {
+    text.starts_with(PREFIX)
+}
+
+/// True once a passphrase has been given in this run.
+pub fn is_unlocked() -> bool {
+    SESSION.lock().is_ok_and(|s| s.is_some())
+}
+
+/// Use `passphrase` for the rest of this run.
+pub fn set_passphrase(passphrase: &str) -> Result<()> {
+    if passphrase.is_empty() {
+        bail!("The passphrase can't be empty.");
+    }
+    let mut salt = [0u8; SALT_LEN];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| anyhow!("Couldn't generate a salt"))?;
+    *SESSION.lock().map_err(|_| anyhow!("Passphrase cache poisoned"))? = Some(Session {
+        passphrase: passphrase.to_string(),
+        salt,
+        keys: HashMap::new(),
+    });
+    Ok(())
+}
+
+/// Forget the passphrase.
+pub fn lock() {
+    if let Ok(mut session) = SESSION.lock() {
+        *session = None;
+    }
+}
+
+/// Make `passphrase` the one for this run if it decrypts `text`, the body of
+/// note `title`, and return the plaintext. The previous passphrase is kept if
+/// it doesn't.
+pub fn try_passphrase(passphrase: &str, text: &str, title: &str) -> Result<String> {
+    if passphrase.is_empty() {
+        bail!("The passphrase can't be empty.");
+    }
+    let previous = SESSION.lock().map_err(|_| anyhow!("Passphrase cache poisoned"))?.take();
+    set_passphrase(passphrase)?;
+    match open(text, title) {
+        Some(plaintext) => {
+            // Encrypt with the key just derived rather than deriving another
+            if let (Some(salt), Ok(Some(session))) = (salt_of(text), SESSION.lock().as_deref_mut()) {
+                session.salt = salt;
+            }
+            Ok(plaintext)
+        }
+        None => {
+            *SESSION.lock().map_err(|_| anyhow!("Passphrase cache poisoned"))? = previous;
+            bail!("Wrong passphrase, or the encrypted text was tampered with.")
+        }
+    }
+}
+
+fn decode(text: &str) -> Option<Vec<u8>> {
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(text.strip_prefix(PREFIX)?)
+        .ok()?;
+    (data.len() >= SALT_LEN + NONCE_LEN).then_some(data)
+}
+
+fn salt_of(text: &str) -> Option<[u8; SALT_LEN]> {
+    decode(text)?[..SALT_LEN].try_into().ok()
+}
+
+/// The additional authenticated data for the body of note `title`.
+fn aad(title: &str) -> Vec<u8> {
+    format!("{}{}", PREFIX, title).into_bytes()
+}
+
+/// `text`, the body of note `title`, decrypted with this run's passphrase. None
+/// if there is no passphrase yet, it's the wrong one, `text` isn't encrypted
+/// or it was encrypted for another note.
+pub fn open(text: &str, title: &str) -> Option<String> {
+    let data = decode(text)?;
+    let (salt, rest) = data.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let key = SESSION.lock().ok()?.as_mut()?.key(salt.try_into().ok()?);
+
+    let mut in_out = ciphertext.to_vec();
+    let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+    let plaintext = key.open_in_place(nonce, Aad::from(aad(title)), &mut in_out).ok()?;
+    String::from_utf8(plaintext.to_vec()).ok()
+}
+
+/// `text` encrypted with this run's passphrase as the body of note `title`.
+pub fn seal(text: &str, title: &str) -> Result<String> {
+    let mut session = SESSION.lock().map_err(|_| anyhow!("Passphrase cache poisoned"))?;
+    let session = session
+        .as_mut()
+        .context("The note is encrypted and scrap doesn't have its passphrase yet")?;
+    let salt = session.salt;
+    let key = session.key(salt);
+
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| anyhow!("Couldn't generate a nonce"))?;
+    let mut in_out = text.as_bytes().to_vec();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(aad(title)), &mut in_out)
+        .map_err(|_| anyhow!("Encryption failed"))?;
+
+    let mut data = Vec::with_capacity(SALT_LEN + NONCE_LEN + in_out.len());
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&in_out);
+    Ok(format!("{}{}", PREFIX, base64::engine::general_purpose::STANDARD.encode(data)))
+}
+
+/// The readable text of the body of note `title`: itself, or decrypted if
+/// it's encrypted, asking for the passphrase if this run doesn't have it yet.
+pub fn unlock(text: &str, title: &str) -> Result<String> {
+    if !is_encrypted(text) {
+        return Ok(text.to_string());
+    }
+    if let Some(plaintext) = open(text, title) {
+        return Ok(plaintext);
+    }
+    let passphrase = ask_passphrase("Passphrase")?;
+    try_passphrase(&passphrase, text, title)
+}
+
+/// A passphrase for encrypting, typed twice to catch typos.
+pub fn new_passphrase() -> Result<String> {
+    let passphrase = ask_passphrase("New passphrase")?;
+    if ask_passphrase("Repeat passphrase")? != passphrase {
+        bail!("The passphrases don't match.");
+    }
+    Ok(passphrase)
+}
+
+/// `SCRAP_PASSPHRASE`, or a passphrase typed at the terminal without echo.
+fn ask_passphrase(label: &str) -> Result<String> {
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+    use std::io::Write;
+
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    // Reads the terminal even when stdin is a pipe
+    crossterm::terminal::enable_raw_mode()
+        .with_context(|| format!("No terminal to ask for the passphrase; set {}", PASSPHRASE_ENV))?;
+    eprint!("{}: ", label);
+    let _ = std::io::stderr().flush();
+
+    let mut input = String::new();
+    let result = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Enter => break Ok(input),
+                KeyCode::Esc => break Err(anyhow!("Cancelled")),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break Err(anyhow!("Cancelled")),
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                _ => {}
+            },
+            Ok(_) => {}
+            Err(e) => break Err(e.into()),
+        }
+    };
+    let _ = crossterm::terminal::disable_raw_mode();
+    eprint!("\r\n");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The passphrase is kept for the whole process, so these run one at a time.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn test_round_trip() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        set_passphrase("correct horse").unwrap();
+        let sealed = seal("secret text\n", "plans").unwrap();
+        assert!(is_encrypted(&sealed));
+        assert!(!sealed.contains("secret"));
+        assert_eq!(open(&sealed, "plans").as_deref(), Some("secret text\n"));
+        // Bound to the note it was encrypted for
+        assert_eq!(open(&sealed, "other"), None);
+        assert_eq!(unlock("plain text", "plans").unwrap(), "plain text");
+
+        lock();
+        assert!(!is_unlocked());
+        assert_eq!(open(&sealed, "plans"), None);
+        assert!(seal("more", "plans").is_err());
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        set_passphrase("correct horse").unwrap();
+        let sealed = seal("secret text", "plans").unwrap();
+
+        assert!(try_passphrase("battery staple", &sealed, "plans").is_err());
+        assert!(try_passphrase("", &sealed, "plans").is_err());
+        // The previous passphrase is still the one in use
+        assert_eq!(open(&sealed, "plans").as_deref(), Some("secret text"));
+
+        lock();
+        assert_eq!(try_passphrase("correct horse", &sealed, "plans").unwrap(), "secret text");
+        assert!(is_unlocked());
+        lock();
+    }
+
+    #[test]
+    fn test_invalid_ciphertext() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        set_passphrase("correct horse").unwrap();
+        let sealed = seal("secret text", "plans").unwrap();
+
+        assert_eq!(open("secret text", "plans"), None);
+        assert_eq!(open(PREFIX, "plans"), None);
+        assert_eq!(open(&format!("{}not base64!", PREFIX), "plans"), None);
+        // Too short to hold a salt and nonce
+        assert_eq!(open(&format!("{}AAAA", PREFIX), "plans"), None);
+        // Cut off inside the ciphertext, so the tag doesn't match
+        let truncated = &sealed[..sealed.len() - 8];
+        assert_eq!(open(truncated, "plans"), None);
+        let mut tampered = sealed.clone().into_bytes();
+        let last = tampered.len() - 3;
+        tampered[last] = if tampered[last] == b'A' { b'B' } else { b'A' };
+        assert_eq!(open(&String::from_utf8(tampered).unwrap(), "plans"), None);
+        lock();
+    }
+}
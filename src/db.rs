@@ -1,14 +1,100 @@
-use anyhow::{Context, Result};
-use rusqlite::{params, Connection};
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use std::collections::HashMap;
 
-#[derive(Clone)]
+use crate::attachments;
+use crate::crypto;
+use crate::output;
+use crate::tasks;
+use crate::utils;
+
+#[derive(Clone, Default, Serialize)]
 pub struct NoteEntry {
     pub id: i64,
     pub title: String,
     pub note: String,
     pub tags: Vec<String>,
-    #[allow(dead_code)]
+    pub created_at: String,
     pub updated_at: String,
+    pub summary: Option<String>,
+    pub pinned: bool,
+    pub archived: bool,
+    pub deleted_at: Option<String>,
+    /// Bumped on every content change, so an edit can tell whether the note
+    /// changed since it was read
+    #[serde(skip)]
+    pub revision: i64,
+    /// When the note should resurface, in UTC
+    pub remind_at: Option<String>,
+    /// Stored encrypted. `note` is the plaintext once the passphrase is known,
+    /// and the ciphertext until then.
+    pub encrypted: bool,
+}
+
+impl NoteEntry {
+    /// Case-insensitive substring match against title, contents and tags.
+    /// The contents of encrypted notes are never searched. `query` must
+    /// already be lowercased.
+    pub fn matches_query(&self, query: &str) -> bool {
+        query.is_empty()
+            || self.title.to_lowercase().contains(query)
+            || (!self.encrypted && self.note.to_lowercase().contains(query))
+            || self.tags.iter().any(|t| t.to_lowercase().contains(query))
+    }
+
+    /// Encrypted and not yet decrypted in this run.
+    pub fn is_locked(&self) -> bool {
+        self.encrypted && crypto::is_encrypted(&self.note)
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.note.split_whitespace().count()
+    }
+
+    /// Number of markdown links (`[text](target)`) in the note body.
+    pub fn link_count(&self) -> usize {
+        self.note.matches("](").count()
+    }
+
+    /// True if the note carries at least one of `tags`, or `tags` is empty.
+    pub fn has_any_tag(&self, tags: &[String]) -> bool {
+        tags.is_empty() || self.tags.iter().any(|t| tags.contains(t))
+    }
+
+    /// True if the note has a reminder whose time has come.
+    pub fn reminder_due(&self) -> bool {
+        self.remind_at
+            .as_deref()
+            .and_then(utils::parse_timestamp)
+            .is_some_and(|at| at <= utils::now_secs())
+    }
+}
+
+const NOTE_ENTRY_COLUMNS: &str =
+    "id, title, note, tags, created_at, updated_at, summary, pinned, archived, deleted_at, revision, remind_at";
+
+fn note_entry_from_row(row: &Row) -> rusqlite::Result<NoteEntry> {
+    let tags_str: String = row.get(3)?;
+    let tags: Vec<String> = serde_json::from_str(&tags_str).unwrap_or_default();
+    let title: String = row.get(1)?;
+    let note: String = row.get(2)?;
+    let encrypted = crypto::is_encrypted(&note);
+    Ok(NoteEntry {
+        id: row.get(0)?,
+        note: if encrypted { crypto::open(&note, &title).unwrap_or(note) } else { note },
+        title,
+        tags,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+        summary: row.get::<_, Option<String>>(6)?.filter(|s| !s.is_empty()),
+        pinned: row.get::<_, i64>(7)? != 0,
+        archived: row.get::<_, i64>(8)? != 0,
+        deleted_at: row.get(9)?,
+        revision: row.get(10)?,
+        remind_at: row.get(11)?,
+        encrypted,
+    })
 }
 
 fn db_path() -> Result<std::path::PathBuf> {
@@ -21,8 +107,22 @@ fn db_path() -> Result<std::path::PathBuf> {
 pub fn get_db() -> Result<Connection> {
     let path = db_path()?;
     let conn = Connection::open(&path)?;
+    create_schema(&conn)?;
+    Ok(conn)
+}
+
+/// A fresh database in memory, for tests.
+#[cfg(test)]
+pub fn open_in_memory() -> Result<Connection> {
+    let conn = Connection::open_in_memory()?;
+    create_schema(&conn)?;
+    Ok(conn)
+}
+
+fn create_schema(conn: &Connection) -> Result<()> {
     conn.execute_batch(
         "DROP TRIGGER IF EXISTS update_last_modified;
+        DROP TRIGGER IF EXISTS update_notes_updated_at;
         CREATE TABLE IF NOT EXISTS notes (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             title TEXT NOT NULL,
@@ -32,7 +132,7 @@ pub fn get_db() -> Result<Connection> {
             updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
         );
         CREATE TRIGGER IF NOT EXISTS update_notes_updated_at
-            AFTER UPDATE ON notes
+            AFTER UPDATE OF title, note, tags ON notes
             WHEN old.updated_at <> CURRENT_TIMESTAMP
         BEGIN
             UPDATE notes SET updated_at = CURRENT_TIMESTAMP WHERE id = OLD.id;
@@ -41,20 +141,91 @@ pub fn get_db() -> Result<Connection> {
     // Migration: add summary columns if they don't exist
     let _ = conn.execute_batch("ALTER TABLE notes ADD COLUMN summary TEXT;");
     let _ = conn.execute_batch("ALTER TABLE notes ADD COLUMN summary_stale INTEGER NOT NULL DEFAULT 0;");
-    Ok(conn)
+    // Migration: add pinned/archived flags
+    let _ = conn.execute_batch("ALTER TABLE notes ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;");
+    let _ = conn.execute_batch("ALTER TABLE notes ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;");
+    // Migration: soft delete into trash
+    let _ = conn.execute_batch("ALTER TABLE notes ADD COLUMN deleted_at TEXT;");
+    // Migration: content revision for detecting concurrent edits
+    let _ = conn.execute_batch("ALTER TABLE notes ADD COLUMN revision INTEGER NOT NULL DEFAULT 0;");
+    // Migration: checkbox task index, and the revision each note was last indexed at
+    let _ = conn.execute_batch("ALTER TABLE notes ADD COLUMN tasks_revision INTEGER NOT NULL DEFAULT -1;");
+    // Migration: reminders
+    let _ = conn.execute_batch("ALTER TABLE notes ADD COLUMN remind_at TEXT;");
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS tasks (
+            note_id INTEGER NOT NULL,
+            line INTEGER NOT NULL,
+            text TEXT NOT NULL,
+            done INTEGER NOT NULL,
+            due TEXT
+        );
+        CREATE INDEX IF NOT EXISTS tasks_note_id ON tasks (note_id);
+        CREATE TRIGGER IF NOT EXISTS delete_note_tasks
+            AFTER DELETE ON notes
+        BEGIN
+            DELETE FROM tasks WHERE note_id = OLD.id;
+        END;
+        CREATE TABLE IF NOT EXISTS attachments (
+            hash TEXT PRIMARY KEY,
+            data BLOB NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS note_attachments (
+            note_id INTEGER NOT NULL,
+            hash TEXT NOT NULL,
+            name TEXT NOT NULL,
+            added_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (note_id, hash)
+        );
+        CREATE TRIGGER IF NOT EXISTS delete_note_attachments
+            AFTER DELETE ON notes
+        BEGIN
+            DELETE FROM note_attachments WHERE note_id = OLD.id;
+        END;
+        CREATE TRIGGER IF NOT EXISTS delete_unused_attachments
+            AFTER DELETE ON note_attachments
+            WHEN NOT EXISTS (SELECT 1 FROM note_attachments WHERE hash = OLD.hash)
+        BEGIN
+            DELETE FROM attachments WHERE hash = OLD.hash;
+        END;",
+    )?;
+    // Notes written without going through `index_tasks`, e.g. by an older scrap or an import
+    index_stale_tasks(conn)
+}
+
+/// Returns the new note's id.
+pub fn insert_note(conn: &Connection, name: &str, contents: &str, tags: &[String]) -> Result<i64> {
+    check_plain(contents)?;
+    insert_stored(conn, name, contents, tags)
 }
 
-pub fn insert_note(conn: &Connection, name: &str, contents: &str, tags: &[String]) -> Result<()> {
+/// Like `insert_note`, but the contents are stored encrypted.
+pub fn insert_encrypted_note(conn: &Connection, name: &str, contents: &str, tags: &[String]) -> Result<i64> {
+    check_plain(contents)?;
+    insert_stored(conn, name, &crypto::seal(contents, name)?, tags)
+}
+
+/// Plain text that would read back as an encrypted note is refused.
+fn check_plain(contents: &str) -> Result<()> {
+    if crypto::is_encrypted(contents) {
+        bail!("Notes can't start with '{}', which marks encrypted notes", crypto::PREFIX);
+    }
+    Ok(())
+}
+
+fn insert_stored(conn: &Connection, name: &str, contents: &str, tags: &[String]) -> Result<i64> {
     let tags_json = serde_json::to_string(tags)?;
     conn.execute(
         "INSERT INTO notes (title, note, tags) VALUES (?1, ?2, ?3)",
         params![name, contents, tags_json],
     )?;
-    Ok(())
+    let id = conn.last_insert_rowid();
+    index_tasks(conn, id)?;
+    Ok(id)
 }
 
 pub fn get_note(conn: &Connection, name: &str) -> Result<Option<(i64, String, String)>> {
-    let mut stmt = conn.prepare("SELECT id, note, tags FROM notes WHERE title = ?1")?;
+    let mut stmt = conn.prepare("SELECT id, note, tags FROM notes WHERE title = ?1 AND deleted_at IS NULL")?;
     let mut rows = stmt.query(params![name])?;
     match rows.next()? {
         Some(row) => Ok(Some((row.get(0)?, row.get(1)?, row.get(2)?))),
@@ -62,8 +233,19 @@ pub fn get_note(conn: &Connection, name: &str) -> Result<Option<(i64, String, St
     }
 }
 
+pub fn get_note_entry(conn: &Connection, name: &str) -> Result<Option<NoteEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {NOTE_ENTRY_COLUMNS} FROM notes WHERE title = ?1 AND deleted_at IS NULL"
+    ))?;
+    let mut rows = stmt.query(params![name])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(note_entry_from_row(row)?)),
+        None => Ok(None),
+    }
+}
+
 pub fn get_tags_and_id(conn: &Connection, name: &str) -> Result<Option<(i64, Vec<String>)>> {
-    let mut stmt = conn.prepare("SELECT id, tags FROM notes WHERE title = ?1")?;
+    let mut stmt = conn.prepare("SELECT id, tags FROM notes WHERE title = ?1 AND deleted_at IS NULL")?;
     let mut rows = stmt.query(params![name])?;
     match rows.next()? {
         Some(row) => {
@@ -76,10 +258,194 @@ pub fn get_tags_and_id(conn: &Connection, name: &str) -> Result<Option<(i64, Vec
     }
 }
 
-pub fn update_note(conn: &Connection, id: i64, contents: &str) -> Result<()> {
+/// A note by id, or None if it is gone or in the trash.
+pub fn get_note_by_id(conn: &Connection, id: i64) -> Result<Option<NoteEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {NOTE_ENTRY_COLUMNS} FROM notes WHERE id = ?1 AND deleted_at IS NULL"
+    ))?;
+    let mut rows = stmt.query(params![id])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(note_entry_from_row(row)?)),
+        None => Ok(None),
+    }
+}
+
+/// Replace a note's contents regardless of what changed since it was read.
+/// Returns the note's new revision, or None if there is no such note.
+pub fn update_note(conn: &Connection, id: i64, contents: &str) -> Result<Option<i64>> {
+    let contents = seal(conn, id, contents)?;
+    let revision = conn
+        .query_row(
+            "UPDATE notes SET note = ?1, revision = revision + 1 WHERE id = ?2 RETURNING revision",
+            params![contents, id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    index_tasks(conn, id)?;
+    Ok(revision)
+}
+
+/// `contents` as it should be stored in note `id`: encrypted if the note is.
+fn seal(conn: &Connection, id: i64, contents: &str) -> Result<String> {
+    check_plain(contents)?;
+    let stored: Option<(String, String)> = conn
+        .query_row("SELECT title, note FROM notes WHERE id = ?1", params![id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .optional()?;
+    match stored {
+        Some((title, stored)) if crypto::is_encrypted(&stored) => crypto::seal(contents, &title),
+        _ => Ok(contents.to_string()),
+    }
+}
+
+/// The title and stored contents of some live encrypted note, if there is one.
+pub fn any_encrypted_note(conn: &Connection) -> Result<Option<(String, String)>> {
+    Ok(conn
+        .query_row(
+            "SELECT title, note FROM notes WHERE deleted_at IS NULL AND substr(note, 1, ?1) = ?2 LIMIT 1",
+            params![crypto::PREFIX.len(), crypto::PREFIX],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?)
+}
+
+/// Store a note's contents exactly as given, for encrypting or decrypting it.
+/// The summary is dropped, since it would give away encrypted text.
+pub fn store_contents(conn: &Connection, id: i64, stored: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE notes SET note = ?1, summary = NULL, summary_stale = 0, revision = revision + 1 WHERE id = ?2",
+        params![stored, id],
+    )?;
+    index_tasks(conn, id)
+}
+
+/// Replace a note's contents only if it is still at `revision`, the revision
+/// the edit started from. Returns false if the note changed or was trashed
+/// in the meantime, leaving it untouched.
+pub fn update_note_if_unchanged(conn: &Connection, id: i64, contents: &str, revision: i64) -> Result<bool> {
+    let contents = seal(conn, id, contents)?;
+    let count = conn.execute(
+        "UPDATE notes SET note = ?1, revision = revision + 1
+         WHERE id = ?2 AND revision = ?3 AND deleted_at IS NULL",
+        params![contents, id, revision],
+    )?;
+    if count == 1 {
+        index_tasks(conn, id)?;
+    }
+    Ok(count == 1)
+}
+
+/// Add text to the end of a note in a single statement, so concurrent appends
+/// and edits can't drop each other's changes. Encrypted notes are decrypted,
+/// appended to and saved if unchanged in between. Returns the new revision.
+pub fn append_note(conn: &Connection, id: i64, text: &str) -> Result<i64> {
+    let mut name = id.to_string();
+    loop {
+        let Some(note) = get_note_by_id(conn, id)? else {
+            return Err(output::not_found(&name));
+        };
+        name = note.title.clone();
+        if !note.encrypted {
+            // Ciphertext can't be appended to, so this skips a note encrypted since
+            let revision = conn
+                .query_row(
+                    "UPDATE notes SET note = note || char(10) || ?1, revision = revision + 1
+                     WHERE id = ?2 AND deleted_at IS NULL AND substr(note, 1, ?3) != ?4
+                     RETURNING revision",
+                    params![text, id, crypto::PREFIX.len(), crypto::PREFIX],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if let Some(revision) = revision {
+                index_tasks(conn, id)?;
+                return Ok(revision);
+            }
+            continue;
+        }
+        if note.is_locked() {
+            bail!("'{}' is encrypted and scrap doesn't have its passphrase yet", note.title);
+        }
+        if update_note_if_unchanged(conn, id, &format!("{}\n{}", note.note, text), note.revision)? {
+            return Ok(note.revision + 1);
+        }
+    }
+}
+
+/// A title for a copy of `title` that no live note uses yet, e.g. `plan (copy 2)`.
+pub fn copy_title(conn: &Connection, title: &str) -> Result<String> {
+    let mut candidate = format!("{} (copy)", title);
+    let mut n = 1;
+    while get_note(conn, &candidate)?.is_some() {
+        n += 1;
+        candidate = format!("{} (copy {})", title, n);
+    }
+    Ok(candidate)
+}
+
+/// `title`, or `title (2)`, `title (3)`, ... if a live note already uses it.
+pub fn unique_title(conn: &Connection, title: &str) -> Result<String> {
+    let mut candidate = title.to_string();
+    let mut n = 1;
+    while get_note(conn, &candidate)?.is_some() {
+        n += 1;
+        candidate = format!("{} ({})", title, n);
+    }
+    Ok(candidate)
+}
+
+/// The current local time as `YYYY-MM-DD HH:MM`.
+pub fn local_timestamp(conn: &Connection) -> Result<String> {
+    Ok(conn.query_row("SELECT strftime('%Y-%m-%d %H:%M', 'now', 'localtime')", [], |row| row.get(0))?)
+}
+
+/// Evaluate SQLite `datetime()` with `args`, None if they don't make a time.
+pub fn datetime(conn: &Connection, args: &[String]) -> Result<Option<String>> {
+    let placeholders: Vec<String> = (1..=args.len()).map(|i| format!("?{}", i)).collect();
+    let sql = format!("SELECT datetime({})", placeholders.join(", "));
+    Ok(conn.query_row(&sql, rusqlite::params_from_iter(args), |row| row.get(0))?)
+}
+
+/// A UTC timestamp in local time, e.g. "2026-10-20 09:00".
+pub fn local_time(conn: &Connection, ts: &str) -> Result<String> {
+    Ok(conn.query_row("SELECT strftime('%Y-%m-%d %H:%M', ?1, 'localtime')", [ts], |row| row.get(0))?)
+}
+
+/// Set or clear (None) a note's reminder.
+pub fn set_reminder(conn: &Connection, id: i64, remind_at: Option<&str>) -> Result<()> {
+    conn.execute(
+        "UPDATE notes SET remind_at = ?1 WHERE id = ?2",
+        params![remind_at, id],
+    )?;
+    Ok(())
+}
+
+/// Rename a note. An encrypted note's body is bound to its title, so it is
+/// encrypted again for the new one, which needs its passphrase.
+pub fn rename_note(conn: &Connection, id: i64, title: &str) -> Result<()> {
+    let stored: Option<(String, String)> = conn
+        .query_row("SELECT title, note FROM notes WHERE id = ?1", params![id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .optional()?;
+    if let Some((previous, stored)) = stored
+        && crypto::is_encrypted(&stored)
+    {
+        let Some(plaintext) = crypto::open(&stored, &previous) else {
+            bail!("'{}' is encrypted; unlock it before renaming it", previous);
+        };
+        let count = conn.execute(
+            "UPDATE notes SET title = ?1, note = ?2 WHERE id = ?3 AND note = ?4",
+            params![title, crypto::seal(&plaintext, title)?, id, stored],
+        )?;
+        if count == 0 {
+            bail!("'{}' changed while renaming it; try again", previous);
+        }
+        return Ok(());
+    }
     conn.execute(
-        "UPDATE notes SET note = ?1 WHERE id = ?2",
-        params![contents, id],
+        "UPDATE notes SET title = ?1 WHERE id = ?2",
+        params![title, id],
     )?;
     Ok(())
 }
@@ -93,11 +459,78 @@ pub fn update_tags(conn: &Connection, id: i64, tags: &[String]) -> Result<()> {
     Ok(())
 }
 
+pub fn set_pinned(conn: &Connection, id: i64, pinned: bool) -> Result<()> {
+    conn.execute(
+        "UPDATE notes SET pinned = ?1 WHERE id = ?2",
+        params![pinned as i64, id],
+    )?;
+    Ok(())
+}
+
+pub fn set_archived(conn: &Connection, id: i64, archived: bool) -> Result<()> {
+    conn.execute(
+        "UPDATE notes SET archived = ?1 WHERE id = ?2",
+        params![archived as i64, id],
+    )?;
+    Ok(())
+}
+
+/// Move a note to the trash. Returns false if no such note exists.
 pub fn delete_note(conn: &Connection, name: &str) -> Result<bool> {
-    let count = conn.execute("DELETE FROM notes WHERE title = ?1", params![name])?;
+    let count = conn.execute(
+        "UPDATE notes SET deleted_at = CURRENT_TIMESTAMP WHERE title = ?1 AND deleted_at IS NULL",
+        params![name],
+    )?;
     Ok(count > 0)
 }
 
+pub fn delete_note_by_id(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE notes SET deleted_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        params![id],
+    )?;
+    Ok(())
+}
+
+pub fn restore_note_by_id(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE notes SET deleted_at = NULL WHERE id = ?1",
+        params![id],
+    )?;
+    Ok(())
+}
+
+pub fn list_trash(conn: &Connection) -> Result<Vec<NoteEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {NOTE_ENTRY_COLUMNS} FROM notes WHERE deleted_at IS NOT NULL
+         ORDER BY deleted_at DESC"
+    ))?;
+    let rows = stmt.query_map([], note_entry_from_row)?;
+    let mut notes = Vec::new();
+    for row in rows {
+        notes.push(row?);
+    }
+    Ok(notes)
+}
+
+/// Permanently delete everything in the trash. Returns the number of notes removed.
+pub fn empty_trash(conn: &Connection) -> Result<usize> {
+    let count = conn.execute("DELETE FROM notes WHERE deleted_at IS NOT NULL", [])?;
+    Ok(count)
+}
+
+/// Permanently delete trashed notes older than `retention_days`. Zero keeps them forever.
+pub fn purge_trash(conn: &Connection, retention_days: u32) -> Result<usize> {
+    if retention_days == 0 {
+        return Ok(0);
+    }
+    let count = conn.execute(
+        "DELETE FROM notes WHERE deleted_at IS NOT NULL AND deleted_at < datetime('now', ?1)",
+        params![format!("-{} days", retention_days)],
+    )?;
+    Ok(count)
+}
+
 pub fn get_summary(conn: &Connection, id: i64) -> Result<Option<(String, bool)>> {
     let mut stmt = conn.prepare("SELECT summary, summary_stale FROM notes WHERE id = ?1")?;
     let mut rows = stmt.query(params![id])?;
@@ -132,24 +565,247 @@ pub fn mark_summary_stale(conn: &Connection, id: i64) -> Result<()> {
     Ok(())
 }
 
+/// Changes whenever another connection commits to the database, so a long-running
+/// process can notice writes from other `scrap` invocations.
+pub fn data_version(conn: &Connection) -> Result<i64> {
+    Ok(conn.query_row("PRAGMA data_version", [], |row| row.get(0))?)
+}
+
 pub fn list_notes(conn: &Connection) -> Result<Vec<NoteEntry>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, title, note, tags, updated_at FROM notes ORDER BY updated_at DESC"
-    )?;
-    let rows = stmt.query_map([], |row| {
-        let tags_str: String = row.get(3)?;
-        let tags: Vec<String> = serde_json::from_str(&tags_str).unwrap_or_default();
-        Ok(NoteEntry {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            note: row.get(2)?,
-            tags,
-            updated_at: row.get(4)?,
-        })
-    })?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {NOTE_ENTRY_COLUMNS} FROM notes WHERE deleted_at IS NULL
+         ORDER BY pinned DESC, updated_at DESC"
+    ))?;
+    let rows = stmt.query_map([], note_entry_from_row)?;
     let mut notes = Vec::new();
     for row in rows {
         notes.push(row?);
     }
     Ok(notes)
 }
+
+/// A file attached to a note. The same file attached to several notes is
+/// stored once.
+#[derive(Clone, Serialize)]
+pub struct AttachmentEntry {
+    pub name: String,
+    pub hash: String,
+    pub size: usize,
+    pub added_at: String,
+}
+
+fn attachment_entry_from_row(row: &Row) -> rusqlite::Result<AttachmentEntry> {
+    Ok(AttachmentEntry {
+        name: row.get(0)?,
+        hash: row.get(1)?,
+        size: row.get::<_, i64>(2)? as usize,
+        added_at: row.get(3)?,
+    })
+}
+
+/// Store `data` and attach it to a note as `name`. Returns the file's hash.
+/// Attaching a file the note already has keeps its first name.
+pub fn add_attachment(conn: &Connection, note_id: i64, name: &str, data: &[u8]) -> Result<String> {
+    let hash = attachments::hash(data);
+    conn.execute(
+        "INSERT OR IGNORE INTO attachments (hash, data) VALUES (?1, ?2)",
+        params![hash, data],
+    )?;
+    conn.execute(
+        "INSERT OR IGNORE INTO note_attachments (note_id, hash, name) VALUES (?1, ?2, ?3)",
+        params![note_id, hash, name],
+    )?;
+    Ok(hash)
+}
+
+/// A note's attachments, oldest first.
+pub fn note_attachments(conn: &Connection, note_id: i64) -> Result<Vec<AttachmentEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT na.name, na.hash, length(a.data), na.added_at
+         FROM note_attachments na JOIN attachments a ON a.hash = na.hash
+         WHERE na.note_id = ?1
+         ORDER BY na.added_at, na.rowid",
+    )?;
+    let rows = stmt.query_map([note_id], attachment_entry_from_row)?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+/// Every stored file by hash, named as it was first attached.
+pub fn all_attachments(conn: &Connection) -> Result<HashMap<String, AttachmentEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT na.name, na.hash, length(a.data), MIN(na.added_at)
+         FROM note_attachments na JOIN attachments a ON a.hash = na.hash
+         GROUP BY na.hash",
+    )?;
+    let rows = stmt.query_map([], attachment_entry_from_row)?;
+    let mut attachments = HashMap::new();
+    for row in rows {
+        let attachment = row?;
+        attachments.insert(attachment.hash.clone(), attachment);
+    }
+    Ok(attachments)
+}
+
+pub fn attachment_data(conn: &Connection, hash: &str) -> Result<Option<Vec<u8>>> {
+    let mut stmt = conn.prepare("SELECT data FROM attachments WHERE hash = ?1")?;
+    let mut rows = stmt.query([hash])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(row.get(0)?)),
+        None => Ok(None),
+    }
+}
+
+/// Give note `to` the attachments of note `from` that `text` refers to, so
+/// text moved between notes keeps its attachments when `from` is deleted.
+pub fn copy_attachments(conn: &Connection, from: i64, to: i64, text: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO note_attachments (note_id, hash, name, added_at)
+         SELECT ?2, hash, name, added_at FROM note_attachments
+         WHERE note_id = ?1 AND instr(?3, ?4 || hash) > 0",
+        params![from, to, text, attachments::SCHEME],
+    )?;
+    Ok(())
+}
+
+/// A checkbox task with the note it's in.
+#[derive(Serialize)]
+pub struct TaskEntry {
+    pub title: String,
+    pub line: usize,
+    pub text: String,
+    pub done: bool,
+    pub due: Option<String>,
+    /// Open and due before today
+    pub overdue: bool,
+    #[serde(skip)]
+    pub tags: Vec<String>,
+    #[serde(skip)]
+    pub archived: bool,
+}
+
+/// Rebuild the task index of one note from its contents.
+fn index_tasks(conn: &Connection, id: i64) -> Result<()> {
+    let row = conn.query_row("SELECT note, revision FROM notes WHERE id = ?1", params![id], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+    });
+    let (contents, revision) = match row {
+        Ok(row) => row,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    conn.execute("DELETE FROM tasks WHERE note_id = ?1", params![id])?;
+    let mut insert = conn.prepare("INSERT INTO tasks (note_id, line, text, done, due) VALUES (?1, ?2, ?3, ?4, ?5)")?;
+    // Encrypted notes stay out of the index
+    let found = if crypto::is_encrypted(&contents) { Vec::new() } else { tasks::parse(&contents) };
+    for task in found {
+        insert.execute(params![id, task.line, task.text, task.done, task.due])?;
+    }
+    conn.execute("UPDATE notes SET tasks_revision = ?1 WHERE id = ?2", params![revision, id])?;
+    Ok(())
+}
+
+fn index_stale_tasks(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare("SELECT id FROM notes WHERE tasks_revision <> revision")?;
+    let ids = stmt.query_map([], |row| row.get::<_, i64>(0))?.collect::<rusqlite::Result<Vec<i64>>>()?;
+    for id in ids {
+        index_tasks(conn, id)?;
+    }
+    Ok(())
+}
+
+/// Tasks in live notes, soonest due first, then by note and line.
+pub fn list_tasks(conn: &Connection) -> Result<Vec<TaskEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT n.title, t.line, t.text, t.done, t.due,
+                t.done = 0 AND t.due IS NOT NULL AND t.due < date('now', 'localtime'), n.tags, n.archived
+         FROM tasks t JOIN notes n ON n.id = t.note_id
+         WHERE n.deleted_at IS NULL
+         ORDER BY t.due IS NULL, t.due, n.title, t.line",
+    )?;
+    let tasks = stmt
+        .query_map([], |row| {
+            let tags_str: String = row.get(6)?;
+            Ok(TaskEntry {
+                title: row.get(0)?,
+                line: row.get(1)?,
+                text: row.get(2)?,
+                done: row.get(3)?,
+                due: row.get(4)?,
+                overdue: row.get(5)?,
+                tags: serde_json::from_str(&tags_str).unwrap_or_default(),
+                archived: row.get::<_, i64>(7)? != 0,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(tasks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_text_that_looks_encrypted_is_refused() {
+        let conn = open_in_memory().unwrap();
+        let looks_encrypted = format!("{}not really", crypto::PREFIX);
+        assert!(insert_note(&conn, "fake", &looks_encrypted, &[]).is_err());
+
+        let id = insert_note(&conn, "plain", "hello\n", &[]).unwrap();
+        assert!(update_note(&conn, id, &looks_encrypted).is_err());
+        assert!(update_note_if_unchanged(&conn, id, &looks_encrypted, 0).is_err());
+        let note = get_note_by_id(&conn, id).unwrap().unwrap();
+        assert_eq!(note.note, "hello\n");
+        assert!(!note.encrypted);
+
+        // Further down the note it is just text
+        let text = format!("hello\n{}\n", looks_encrypted);
+        update_note(&conn, id, &text).unwrap();
+        assert_eq!(get_note_by_id(&conn, id).unwrap().unwrap().note, text);
+    }
+
+    fn trash(conn: &Connection, name: &str, days_ago: u32) -> i64 {
+        let id = insert_note(conn, name, "text\n", &[]).unwrap();
+        conn.execute(
+            "UPDATE notes SET deleted_at = datetime('now', ?1) WHERE id = ?2",
+            params![format!("-{} days", days_ago), id],
+        )
+        .unwrap();
+        id
+    }
+
+    fn titles(notes: &[NoteEntry]) -> Vec<&str> {
+        notes.iter().map(|n| n.title.as_str()).collect()
+    }
+
+    #[test]
+    fn test_purge_trash_retention() {
+        let conn = open_in_memory().unwrap();
+        insert_note(&conn, "live", "text\n", &[]).unwrap();
+        trash(&conn, "recent", 1);
+        trash(&conn, "old", 40);
+        trash(&conn, "ancient", 400);
+
+        // Zero keeps the trash forever
+        assert_eq!(purge_trash(&conn, 0).unwrap(), 0);
+        assert_eq!(list_trash(&conn).unwrap().len(), 3);
+
+        assert_eq!(purge_trash(&conn, 30).unwrap(), 2);
+        assert_eq!(titles(&list_trash(&conn).unwrap()), ["recent"]);
+        assert!(get_note(&conn, "live").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_restore_from_trash() {
+        let conn = open_in_memory().unwrap();
+        let id = insert_note(&conn, "note", "text\n", &[]).unwrap();
+        assert!(delete_note(&conn, "note").unwrap());
+        assert!(get_note(&conn, "note").unwrap().is_none());
+        assert_eq!(titles(&list_trash(&conn).unwrap()), ["note"]);
+
+        restore_note_by_id(&conn, id).unwrap();
+        assert!(get_note(&conn, "note").unwrap().is_some());
+        assert!(list_trash(&conn).unwrap().is_empty());
+        // A restored note is safe from the purge
+        assert_eq!(purge_trash(&conn, 1).unwrap(), 0);
+    }
+}
@@ -1,15 +1,32 @@
+mod attachments;
+mod clipboard;
 mod commands;
+mod config;
+mod crypto;
 mod db;
 mod llm;
+mod markdown;
+mod merge;
+mod output;
+mod remind;
+mod session;
+mod sort;
+mod tasks;
 mod tui;
 mod utils;
 mod version_check;
 
 use clap::{Parser, Subcommand};
 
+use output::OutputFormat;
+use sort::SortKey;
+
 #[derive(Parser)]
 #[command(name = "scrap", about = "A CLI note-taking app")]
 struct Cli {
+    /// Output format for commands that print notes or tags
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -23,7 +40,15 @@ enum Commands {
         /// Tags for the note
         tags: Vec<String>,
     },
-    /// Delete a note
+    /// Quickly save text as a new note tagged `inbox`, titled from its first line
+    Capture {
+        /// Text to capture; read from stdin if omitted
+        text: Vec<String>,
+        /// Extra tag for the note (repeatable)
+        #[arg(long)]
+        tag: Vec<String>,
+    },
+    /// Move a note to the trash
     Delete {
         /// Name of the note to delete
         name: String,
@@ -32,6 +57,12 @@ enum Commands {
     Find {
         /// Optional search query
         query: Option<String>,
+        /// Preselect a tag filter (repeatable)
+        #[arg(long)]
+        tag: Vec<String>,
+        /// Pick a note and print its title to stdout instead of opening the TUI
+        #[arg(long)]
+        print: bool,
     },
     /// Open and edit an existing note
     Open {
@@ -68,12 +99,166 @@ enum Commands {
         /// Filter by tag
         #[arg(long)]
         tag: Option<String>,
+        /// Include archived notes
+        #[arg(long)]
+        archived: bool,
+        /// Sort order (pinned notes always come first)
+        #[arg(long, value_enum, default_value_t = SortKey::Updated)]
+        sort: SortKey,
+        /// Reverse the sort order
+        #[arg(long)]
+        reverse: bool,
+    },
+    /// Search note titles, contents and tags
+    Search {
+        /// Text to search for (case-insensitive)
+        query: String,
+        /// Filter by tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Include archived notes
+        #[arg(long)]
+        archived: bool,
+    },
+    /// List all tags with their note counts
+    Tags,
+    /// List checkbox tasks across notes
+    Tasks {
+        /// Only unchecked tasks
+        #[arg(long, conflicts_with = "done")]
+        open: bool,
+        /// Only checked tasks
+        #[arg(long)]
+        done: bool,
+        /// Only unchecked tasks whose due: date has passed
+        #[arg(long, conflicts_with = "done")]
+        overdue: bool,
+        /// Filter by tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Include tasks in archived notes
+        #[arg(long)]
+        archived: bool,
+    },
+    /// Set a time for a note to resurface in `scrap due` and at the top of the TUI
+    Remind {
+        /// Name of the note
+        name: String,
+        /// When, e.g. 30m, 2h, in 3 days, tomorrow, friday 14:00, 2026-10-20 17:30
+        #[arg(required_unless_present = "clear")]
+        when: Vec<String>,
+        /// Remove the note's reminder
+        #[arg(long, conflicts_with = "when")]
+        clear: bool,
+    },
+    /// List reminders that have come due and open tasks due today or earlier
+    Due {
+        /// Include archived notes
+        #[arg(long)]
+        archived: bool,
     },
     /// Append stdin content to an existing note
     Append {
         /// Name of the note
         name: String,
     },
+    /// Copy a note to the system clipboard
+    Clip {
+        /// Name of the note
+        name: String,
+    },
+    /// Append the system clipboard to an existing note
+    Paste {
+        /// Name of the note
+        name: String,
+    },
+    /// Store files with a note
+    Attach {
+        /// Name of the note
+        name: String,
+        /// Files to attach
+        #[arg(required = true)]
+        files: Vec<String>,
+        /// Also append a markdown reference to each file to the note
+        #[arg(long)]
+        link: bool,
+    },
+    /// List a note's attachments, or extract them to a directory
+    Attachments {
+        /// Name of the note
+        name: String,
+        /// Attachments to list or extract, by file name or hash prefix (default: all)
+        which: Vec<String>,
+        /// Write the attachments into this directory
+        #[arg(long, value_name = "DIR")]
+        extract: Option<String>,
+    },
+    /// Combine notes into one, with a heading for each
+    Merge {
+        /// Notes to merge, in order
+        #[arg(required = true)]
+        names: Vec<String>,
+        /// Note to merge into; created if it doesn't exist, appended to if it does
+        #[arg(long)]
+        into: String,
+        /// Archive the merged notes instead of moving them to the trash
+        #[arg(long)]
+        archive: bool,
+    },
+    /// Break a note up into one note per section
+    Split {
+        /// Name of the note
+        name: String,
+        /// Heading level that starts a section (1 for #, 2 for ##, ...)
+        #[arg(long, value_name = "LEVEL", default_value_t = 2, value_parser = clap::value_parser!(u8).range(1..=6))]
+        on_heading: u8,
+        /// Leave the original note unchanged
+        #[arg(long)]
+        keep: bool,
+    },
+    /// Pin a note to the top of the list
+    Pin {
+        /// Name of the note
+        name: String,
+    },
+    /// Unpin a note
+    Unpin {
+        /// Name of the note
+        name: String,
+    },
+    /// Archive a note, hiding it from lists and search
+    Archive {
+        /// Name of the note
+        name: String,
+    },
+    /// Restore an archived note
+    Unarchive {
+        /// Name of the note
+        name: String,
+    },
+    /// Encrypt a note's contents with a passphrase
+    Encrypt {
+        /// Name of the note
+        name: String,
+    },
+    /// Store an encrypted note as plain text again
+    Decrypt {
+        /// Name of the note
+        name: String,
+    },
+    /// Manage deleted notes
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+    /// Hold copied text on the clipboard in the background (Linux)
+    #[command(name = clipboard::SERVE_COMMAND, hide = true)]
+    ServeClipboard,
+    /// Restore edits left behind by an editor that crashed or a save that failed
+    Recover {
+        #[command(subcommand)]
+        action: Option<RecoverAction>,
+    },
     /// Export all notes to a SQL file
     Export {
         /// Output file path
@@ -89,17 +274,61 @@ enum Commands {
     },
 }
 
-fn main() -> anyhow::Result<()> {
-    // Check for updates (non-blocking, cached)
-    version_check::check_for_updates();
+#[derive(Subcommand)]
+enum TrashAction {
+    /// List notes in the trash
+    List,
+    /// Restore a note from the trash
+    Restore {
+        /// Name of the note to restore
+        name: String,
+    },
+    /// Permanently delete all notes in the trash
+    Empty,
+}
 
+#[derive(Subcommand)]
+enum RecoverAction {
+    /// List unsaved edits, most recent first (the default)
+    List,
+    /// Save an unsaved edit back to its note
+    Restore {
+        /// Number of the edit, from `scrap recover list`
+        number: usize,
+    },
+    /// Throw away an unsaved edit
+    Discard {
+        /// Number of the edit, from `scrap recover list`
+        number: usize,
+    },
+}
+
+fn main() {
     let cli = Cli::parse();
 
+    // Check for updates (non-blocking, cached). Not from the detached clipboard
+    // server, which has no terminal, or while recovering unsaved edits.
+    if !matches!(cli.command, Some(Commands::ServeClipboard | Commands::Recover { .. })) {
+        version_check::check_for_updates();
+    }
+
+    if let Err(e) = run(cli) {
+        eprintln!("Error: {:?}", e);
+        std::process::exit(output::exit_code(&e));
+    }
+}
+
+fn run(cli: Cli) -> anyhow::Result<()> {
+    let format = cli.format;
+
     match cli.command {
-        None => tui::run(),
+        None => tui::run(None, &[]),
         Some(Commands::Add { name, tags }) => commands::add::run(&name, &tags),
+        Some(Commands::Capture { text, tag }) => commands::capture::run(&text, &tag),
         Some(Commands::Delete { name }) => commands::delete::run(&name),
-        Some(Commands::Find { query: _ }) => tui::run(),
+        Some(Commands::Find { query, tag, print }) => {
+            commands::find::run(query.as_deref(), &tag, print)
+        }
         Some(Commands::Open { name }) => commands::open::run(&name),
         Some(Commands::EditTag {
             add,
@@ -108,9 +337,54 @@ fn main() -> anyhow::Result<()> {
             tags,
         }) => commands::edit_tag::run(&name, &tags, add, delete),
         Some(Commands::Write { name, tags }) => commands::write::run(&name, &tags),
-        Some(Commands::Read { name }) => commands::read::run(&name),
-        Some(Commands::List { tag }) => commands::list::run(tag.as_deref()),
+        Some(Commands::Read { name }) => commands::read::run(&name, format),
+        Some(Commands::List {
+            tag,
+            archived,
+            sort,
+            reverse,
+        }) => commands::list::run(tag.as_deref(), archived, sort, reverse, format),
+        Some(Commands::Search {
+            query,
+            tag,
+            archived,
+        }) => commands::search::run(&query, tag.as_deref(), archived, format),
+        Some(Commands::Tags) => commands::tags::run(format),
+        Some(Commands::Tasks {
+            open,
+            done,
+            overdue,
+            tag,
+            archived,
+        }) => commands::tasks::run(open, done, overdue, tag.as_deref(), archived, format),
+        Some(Commands::Remind { name, when, clear }) => commands::remind::run(&name, &when, clear),
+        Some(Commands::Due { archived }) => commands::due::run(archived, format),
         Some(Commands::Append { name }) => commands::append::run(&name),
+        Some(Commands::Clip { name }) => commands::clip::run(&name),
+        Some(Commands::Paste { name }) => commands::paste::run(&name),
+        Some(Commands::Attach { name, files, link }) => commands::attach::run(&name, &files, link),
+        Some(Commands::Attachments { name, which, extract }) => {
+            commands::attachments::run(&name, &which, extract.as_deref(), format)
+        }
+        Some(Commands::Merge { names, into, archive }) => commands::merge::run(&names, &into, archive),
+        Some(Commands::Split { name, on_heading, keep }) => commands::split::run(&name, on_heading as usize, keep),
+        Some(Commands::Pin { name }) => commands::pin::run(&name, true),
+        Some(Commands::Unpin { name }) => commands::pin::run(&name, false),
+        Some(Commands::Archive { name }) => commands::archive::run(&name, true),
+        Some(Commands::Unarchive { name }) => commands::archive::run(&name, false),
+        Some(Commands::Encrypt { name }) => commands::encrypt::run(&name, true),
+        Some(Commands::Decrypt { name }) => commands::encrypt::run(&name, false),
+        Some(Commands::Trash { action }) => match action {
+            TrashAction::List => commands::trash::list(format),
+            TrashAction::Restore { name } => commands::trash::restore(&name),
+            TrashAction::Empty => commands::trash::empty(),
+        },
+        Some(Commands::ServeClipboard) => clipboard::serve(),
+        Some(Commands::Recover { action }) => match action {
+            None | Some(RecoverAction::List) => commands::recover::list(),
+            Some(RecoverAction::Restore { number }) => commands::recover::restore(number),
+            Some(RecoverAction::Discard { number }) => commands::recover::discard(number),
+        },
         Some(Commands::Export { path }) => commands::export::run(&path),
         Some(Commands::Import { path, overwrite }) => commands::import::run(&path, overwrite),
     }
@@ -0,0 +1,41 @@
+//! Markdown structure shared by the TUI and the commands.
+
+/// Rows holding markdown headings, skipping fenced code blocks.
+pub fn heading_rows(lines: &[String]) -> Vec<usize> {
+    let mut rows = Vec::new();
+    let mut in_fence = false;
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if !in_fence && heading_level(line).is_some() {
+            rows.push(i);
+        }
+    }
+    rows
+}
+
+/// Level of an ATX heading (`# Title` is 1), or None if the line is not a heading.
+pub fn heading_level(line: &str) -> Option<usize> {
+    let hashes = line.chars().take_while(|&c| c == '#').count();
+    if (1..=6).contains(&hashes) && line[hashes..].starts_with([' ', '\t']) {
+        Some(hashes)
+    } else {
+        None
+    }
+}
+
+/// Headings as `(row, level, text)`.
+pub fn outline(lines: &[String]) -> Vec<(usize, usize, String)> {
+    heading_rows(lines)
+        .into_iter()
+        .filter_map(|row| {
+            let line = &lines[row];
+            let level = heading_level(line)?;
+            let text = line[level..].trim().trim_end_matches('#').trim_end();
+            Some((row, level, text.to_string()))
+        })
+        .collect()
+}
@@ -0,0 +1,182 @@
+//! Line-based three-way merge for edits that raced with another writer.
+
+const MARKER_OURS: &str = "<<<<<<< yours";
+const MARKER_BASE: &str = "||||||| original";
+const MARKER_SEPARATOR: &str = "=======";
+const MARKER_THEIRS: &str = ">>>>>>> theirs";
+
+pub struct Merge {
+    pub text: String,
+    /// Sections changed differently on both sides, left between conflict markers
+    pub conflicts: usize,
+}
+
+/// Merge `ours` and `theirs`, two edits of `base`. Lines changed on only one
+/// side are taken from that side; lines changed differently on both sides are
+/// written out diff3-style between conflict markers.
+pub fn merge3(base: &str, ours: &str, theirs: &str) -> Merge {
+    let base_lines: Vec<&str> = base.lines().collect();
+    let our_lines: Vec<&str> = ours.lines().collect();
+    let their_lines: Vec<&str> = theirs.lines().collect();
+    let ours_at = matching_lines(&base_lines, &our_lines);
+    let theirs_at = matching_lines(&base_lines, &their_lines);
+
+    let mut out: Vec<&str> = Vec::new();
+    let mut conflicts = 0;
+    let (mut i, mut a, mut b) = (0, 0, 0);
+    loop {
+        // Next base line kept by both sides, or the end of all three
+        let stable = (i..base_lines.len()).find_map(|j| Some((j, ours_at[j]?, theirs_at[j]?)));
+        let (j, a_end, b_end) = stable.unwrap_or((base_lines.len(), our_lines.len(), their_lines.len()));
+
+        let base_chunk = &base_lines[i..j];
+        let our_chunk = &our_lines[a..a_end];
+        let their_chunk = &their_lines[b..b_end];
+        if our_chunk == base_chunk || our_chunk == their_chunk {
+            out.extend_from_slice(their_chunk);
+        } else if their_chunk == base_chunk {
+            out.extend_from_slice(our_chunk);
+        } else {
+            conflicts += 1;
+            out.push(MARKER_OURS);
+            out.extend_from_slice(our_chunk);
+            out.push(MARKER_BASE);
+            out.extend_from_slice(base_chunk);
+            out.push(MARKER_SEPARATOR);
+            out.extend_from_slice(their_chunk);
+            out.push(MARKER_THEIRS);
+        }
+
+        if stable.is_none() {
+            break;
+        }
+        out.push(base_lines[j]);
+        (i, a, b) = (j + 1, a_end + 1, b_end + 1);
+    }
+
+    let mut text = out.join("\n");
+    if !text.is_empty() && (ours.ends_with('\n') || theirs.ends_with('\n')) {
+        text.push('\n');
+    }
+    Merge { text, conflicts }
+}
+
+/// Line index of the first conflict marker in merged text.
+pub fn first_conflict(text: &str) -> Option<usize> {
+    text.lines().position(|l| l == MARKER_OURS)
+}
+
+/// Most cells `matching_lines` gives its table; a bigger difference is taken
+/// as rewritten outright rather than lined up.
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// For each line of `base`, the line of `other` it lines up with in a longest
+/// common subsequence, if any.
+fn matching_lines(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; base.len()];
+
+    // Lines shared at the start and end line up without the table
+    let prefix = base.iter().zip(other).take_while(|(x, y)| x == y).count();
+    let suffix = base[prefix..]
+        .iter()
+        .rev()
+        .zip(other[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    for (i, matched) in matches.iter_mut().enumerate().take(prefix) {
+        *matched = Some(i);
+    }
+    for k in 1..=suffix {
+        matches[base.len() - k] = Some(other.len() - k);
+    }
+
+    let base = &base[prefix..base.len() - suffix];
+    let other = &other[prefix..other.len() - suffix];
+    let (n, m) = (base.len(), other.len());
+    if (n + 1).saturating_mul(m + 1) > MAX_LCS_CELLS {
+        return matches;
+    }
+    // lcs[i][j] = LCS length of base[i..] and other[j..]
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if base[i] == other[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if base[i] == other[j] {
+            matches[prefix + i] = Some(prefix + j);
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_side_changes() {
+        let base = "a\nb\nc\nd\n";
+        let merged = merge3(base, "a\nB\nc\nd\n", "a\nb\nc\nD\ne\n");
+        assert_eq!(merged.text, "a\nB\nc\nD\ne\n");
+        assert_eq!(merged.conflicts, 0);
+
+        // The same change on both sides is taken once
+        let merged = merge3(base, "a\nX\nc\nd\n", "a\nX\nc\nd\n");
+        assert_eq!(merged.text, "a\nX\nc\nd\n");
+        assert_eq!(merged.conflicts, 0);
+
+        // Deleting on one side
+        let merged = merge3(base, "a\nc\nd\n", base);
+        assert_eq!(merged.text, "a\nc\nd\n");
+    }
+
+    #[test]
+    fn test_conflict() {
+        let merged = merge3("a\nb\nc\n", "a\nours\nc\n", "a\ntheirs\nc\n");
+        assert_eq!(merged.conflicts, 1);
+        assert_eq!(
+            merged.text,
+            "a\n<<<<<<< yours\nours\n||||||| original\nb\n=======\ntheirs\n>>>>>>> theirs\nc\n"
+        );
+        assert_eq!(first_conflict(&merged.text), Some(1));
+        assert_eq!(first_conflict("a\nb\n"), None);
+    }
+
+    #[test]
+    fn test_trailing_newline() {
+        assert_eq!(merge3("a\nb", "a\nB", "a\nb").text, "a\nB");
+        assert_eq!(merge3("a\nb", "a\nB", "a\nb\n").text, "a\nB\n");
+        assert_eq!(merge3("a\n", "", "a\n").text, "");
+    }
+
+    #[test]
+    fn test_large_notes() {
+        let base: Vec<String> = (0..5000).map(|i| format!("line {}", i)).collect();
+        let mut ours = base.clone();
+        ours[2500] = "changed".to_string();
+        let mut theirs = base.clone();
+        theirs.push("appended".to_string());
+        let merged = merge3(&base.join("\n"), &ours.join("\n"), &theirs.join("\n"));
+        assert_eq!(merged.conflicts, 0);
+        assert!(merged.text.contains("changed") && merged.text.ends_with("appended"));
+
+        // Too different to line up: still merged when only one side changed
+        let rewritten: Vec<String> = (0..5000).map(|i| format!("new {}", i)).collect();
+        let merged = merge3(&base.join("\n"), &rewritten.join("\n"), &base.join("\n"));
+        assert_eq!(merged.text, rewritten.join("\n"));
+    }
+}
@@ -0,0 +1,302 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fmt;
+use std::io::Write;
+
+use crate::db::{AttachmentEntry, NoteEntry, TaskEntry};
+use crate::utils;
+
+/// Exit code used when the requested note (or other record) does not exist.
+pub const EXIT_NOT_FOUND: i32 = 3;
+
+/// Error returned when a command targets a note that does not exist.
+/// `main` maps it to `EXIT_NOT_FOUND` so scripts can tell it apart from other failures.
+#[derive(Debug)]
+pub struct NotFound(pub String);
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Note '{}' not found.", self.0)
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+pub fn not_found(name: &str) -> anyhow::Error {
+    NotFound(name.to_string()).into()
+}
+
+/// Process exit code for a command that failed with `e`.
+pub fn exit_code(e: &anyhow::Error) -> i32 {
+    if e.chain().any(|cause| cause.is::<NotFound>()) {
+        EXIT_NOT_FOUND
+    } else {
+        1
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable output
+    #[default]
+    Text,
+    /// A single JSON document
+    Json,
+    /// One JSON object per line
+    Jsonl,
+    /// Tab-separated values with a header row
+    Tsv,
+}
+
+#[derive(Serialize)]
+pub struct TagCount {
+    pub name: String,
+    pub count: usize,
+}
+
+const NOTE_TSV_HEADER: &[&str] = &[
+    "id",
+    "title",
+    "tags",
+    "created_at",
+    "updated_at",
+    "pinned",
+    "archived",
+];
+
+fn note_tsv_row(note: &NoteEntry) -> Vec<String> {
+    vec![
+        note.id.to_string(),
+        note.title.clone(),
+        note.tags.join(","),
+        note.created_at.clone(),
+        note.updated_at.clone(),
+        note.pinned.to_string(),
+        note.archived.to_string(),
+    ]
+}
+
+/// Print a list of notes. Text output is one title per line.
+pub fn print_notes(format: OutputFormat, notes: &[NoteEntry]) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            for note in notes {
+                println!("{}", note.title);
+            }
+            Ok(())
+        }
+        _ => print_records(format, notes, NOTE_TSV_HEADER, note_tsv_row),
+    }
+}
+
+/// Print a single note. Text output is the raw note content.
+pub fn print_note(format: OutputFormat, note: &NoteEntry) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            print!("{}", note.note);
+            Ok(())
+        }
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(note)?);
+            Ok(())
+        }
+        OutputFormat::Jsonl => {
+            println!("{}", serde_json::to_string(note)?);
+            Ok(())
+        }
+        OutputFormat::Tsv => {
+            let mut header = NOTE_TSV_HEADER.to_vec();
+            header.push("note");
+            let mut row = note_tsv_row(note);
+            row.push(note.note.clone());
+            write_tsv(&header, std::iter::once(row))
+        }
+    }
+}
+
+/// Print tag names with their note counts.
+pub fn print_tags(format: OutputFormat, tags: &[TagCount]) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            for tag in tags {
+                println!("{} ({})", tag.name, tag.count);
+            }
+            Ok(())
+        }
+        _ => print_records(format, tags, &["name", "count"], |t| {
+            vec![t.name.clone(), t.count.to_string()]
+        }),
+    }
+}
+
+/// Print checkbox tasks. Text output is one task per line with its note.
+pub fn print_tasks(format: OutputFormat, tasks: &[TaskEntry]) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            for task in tasks {
+                let mark = if task.done { 'x' } else { ' ' };
+                let due = match &task.due {
+                    Some(due) if task.overdue => format!("  due {} (overdue)", due),
+                    Some(due) => format!("  due {}", due),
+                    None => String::new(),
+                };
+                println!("[{}] {}{}  ({}:{})", mark, task.text, due, task.title, task.line);
+            }
+            Ok(())
+        }
+        _ => print_records(format, tasks, &["title", "line", "text", "done", "due", "overdue"], |t| {
+            vec![
+                t.title.clone(),
+                t.line.to_string(),
+                t.text.clone(),
+                t.done.to_string(),
+                t.due.clone().unwrap_or_default(),
+                t.overdue.to_string(),
+            ]
+        }),
+    }
+}
+
+/// Print a note's attachments. Text output is the short hash, size and name.
+pub fn print_attachments(format: OutputFormat, attachments: &[AttachmentEntry]) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            for a in attachments {
+                println!("{}  {:>9}  {}", &a.hash[..12], utils::format_size(a.size), a.name);
+            }
+            Ok(())
+        }
+        _ => print_records(format, attachments, &["name", "hash", "size", "added_at"], |a| {
+            vec![a.name.clone(), a.hash.clone(), a.size.to_string(), a.added_at.clone()]
+        }),
+    }
+}
+
+/// A reminder or dated task that needs attention, for `scrap due`.
+#[derive(Serialize)]
+pub struct DueItem {
+    /// "reminder" or "task"
+    pub kind: &'static str,
+    pub title: String,
+    /// Local time of a reminder, or a task's due date
+    pub due: String,
+    /// How long ago a reminder came due, or whether a task is overdue
+    #[serde(skip)]
+    pub when: String,
+    /// The task's line in its note
+    pub line: Option<usize>,
+    pub text: Option<String>,
+}
+
+/// Print due reminders and tasks, reminders first.
+pub fn print_due(format: OutputFormat, items: &[DueItem]) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            for item in items {
+                match (&item.text, item.line) {
+                    (Some(text), Some(line)) => {
+                        println!("[ ] {}  due {} ({})  ({}:{})", text, item.due, item.when, item.title, line)
+                    }
+                    _ => println!("{}  reminder {} ({})", item.title, item.due, item.when),
+                }
+            }
+            Ok(())
+        }
+        _ => print_records(format, items, &["kind", "title", "due", "line", "text"], |i| {
+            vec![
+                i.kind.to_string(),
+                i.title.clone(),
+                i.due.clone(),
+                i.line.map(|l| l.to_string()).unwrap_or_default(),
+                i.text.clone().unwrap_or_default(),
+            ]
+        }),
+    }
+}
+
+fn print_records<T: Serialize>(
+    format: OutputFormat,
+    records: &[T],
+    tsv_header: &[&str],
+    tsv_row: impl Fn(&T) -> Vec<String>,
+) -> Result<()> {
+    match format {
+        OutputFormat::Text | OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(records)?);
+            Ok(())
+        }
+        OutputFormat::Jsonl => {
+            let mut out = std::io::stdout().lock();
+            for record in records {
+                writeln!(out, "{}", serde_json::to_string(record)?)?;
+            }
+            Ok(())
+        }
+        OutputFormat::Tsv => write_tsv(tsv_header, records.iter().map(tsv_row)),
+    }
+}
+
+fn write_tsv(header: &[&str], rows: impl Iterator<Item = Vec<String>>) -> Result<()> {
+    let mut out = std::io::stdout().lock();
+    writeln!(out, "{}", header.join("\t"))?;
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(|f| escape_tsv(f)).collect();
+        writeln!(out, "{}", fields.join("\t"))?;
+    }
+    Ok(())
+}
+
+/// Escape characters that would break a TSV row.
+fn escape_tsv(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_tsv() {
+        assert_eq!(escape_tsv("plain text"), "plain text");
+        assert_eq!(escape_tsv("a\tb\nc\r\n"), "a\\tb\\nc\\r\\n");
+        assert_eq!(escape_tsv("C:\\notes"), "C:\\\\notes");
+        // A backslash before a letter stays distinguishable from an escape
+        assert_eq!(escape_tsv("\\t\t"), "\\\\t\\t");
+    }
+
+    #[test]
+    fn test_note_tsv_row() {
+        let note = NoteEntry {
+            id: 4,
+            title: "a\tb".to_string(),
+            tags: vec!["x".to_string(), "y".to_string()],
+            pinned: true,
+            ..Default::default()
+        };
+        let row = note_tsv_row(&note);
+        assert_eq!(row.len(), NOTE_TSV_HEADER.len());
+        assert_eq!(row[..3], ["4", "a\tb", "x,y"]);
+        assert_eq!(row[5..], ["true", "false"]);
+        let fields: Vec<String> = row.iter().map(|f| escape_tsv(f)).collect();
+        assert_eq!(fields.join("\t").split('\t').count(), NOTE_TSV_HEADER.len());
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(exit_code(&not_found("todo")), EXIT_NOT_FOUND);
+        assert_eq!(exit_code(&not_found("todo").context("Reading note")), EXIT_NOT_FOUND);
+        assert_eq!(exit_code(&anyhow::anyhow!("Note 'todo' not found.")), 1);
+        assert_eq!(not_found("todo").to_string(), "Note 'todo' not found.");
+    }
+}
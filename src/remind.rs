@@ -0,0 +1,171 @@
+//! Reminder times. `scrap remind` and `:remind` accept relative times
+//! (`30m`, `in 2 days`), days (`tomorrow`, `friday 14:00`) and dates
+//! (`2026-10-20`, `2026-10-20 17:30`), all in local time. They are stored in
+//! UTC like the note timestamps, and nothing runs in the background: a note's
+//! reminder is due whenever scrap next looks at it after that time.
+
+use anyhow::{bail, Result};
+use rusqlite::Connection;
+
+use crate::db;
+use crate::tasks::is_date;
+
+/// Time of day for reminders given as a day without a time.
+const DEFAULT_HOUR: u32 = 9;
+
+const WEEKDAYS: &[&str] = &["sunday", "monday", "tuesday", "wednesday", "thursday", "friday", "saturday"];
+
+/// The UTC timestamp `when` refers to.
+pub fn resolve(conn: &Connection, when: &str) -> Result<String> {
+    let args = match parse(when) {
+        Some(args) => args,
+        None => bail!(
+            "Couldn't understand '{}'. Try '30m', 'in 2 days', 'tomorrow 9:00', 'friday' or '2026-10-20 17:30'.",
+            when
+        ),
+    };
+    match db::datetime(conn, &args)? {
+        Some(time) => Ok(time),
+        None => bail!("'{}' is not a valid date.", when),
+    }
+}
+
+/// SQLite `datetime()` arguments for `when`: a time value and modifiers.
+fn parse(when: &str) -> Option<Vec<String>> {
+    let when = when.trim().to_lowercase();
+    let when = when.strip_prefix("in ").unwrap_or(&when);
+    if let Some(offset) = relative(when) {
+        return Some(vec!["now".to_string(), offset]);
+    }
+
+    // `2026-10-20T17:30` as well as `2026-10-20 17:30`
+    let (day, time) = match when.split_once(' ') {
+        Some((day, time)) => (day, Some(time.trim())),
+        None => match when.get(..10) {
+            Some(day) if is_date(day) && when.as_bytes().get(10) == Some(&b't') => (day, Some(&when[11..])),
+            _ => (when, None),
+        },
+    };
+    let (hour, minute) = match time {
+        Some(time) => time_of_day(time)?,
+        None if is_date(day) || day_offset(day).is_some() => (DEFAULT_HOUR, 0),
+        // A bare time is today at that time
+        None => {
+            let (hour, minute) = time_of_day(day)?;
+            return Some(local_day(vec![], hour, minute));
+        }
+    };
+
+    if is_date(day) {
+        return Some(vec![format!("{} {:02}:{:02}", day, hour, minute), "utc".to_string()]);
+    }
+    Some(local_day(day_offset(day)?, hour, minute))
+}
+
+/// `+N unit` for relative times such as `30m`, `2h`, `3 days` or `1w`.
+fn relative(text: &str) -> Option<String> {
+    let digits = text.chars().take_while(|c| c.is_ascii_digit()).count();
+    let count: u32 = text[..digits].parse().ok()?;
+    let unit = text[digits..].trim();
+    let (count, unit) = match unit {
+        "m" | "min" | "mins" | "minute" | "minutes" => (count, "minutes"),
+        "h" | "hr" | "hrs" | "hour" | "hours" => (count, "hours"),
+        "d" | "day" | "days" => (count, "days"),
+        "w" | "week" | "weeks" => (count.checked_mul(7)?, "days"),
+        _ => return None,
+    };
+    Some(format!("+{} {}", count, unit))
+}
+
+/// Modifiers taking the start of today to the start of `day`.
+fn day_offset(day: &str) -> Option<Vec<String>> {
+    match day {
+        "today" => Some(vec![]),
+        "tomorrow" => Some(vec!["+1 day".to_string()]),
+        _ => {
+            let weekday = WEEKDAYS.iter().position(|w| *w == day || w[..3] == *day)?;
+            // The next such day, a week from today if it's today
+            Some(vec!["+1 day".to_string(), format!("weekday {}", weekday)])
+        }
+    }
+}
+
+fn local_day(offset: Vec<String>, hour: u32, minute: u32) -> Vec<String> {
+    let mut args = vec!["now".to_string(), "localtime".to_string(), "start of day".to_string()];
+    args.extend(offset);
+    args.push(format!("+{} hours", hour));
+    args.push(format!("+{} minutes", minute));
+    args.push("utc".to_string());
+    args
+}
+
+/// Hour and minute from `17:30`, `9`, `5pm` or `5:30pm`.
+fn time_of_day(text: &str) -> Option<(u32, u32)> {
+    let (text, pm) = match text.strip_suffix("pm") {
+        Some(t) => (t, Some(true)),
+        None => (text.strip_suffix("am").map_or(text, |t| t), text.ends_with("am").then_some(false)),
+    };
+    let (hour, minute) = match text.trim().split_once(':') {
+        Some((h, m)) if m.len() == 2 => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+        Some(_) => return None,
+        None => (text.trim().parse::<u32>().ok()?, 0),
+    };
+    let hour = match pm {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(true) => hour % 12 + 12,
+        Some(false) => hour % 12,
+        None => hour,
+    };
+    (hour < 24 && minute < 60).then_some((hour, minute))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Option<Vec<String>> {
+        Some(list.iter().map(|a| a.to_string()).collect())
+    }
+
+    #[test]
+    fn test_relative() {
+        assert_eq!(parse("2h"), args(&["now", "+2 hours"]));
+        assert_eq!(parse("3d"), args(&["now", "+3 days"]));
+        assert_eq!(parse("1w"), args(&["now", "+7 days"]));
+        assert_eq!(parse("in 30 minutes"), args(&["now", "+30 minutes"]));
+    }
+
+    #[test]
+    fn test_weekdays() {
+        let friday = args(&["now", "localtime", "start of day", "+1 day", "weekday 5", "+9 hours", "+0 minutes", "utc"]);
+        assert_eq!(parse("friday"), friday);
+        assert_eq!(parse("Fri"), friday);
+        assert_eq!(
+            parse("mon 5pm"),
+            args(&["now", "localtime", "start of day", "+1 day", "weekday 1", "+17 hours", "+0 minutes", "utc"])
+        );
+        assert_eq!(
+            parse("tomorrow"),
+            args(&["now", "localtime", "start of day", "+1 day", "+9 hours", "+0 minutes", "utc"])
+        );
+    }
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(parse("2026-10-20"), args(&["2026-10-20 09:00", "utc"]));
+        assert_eq!(parse("2026-10-20 17:30"), args(&["2026-10-20 17:30", "utc"]));
+        assert_eq!(parse("2026-10-20T17:30"), args(&["2026-10-20 17:30", "utc"]));
+    }
+
+    #[test]
+    fn test_rejected() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("someday"), None);
+        assert_eq!(parse("friday 25:00"), None);
+        assert_eq!(parse("2026-10-20 13pm"), None);
+        assert_eq!(parse("aääääää"), None);
+        assert_eq!(parse("2026-10-2ät9:00"), None);
+        assert_eq!(parse("99999999999w"), None);
+        assert_eq!(parse("999999999w"), None);
+    }
+}
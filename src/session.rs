@@ -0,0 +1,396 @@
+//! Editor sessions. Every run of $EDITOR gets its own temp file under
+//! `~/.scrap/temp`, with a JSON sidecar saying which note it edits. Both stay
+//! on disk until the edit is saved, so text from a crashed editor or a failed
+//! save can be found again by `scrap recover`.
+
+use anyhow::{bail, Context, Result};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::db::{self, NoteEntry};
+use crate::merge;
+use crate::utils;
+
+/// What a session is editing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Target {
+    pub title: String,
+    /// None while creating a note
+    pub note_id: Option<i64>,
+    /// The note's revision and contents when editing started
+    pub revision: i64,
+    pub base: String,
+    pub tags: Vec<String>,
+}
+
+impl Target {
+    pub fn new_note(title: &str, tags: &[String]) -> Self {
+        Self {
+            title: title.to_string(),
+            note_id: None,
+            revision: 0,
+            base: String::new(),
+            tags: tags.to_vec(),
+        }
+    }
+
+    pub fn existing(note: &NoteEntry) -> Self {
+        Self {
+            title: note.title.clone(),
+            note_id: Some(note.id),
+            revision: note.revision,
+            base: note.note.clone(),
+            tags: note.tags.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Sidecar {
+    #[serde(flatten)]
+    target: Target,
+    pid: u32,
+}
+
+/// The temp file of an edit that hasn't been saved yet.
+pub struct Session {
+    path: PathBuf,
+}
+
+impl Session {
+    /// The edit was saved or deliberately dropped; remove its temp file.
+    pub fn finish(self) {
+        remove_files(&self.path);
+    }
+}
+
+/// Write `initial` and its sidecar to a new temp file in `temp_dir`.
+fn start(temp_dir: &Path, target: &Target, initial: &str) -> Result<Session> {
+    std::fs::create_dir_all(temp_dir)?;
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let stem = format!("{}-{}-{}", utils::sanitize_filename(&target.title), std::process::id(), nanos);
+    let path = temp_dir.join(format!("{}.md", stem));
+
+    // Sidecar first, so a temp file never exists without one
+    let sidecar = Sidecar {
+        target: target.clone(),
+        pid: std::process::id(),
+    };
+    std::fs::write(sidecar_path(&path), serde_json::to_string(&sidecar)?)?;
+    std::fs::write(&path, initial)?;
+    Ok(Session { path })
+}
+
+/// Edit `initial` in $EDITOR. The returned session keeps the temp file until
+/// `Session::finish`, so call that only once the text is safely stored.
+pub fn edit(target: &Target, initial: &str) -> Result<(String, Session)> {
+    let session = start(&temp_dir()?, target, initial)?;
+
+    let editor = utils::get_editor()?;
+    let status = match Command::new(&editor).arg(&session.path).status() {
+        Ok(status) => status,
+        Err(e) => {
+            session.finish();
+            return Err(e).with_context(|| format!("Failed to open editor: {}", editor));
+        }
+    };
+
+    let contents = std::fs::read_to_string(&session.path)?;
+    if !status.success() {
+        if contents == initial {
+            session.finish();
+            bail!("Editor exited with non-zero status");
+        }
+        bail!("Editor exited with non-zero status. Your text was kept; run 'scrap recover' to restore it.");
+    }
+    Ok((contents, session))
+}
+
+/// Text left behind by an editor session that is no longer running.
+pub struct Orphan {
+    pub title: String,
+    pub contents: String,
+    /// When the temp file was last written, in Unix seconds
+    pub modified: i64,
+    /// None for temp files from scrap versions that didn't record sessions
+    target: Option<Target>,
+    path: PathBuf,
+}
+
+impl Orphan {
+    /// Short description of where the text would go, e.g. "edit of 'todo'".
+    pub fn kind(&self) -> String {
+        match &self.target {
+            Some(Target { note_id: Some(_), title, .. }) => format!("edit of '{}'", title),
+            _ => "new note".to_string(),
+        }
+    }
+
+    fn tags(&self) -> Vec<String> {
+        self.target.as_ref().map(|t| t.tags.clone()).unwrap_or_default()
+    }
+}
+
+/// Edits whose session ended without saving, most recent first. Sessions that
+/// never changed their text are removed instead of listed.
+pub fn orphans() -> Result<Vec<Orphan>> {
+    Ok(orphans_in(&temp_dir()?))
+}
+
+fn orphans_in(temp_dir: &Path) -> Vec<Orphan> {
+    let Ok(entries) = std::fs::read_dir(temp_dir) else {
+        return Vec::new();
+    };
+
+    let mut orphans = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_some_and(|e| e == "json") {
+            // Sidecar of a session whose temp file is already gone
+            if !path.with_extension("md").exists() {
+                let _ = std::fs::remove_file(&path);
+            }
+            continue;
+        }
+        if path.extension().is_none_or(|e| e != "md") {
+            continue;
+        }
+
+        let target = match std::fs::read_to_string(sidecar_path(&path)) {
+            Ok(json) => match serde_json::from_str::<Sidecar>(&json) {
+                Ok(sidecar) if process_alive(sidecar.pid) => continue,
+                Ok(sidecar) => Some(sidecar.target),
+                Err(_) => None,
+            },
+            Err(_) => None,
+        };
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let unchanged = target.as_ref().map_or("", |t| t.base.as_str());
+        if contents == unchanged {
+            remove_files(&path);
+            continue;
+        }
+
+        let title = match &target {
+            Some(target) => target.title.clone(),
+            None => path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+        };
+        let modified = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        orphans.push(Orphan {
+            title,
+            contents,
+            modified,
+            target,
+            path,
+        });
+    }
+    orphans.sort_by(|a, b| b.modified.cmp(&a.modified).then_with(|| a.path.cmp(&b.path)));
+    orphans
+}
+
+/// Save an orphaned edit and remove its temp file. Edits of a note that has
+/// changed since are merged in when they don't conflict; otherwise, and for
+/// deleted notes, the text is saved as a copy. Returns what was done.
+pub fn restore(conn: &Connection, orphan: Orphan) -> Result<String> {
+    let message = match &orphan.target {
+        Some(Target { note_id: Some(id), base, title, .. }) => match db::get_note_by_id(conn, *id)? {
+            Some(current) => {
+                let merged = merge::merge3(base, &orphan.contents, &current.note);
+                if merged.conflicts == 0 && db::update_note_if_unchanged(conn, *id, &merged.text, current.revision)? {
+                    db::mark_summary_stale(conn, *id)?;
+                    if merged.text == orphan.contents {
+                        format!("Restored your edit of '{}'", current.title)
+                    } else {
+                        format!("Merged your edit into '{}', which changed since", current.title)
+                    }
+                } else {
+                    let copy = db::copy_title(conn, &current.title)?;
+                    db::insert_note(conn, &copy, &orphan.contents, &current.tags)?;
+                    format!("'{}' changed since; saved your edit as '{}'", current.title, copy)
+                }
+            }
+            None => {
+                let copy = db::copy_title(conn, title)?;
+                db::insert_note(conn, &copy, &orphan.contents, &orphan.tags())?;
+                format!("'{}' was deleted; saved your edit as '{}'", title, copy)
+            }
+        },
+        _ => {
+            let title = if db::get_note(conn, &orphan.title)?.is_some() {
+                db::copy_title(conn, &orphan.title)?
+            } else {
+                orphan.title.clone()
+            };
+            db::insert_note(conn, &title, &orphan.contents, &orphan.tags())?;
+            format!("Restored '{}'", title)
+        }
+    };
+    discard(orphan);
+    Ok(message)
+}
+
+/// Throw away an orphaned edit.
+pub fn discard(orphan: Orphan) {
+    remove_files(&orphan.path);
+}
+
+fn temp_dir() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Could not determine home directory")?;
+    Ok(home.join(".scrap/temp"))
+}
+
+fn sidecar_path(path: &Path) -> PathBuf {
+    path.with_extension("json")
+}
+
+fn remove_files(path: &Path) {
+    let _ = std::fs::remove_file(path);
+    let _ = std::fs::remove_file(sidecar_path(path));
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return true;
+    }
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Without a cheap way to ask, only this process's own sessions count as live.
+#[cfg(not(unix))]
+fn process_alive(pid: u32) -> bool {
+    pid == std::process::id()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty temp dir of its own for each test.
+    fn temp(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("scrap-session-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Leave `text` in a session of `target` whose editor has gone away.
+    fn abandon(dir: &Path, target: &Target, text: &str) -> Session {
+        let session = start(dir, target, &target.base).unwrap();
+        std::fs::write(&session.path, text).unwrap();
+        // No process has this pid
+        let sidecar = Sidecar {
+            target: target.clone(),
+            pid: i32::MAX as u32,
+        };
+        std::fs::write(sidecar_path(&session.path), serde_json::to_string(&sidecar).unwrap()).unwrap();
+        session
+    }
+
+    fn target_of(conn: &Connection, name: &str) -> Target {
+        Target::existing(&db::get_note_entry(conn, name).unwrap().unwrap())
+    }
+
+    #[test]
+    fn test_sidecar_round_trip() {
+        let dir = temp("round-trip");
+        let target = Target {
+            title: "todo".to_string(),
+            note_id: Some(7),
+            revision: 3,
+            base: "a\n".to_string(),
+            tags: vec!["work".to_string()],
+        };
+        abandon(&dir, &target, "a\nb\n");
+
+        let orphans = orphans_in(&dir);
+        assert_eq!(orphans.len(), 1);
+        let orphan = &orphans[0];
+        assert_eq!((orphan.title.as_str(), orphan.contents.as_str()), ("todo", "a\nb\n"));
+        assert_eq!(orphan.kind(), "edit of 'todo'");
+        assert_eq!(orphan.tags(), ["work"]);
+        let restored = orphan.target.as_ref().unwrap();
+        assert_eq!((restored.note_id, restored.revision, restored.base.as_str()), (Some(7), 3, "a\n"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_live_and_unchanged_sessions_are_skipped() {
+        let dir = temp("skipped");
+        let target = Target::new_note("new", &[]);
+        // Still being edited by this process
+        let live = start(&dir, &target, "").unwrap();
+        std::fs::write(&live.path, "draft").unwrap();
+        // Gone, but nothing was typed
+        let unchanged = abandon(&dir, &target, "");
+
+        assert!(orphans_in(&dir).is_empty());
+        assert!(live.path.exists());
+        assert!(!unchanged.path.exists() && !sidecar_path(&unchanged.path).exists());
+        live.finish();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_restore_merges_into_a_changed_note() {
+        let dir = temp("merge");
+        let conn = db::open_in_memory().unwrap();
+        let id = db::insert_note(&conn, "todo", "a\nb\nc\n", &[]).unwrap();
+        let session = abandon(&dir, &target_of(&conn, "todo"), "A\nb\nc\n");
+        db::update_note(&conn, id, "a\nb\nC\n").unwrap();
+
+        let orphan = orphans_in(&dir).pop().unwrap();
+        assert_eq!(restore(&conn, orphan).unwrap(), "Merged your edit into 'todo', which changed since");
+        assert_eq!(db::get_note_by_id(&conn, id).unwrap().unwrap().note, "A\nb\nC\n");
+        assert!(!session.path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_restore_saves_a_copy_on_conflict() {
+        let dir = temp("conflict");
+        let conn = db::open_in_memory().unwrap();
+        let id = db::insert_note(&conn, "todo", "a\n", &["work".to_string()]).unwrap();
+        abandon(&dir, &target_of(&conn, "todo"), "mine\n");
+        db::update_note(&conn, id, "theirs\n").unwrap();
+
+        let orphan = orphans_in(&dir).pop().unwrap();
+        let copy = db::copy_title(&conn, "todo").unwrap();
+        let message = restore(&conn, orphan).unwrap();
+        assert_eq!(message, format!("'todo' changed since; saved your edit as '{}'", copy));
+        assert_eq!(db::get_note_by_id(&conn, id).unwrap().unwrap().note, "theirs\n");
+        let saved = db::get_note_entry(&conn, &copy).unwrap().unwrap();
+        assert_eq!((saved.note.as_str(), saved.tags.as_slice()), ("mine\n", ["work".to_string()].as_slice()));
+        assert!(orphans_in(&dir).is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_restore_unchanged_note() {
+        let dir = temp("unchanged");
+        let conn = db::open_in_memory().unwrap();
+        let id = db::insert_note(&conn, "todo", "a\n", &[]).unwrap();
+        abandon(&dir, &target_of(&conn, "todo"), "b\n");
+
+        let orphan = orphans_in(&dir).pop().unwrap();
+        assert_eq!(restore(&conn, orphan).unwrap(), "Restored your edit of 'todo'");
+        assert_eq!(db::get_note_by_id(&conn, id).unwrap().unwrap().note, "b\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,140 @@
+use clap::ValueEnum;
+use std::cmp::Ordering;
+
+use crate::db::NoteEntry;
+
+/// Orderings available to `scrap list --sort` and the TUI sort key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum SortKey {
+    /// Most recently updated first
+    #[default]
+    Updated,
+    /// Most recently created first
+    Created,
+    /// Alphabetical by title
+    Title,
+    /// Largest note first
+    Size,
+    /// Most tags first
+    TagCount,
+}
+
+impl SortKey {
+    pub fn label(self) -> &'static str {
+        match self {
+            SortKey::Updated => "updated",
+            SortKey::Created => "created",
+            SortKey::Title => "title",
+            SortKey::Size => "size",
+            SortKey::TagCount => "tag-count",
+        }
+    }
+
+    /// The next key in the TUI sort cycle.
+    pub fn next(self) -> Self {
+        match self {
+            SortKey::Updated => SortKey::Created,
+            SortKey::Created => SortKey::Title,
+            SortKey::Title => SortKey::Size,
+            SortKey::Size => SortKey::TagCount,
+            SortKey::TagCount => SortKey::Updated,
+        }
+    }
+
+    fn compare(self, a: &NoteEntry, b: &NoteEntry) -> Ordering {
+        match self {
+            SortKey::Updated => b.updated_at.cmp(&a.updated_at),
+            SortKey::Created => b.created_at.cmp(&a.created_at),
+            SortKey::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            SortKey::Size => b.note.len().cmp(&a.note.len()),
+            SortKey::TagCount => b.tags.len().cmp(&a.tags.len()),
+        }
+    }
+}
+
+/// Sort notes by `key`, keeping pinned notes first regardless of direction.
+pub fn sort_notes(notes: &mut [NoteEntry], key: SortKey, reverse: bool) {
+    notes.sort_by(|a, b| {
+        let order = key.compare(a, b);
+        let order = if reverse { order.reverse() } else { order };
+        b.pinned.cmp(&a.pinned).then(order)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(title: &str, created_at: &str, updated_at: &str, size: usize, tags: usize) -> NoteEntry {
+        NoteEntry {
+            title: title.to_string(),
+            created_at: created_at.to_string(),
+            updated_at: updated_at.to_string(),
+            note: "x".repeat(size),
+            tags: (0..tags).map(|i| i.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn notes() -> Vec<NoteEntry> {
+        vec![
+            note("banana", "2026-01-02", "2026-03-01", 5, 0),
+            note("Apple", "2026-01-03", "2026-02-01", 1, 2),
+            note("cherry", "2026-01-01", "2026-04-01", 9, 1),
+        ]
+    }
+
+    fn sorted(key: SortKey, reverse: bool) -> Vec<String> {
+        let mut notes = notes();
+        sort_notes(&mut notes, key, reverse);
+        notes.into_iter().map(|n| n.title).collect()
+    }
+
+    #[test]
+    fn test_each_key() {
+        assert_eq!(sorted(SortKey::Updated, false), ["cherry", "banana", "Apple"]);
+        assert_eq!(sorted(SortKey::Created, false), ["Apple", "banana", "cherry"]);
+        // Case doesn't matter
+        assert_eq!(sorted(SortKey::Title, false), ["Apple", "banana", "cherry"]);
+        assert_eq!(sorted(SortKey::Size, false), ["cherry", "banana", "Apple"]);
+        assert_eq!(sorted(SortKey::TagCount, false), ["Apple", "cherry", "banana"]);
+    }
+
+    #[test]
+    fn test_reverse() {
+        assert_eq!(sorted(SortKey::Updated, true), ["Apple", "banana", "cherry"]);
+        assert_eq!(sorted(SortKey::Title, true), ["cherry", "banana", "Apple"]);
+        assert_eq!(sorted(SortKey::TagCount, true), ["banana", "cherry", "Apple"]);
+    }
+
+    #[test]
+    fn test_pinned_first() {
+        for key in [SortKey::Updated, SortKey::Created, SortKey::Title, SortKey::Size, SortKey::TagCount] {
+            for reverse in [false, true] {
+                let mut notes = notes();
+                notes[0].pinned = true;
+                sort_notes(&mut notes, key, reverse);
+                assert_eq!(notes[0].title, "banana", "{:?} reverse={}", key, reverse);
+            }
+        }
+
+        let mut notes = notes();
+        notes[0].pinned = true;
+        notes[1].pinned = true;
+        sort_notes(&mut notes, SortKey::Title, true);
+        let titles: Vec<&str> = notes.iter().map(|n| n.title.as_str()).collect();
+        assert_eq!(titles, ["banana", "Apple", "cherry"]);
+    }
+
+    #[test]
+    fn test_next_cycles_through_every_key() {
+        let mut key = SortKey::default();
+        let mut seen = Vec::new();
+        for _ in 0..SortKey::value_variants().len() {
+            seen.push(key.label());
+            key = key.next();
+        }
+        assert_eq!(key, SortKey::default());
+        assert_eq!(seen, ["updated", "created", "title", "size", "tag-count"]);
+    }
+}
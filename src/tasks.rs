@@ -0,0 +1,148 @@
+//! Markdown checkbox tasks (`- [ ] call the bank due:2026-10-20`) found in
+//! notes. The database keeps an index of them, rebuilt whenever a note's
+//! contents are saved.
+
+/// A checkbox line in a note.
+pub struct Task {
+    /// 1-based line number
+    pub line: usize,
+    pub done: bool,
+    /// The task without its checkbox and `due:` date
+    pub text: String,
+    /// `YYYY-MM-DD` from a `due:` token
+    pub due: Option<String>,
+}
+
+/// Every checkbox task in `contents`, skipping fenced code blocks.
+pub fn parse(contents: &str) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    let mut in_fence = false;
+    for (i, line) in contents.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let Some(mark) = checkbox(line) else {
+            continue;
+        };
+        let rest = line[mark + 2..].trim();
+        let mut due = None;
+        let words: Vec<&str> = rest
+            .split_whitespace()
+            .filter(|word| match word.strip_prefix("due:").filter(|d| is_date(d)) {
+                Some(date) => {
+                    due = Some(date.to_string());
+                    false
+                }
+                None => true,
+            })
+            .collect();
+        tasks.push(Task {
+            line: i + 1,
+            done: line.as_bytes()[mark] != b' ',
+            text: words.join(" "),
+            due,
+        });
+    }
+    tasks
+}
+
+/// `line` with its checkbox ticked or cleared, or None if it isn't a task.
+pub fn toggle(line: &str) -> Option<String> {
+    let mark = checkbox(line)?;
+    let done = line.as_bytes()[mark] != b' ';
+    Some(format!("{}{}{}", &line[..mark], if done { ' ' } else { 'x' }, &line[mark + 1..]))
+}
+
+/// Byte index of the mark inside a list item's `[ ]`, `[x]` or `[X]`.
+fn checkbox(line: &str) -> Option<usize> {
+    let indent = line.len() - line.trim_start().len();
+    let item = &line[indent..];
+    let marker = if item.starts_with(['-', '*', '+']) {
+        1
+    } else {
+        let digits = item.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 || !item[digits..].starts_with(['.', ')']) {
+            return None;
+        }
+        digits + 1
+    };
+    let after = &item[marker..];
+    let spaces = after.len() - after.trim_start_matches(' ').len();
+    let box_start = marker + spaces;
+    let rest = &item[box_start..];
+    if spaces == 0 || !(rest.starts_with("[ ]") || rest.starts_with("[x]") || rest.starts_with("[X]")) {
+        return None;
+    }
+    if !(rest.len() == 3 || rest[3..].starts_with([' ', '\t'])) {
+        return None;
+    }
+    Some(indent + box_start + 1)
+}
+
+/// Whether `text` looks like `YYYY-MM-DD`.
+pub fn is_date(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() == 10
+        && bytes.iter().enumerate().all(|(i, b)| if i == 4 || i == 7 { *b == b'-' } else { b.is_ascii_digit() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let contents = "# Plan\n- [ ] call the bank due:2026-10-20\n* [x] done\n  + [X] nested\n1. [ ] first\n2) [x] second\n-[ ] no space\n- [] empty\n- [ ]x glued\nplain line\n";
+        let tasks = parse(contents);
+        let found: Vec<(usize, bool, &str)> = tasks.iter().map(|t| (t.line, t.done, t.text.as_str())).collect();
+        assert_eq!(
+            found,
+            vec![
+                (2, false, "call the bank"),
+                (3, true, "done"),
+                (4, true, "nested"),
+                (5, false, "first"),
+                (6, true, "second"),
+            ]
+        );
+        assert_eq!(tasks[0].due.as_deref(), Some("2026-10-20"));
+        assert_eq!(tasks[1].due, None);
+    }
+
+    #[test]
+    fn test_parse_skips_code_blocks() {
+        let tasks = parse("```\n- [ ] not a task\n```\n- [ ] a task\n");
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].line, 4);
+    }
+
+    #[test]
+    fn test_toggle() {
+        assert_eq!(toggle("- [ ] call the bank").as_deref(), Some("- [x] call the bank"));
+        assert_eq!(toggle("- [x] call the bank").as_deref(), Some("- [ ] call the bank"));
+        assert_eq!(toggle("- [X] done").as_deref(), Some("- [ ] done"));
+        assert_eq!(toggle("1. [ ] first").as_deref(), Some("1. [x] first"));
+        assert_eq!(toggle("10) [ ] tenth").as_deref(), Some("10) [x] tenth"));
+        // Everything but the mark is kept byte for byte
+        assert_eq!(
+            toggle("\t  *   [ ] émoji ✓  due:2026-10-20  ").as_deref(),
+            Some("\t  *   [x] émoji ✓  due:2026-10-20  ")
+        );
+        assert_eq!(toggle("- [ ]").as_deref(), Some("- [x]"));
+        assert_eq!(toggle("just text"), None);
+        assert_eq!(toggle("- item"), None);
+        assert_eq!(toggle("[ ] no marker"), None);
+    }
+
+    #[test]
+    fn test_is_date() {
+        assert!(is_date("2026-10-20"));
+        assert!(!is_date("2026-1-20"));
+        assert!(!is_date("2026/10/20"));
+    }
+}
@@ -0,0 +1,153 @@
+use super::registers;
+
+/// Buffer for the embedded note editor. The cursor row lives in `App::preview_cursor`
+/// so scrolling and visual selection reuse the preview's cursor model; this struct
+/// tracks the column and the text itself.
+pub struct EditorState {
+    pub note_id: i64,
+    pub title: String,
+    pub lines: Vec<String>,
+    pub col: usize,
+    pub original: String,
+    /// Revision of the note the buffer was loaded from
+    pub revision: i64,
+    pub trailing_newline: bool,
+    pub undo: Vec<(Vec<String>, usize, usize)>,
+    pub pending_discard: bool,
+    pub pending_d: bool,
+}
+
+impl EditorState {
+    pub fn new(note_id: i64, title: &str, contents: &str, revision: i64) -> Self {
+        let mut lines: Vec<String> = contents.lines().map(|l| l.to_string()).collect();
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+        Self {
+            note_id,
+            title: title.to_string(),
+            lines,
+            col: 0,
+            original: contents.to_string(),
+            revision,
+            trailing_newline: contents.ends_with('\n') || contents.is_empty(),
+            undo: Vec::new(),
+            pending_discard: false,
+            pending_d: false,
+        }
+    }
+
+    pub fn contents(&self) -> String {
+        let mut text = self.lines.join("\n");
+        if self.trailing_newline {
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.contents() != self.original
+    }
+
+    /// Record the current buffer so the next change can be undone.
+    pub fn snapshot(&mut self, row: usize) {
+        self.undo.push((self.lines.clone(), row, self.col));
+    }
+
+    /// Restore the last snapshot. Returns the cursor row to move to.
+    pub fn undo(&mut self) -> Option<usize> {
+        let (lines, row, col) = self.undo.pop()?;
+        self.lines = lines;
+        self.col = col;
+        Some(row)
+    }
+
+    pub fn line_len(&self, row: usize) -> usize {
+        self.lines.get(row).map(|l| l.chars().count()).unwrap_or(0)
+    }
+
+    /// Keep the column within the line. In normal mode the cursor sits on a character,
+    /// so it may not go past the last one.
+    pub fn clamp_col(&mut self, row: usize, insert: bool) {
+        let len = self.line_len(row);
+        let max = if insert { len } else { len.saturating_sub(1) };
+        self.col = self.col.min(max);
+    }
+
+    fn byte_index(&self, row: usize, col: usize) -> usize {
+        let line = &self.lines[row];
+        line.char_indices().nth(col).map(|(i, _)| i).unwrap_or(line.len())
+    }
+
+    pub fn insert_char(&mut self, row: usize, c: char) {
+        let idx = self.byte_index(row, self.col);
+        self.lines[row].insert(idx, c);
+        self.col += 1;
+    }
+
+    /// Split the line at the cursor. Returns the new cursor row.
+    pub fn insert_newline(&mut self, row: usize) -> usize {
+        let idx = self.byte_index(row, self.col);
+        let rest = self.lines[row].split_off(idx);
+        self.lines.insert(row + 1, rest);
+        self.col = 0;
+        row + 1
+    }
+
+    /// Delete the character before the cursor, joining lines at column zero.
+    /// Returns the new cursor row.
+    pub fn backspace(&mut self, row: usize) -> usize {
+        if self.col > 0 {
+            let idx = self.byte_index(row, self.col - 1);
+            self.lines[row].remove(idx);
+            self.col -= 1;
+            row
+        } else if row > 0 {
+            let line = self.lines.remove(row);
+            self.col = self.line_len(row - 1);
+            self.lines[row - 1].push_str(&line);
+            row - 1
+        } else {
+            row
+        }
+    }
+
+    /// Delete the character under the cursor, joining the next line at end of line.
+    pub fn delete_char(&mut self, row: usize) {
+        if self.col < self.line_len(row) {
+            let idx = self.byte_index(row, self.col);
+            self.lines[row].remove(idx);
+        } else if row + 1 < self.lines.len() {
+            let next = self.lines.remove(row + 1);
+            self.lines[row].push_str(&next);
+        }
+    }
+
+    /// Remove lines `start..=end`, keeping at least one empty line. Returns the new cursor row.
+    pub fn delete_lines(&mut self, start: usize, end: usize) -> usize {
+        let end = end.min(self.lines.len().saturating_sub(1));
+        self.lines.drain(start..=end);
+        if self.lines.is_empty() {
+            self.lines.push(String::new());
+        }
+        self.col = 0;
+        start.min(self.lines.len() - 1)
+    }
+
+    /// Open an empty line below (or above) `row`. Returns the new cursor row.
+    pub fn open_line(&mut self, row: usize, above: bool) -> usize {
+        let at = if above { row } else { row + 1 };
+        self.lines.insert(at, String::new());
+        self.col = 0;
+        at
+    }
+
+    /// Insert `text` as whole lines below (or above) `row`. Returns the row of
+    /// the first inserted line.
+    pub fn put_lines(&mut self, row: usize, text: &str, above: bool) -> usize {
+        let at = if above { row } else { row + 1 };
+        self.lines.splice(at..at, registers::lines(text).map(|l| l.to_string()));
+        self.col = 0;
+        at
+    }
+}
@@ -1,6 +1,10 @@
 use anyhow::Result;
+use clap::ValueEnum;
 use crossterm::{
-    event::{KeyCode, KeyEvent, KeyModifiers},
+    event::{
+        DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -8,9 +12,23 @@ use ratatui::prelude::*;
 
 use std::time::{Duration, Instant};
 
-use super::{App, Focus, Mode, PreviewTab};
+use super::editor::EditorState;
+use super::keymap::KeyContext;
+use super::registers::{self, Registers, CLIPBOARD, UNNAMED};
+use super::{
+    motions, palette, App, Focus, Mode, MouseDrag, NoteTab, PendingSave, PreviewTab, SplitPane, UndoAction,
+};
+use crate::commands;
+use crate::commands::capture::INBOX_TAG;
+use crate::crypto;
 use crate::db;
 use crate::llm;
+use crate::markdown;
+use crate::merge;
+use crate::remind;
+use crate::session::{self, Session, Target};
+use crate::sort::SortKey;
+use crate::tasks;
 use crate::utils;
 
 pub fn handle_key(
@@ -18,10 +36,193 @@ pub fn handle_key(
     key: KeyEvent,
     terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
 ) -> Result<()> {
+    let context = KeyContext::current(app);
+    for key in app.keymap.translate(context, key) {
+        dispatch_key(app, key, terminal)?;
+    }
+    Ok(())
+}
+
+/// Lines scrolled per mouse wheel step.
+const WHEEL_LINES: i64 = 3;
+
+/// Limits for dragging the border between the left panels and the preview.
+const MIN_LEFT_PERCENT: u16 = 15;
+const MAX_LEFT_PERCENT: u16 = 70;
+
+/// Mouse input while browsing: clicks select and focus, the wheel scrolls the
+/// pane under the pointer, dragging in the preview selects lines for `y`, and
+/// dragging the border between the panels resizes them.
+pub fn handle_mouse(app: &mut App, mouse: MouseEvent) -> Result<()> {
+    if !matches!(app.mode, Mode::Normal | Mode::TagBrowse | Mode::VisualLine) {
+        app.mouse_drag = None;
+        return Ok(());
+    }
+    let areas = app.areas;
+    let pos = Position::new(mouse.column, mouse.row);
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            app.pending_g = false;
+            app.pending_prefix = None;
+            app.pending_window = false;
+            app.preview_count = None;
+            if areas.main.contains(pos) && (mouse.column == areas.divider || mouse.column + 1 == areas.divider) {
+                app.mouse_drag = Some(MouseDrag::Divider);
+            } else if areas.note_list.contains(pos) {
+                app.focus = Focus::NoteList;
+                app.mode = Mode::Normal;
+                app.visual_anchor = None;
+                select_note(app, areas.note_list_offset + (mouse.row - areas.note_list.y) as usize);
+            } else if areas.tag_panel.contains(pos) {
+                let index = areas.tag_offset + (mouse.row - areas.tag_panel.y) as usize;
+                if index < app.visible_tags.len() {
+                    app.focus = Focus::TagPanel;
+                    app.mode = Mode::TagBrowse;
+                    app.visual_anchor = None;
+                    clear_summary(app);
+                    app.selected_tag = index;
+                    toggle_selected_tag(app);
+                }
+            } else if areas.preview.contains(pos) {
+                let line = preview_line_at(app, mouse.row);
+                app.focus = Focus::Preview;
+                app.mode = Mode::Normal;
+                app.visual_anchor = None;
+                app.preview_cursor = line;
+                let width = app.preview_raw_lines().get(line).map_or(0, |l| l.chars().count());
+                app.preview_col = ((mouse.column - areas.preview.x) as usize).min(width.saturating_sub(1));
+                app.mouse_drag = Some(MouseDrag::Selection(line));
+            } else if areas.split.contains(pos) {
+                app.focus = Focus::Split;
+                app.mode = Mode::Normal;
+                app.visual_anchor = None;
+                let len = split_line_count(app);
+                if let Some(split) = &mut app.split {
+                    let line = split.scroll as usize + (mouse.row - areas.split.y) as usize;
+                    split.cursor = line.min(len.saturating_sub(1));
+                }
+            }
+        }
+        MouseEventKind::Drag(MouseButton::Left) => match app.mouse_drag {
+            Some(MouseDrag::Divider) => {
+                let offset = mouse.column.saturating_sub(areas.main.x) as u32;
+                let percent = offset * 100 / areas.main.width.max(1) as u32;
+                app.left_percent = (percent as u16).clamp(MIN_LEFT_PERCENT, MAX_LEFT_PERCENT);
+            }
+            Some(MouseDrag::Selection(start)) => {
+                let line = preview_line_at(app, mouse.row);
+                if line != start || app.visual_anchor.is_some() {
+                    app.visual_anchor = Some(start);
+                    app.mode = Mode::VisualLine;
+                }
+                app.preview_cursor = line;
+                ensure_cursor_visible(app);
+            }
+            None => {}
+        },
+        MouseEventKind::Up(MouseButton::Left) => app.mouse_drag = None,
+        MouseEventKind::ScrollDown => scroll_at(app, pos, 1),
+        MouseEventKind::ScrollUp => scroll_at(app, pos, -1),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Select a note in the list and show it in the preview.
+fn select_note(app: &mut App, index: usize) {
+    if index >= app.filtered_notes.len() || (index == app.selected && app.active_tab.is_none()) {
+        return;
+    }
+    if app.mode == Mode::VisualLine {
+        app.mode = Mode::Normal;
+    }
+    app.visual_anchor = None;
+    app.selected = index;
+    app.active_tab = None;
+    clear_summary(app);
+    app.preview_scroll = 0;
+    app.preview_cursor = 0;
+    app.preview_col = 0;
+}
+
+/// The preview line at screen row `row`. Rows above or below the preview give
+/// the line just off screen, so dragging a selection past the edge scrolls.
+fn preview_line_at(app: &App, row: u16) -> usize {
+    let area = app.areas.preview;
+    let scroll = app.preview_scroll as usize;
+    let line = if row < area.y {
+        scroll.saturating_sub(1)
+    } else if row >= area.bottom() {
+        scroll + area.height as usize
+    } else {
+        scroll + (row - area.y) as usize
+    };
+    line.min(app.preview_raw_lines().len().saturating_sub(1))
+}
+
+fn split_line_count(app: &App) -> usize {
+    app.split
+        .as_ref()
+        .and_then(|s| app.note_by_id(s.note_id))
+        .map_or(0, |n| n.note.lines().count())
+}
+
+/// Wheel over a pane: move the note or tag selection, or scroll the text,
+/// without changing focus.
+fn scroll_at(app: &mut App, pos: Position, direction: i64) {
+    let areas = app.areas;
+    if areas.note_list.contains(pos) {
+        let last = app.filtered_notes.len().saturating_sub(1) as i64;
+        select_note(app, (app.selected as i64 + direction).clamp(0, last) as usize);
+    } else if areas.tag_panel.contains(pos) {
+        app.move_tag_selection(direction as i32);
+    } else if areas.preview.contains(pos) {
+        let max_scroll = app.preview_raw_lines().len().saturating_sub(1) as i64;
+        let scroll = (app.preview_scroll as i64 + direction * WHEEL_LINES).clamp(0, max_scroll) as usize;
+        app.preview_scroll = scroll as u16;
+        // Keep the cursor on screen so the next key doesn't jump back
+        let bottom = (scroll + (app.preview_content_height as usize).max(1) - 1).min(max_scroll as usize);
+        app.preview_cursor = app.preview_cursor.clamp(scroll, bottom);
+    } else if areas.split.contains(pos) {
+        let max_scroll = split_line_count(app).saturating_sub(1) as i64;
+        if let Some(split) = &mut app.split {
+            let scroll = (split.scroll as i64 + direction * WHEEL_LINES).clamp(0, max_scroll) as usize;
+            split.scroll = scroll as u16;
+            let bottom = (scroll + (split.content_height as usize).max(1) - 1).min(max_scroll as usize);
+            split.cursor = split.cursor.clamp(scroll, bottom);
+        }
+    }
+}
+
+fn dispatch_key(
+    app: &mut App,
+    key: KeyEvent,
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+) -> Result<()> {
+    // Ctrl-w window commands work from any pane while browsing
+    if app.pending_window {
+        app.pending_window = false;
+        handle_window_command(app, key);
+        return Ok(());
+    }
+    if key.code == KeyCode::Char('w')
+        && key.modifiers.contains(KeyModifiers::CONTROL)
+        && (app.mode == Mode::Normal || app.mode == Mode::TagBrowse)
+    {
+        app.pending_window = true;
+        return Ok(());
+    }
+
     // Preview focus is handled regardless of mode
     if app.focus == Focus::Preview && (app.mode == Mode::Normal || app.mode == Mode::VisualLine) {
         return handle_preview(app, key);
     }
+    if app.focus == Focus::Outline && app.mode == Mode::Normal {
+        return handle_outline(app, key);
+    }
+    if app.focus == Focus::Split && app.mode == Mode::Normal {
+        return handle_split(app, key);
+    }
 
     match &app.mode {
         Mode::Normal => handle_normal(app, key, terminal),
@@ -31,6 +232,17 @@ pub fn handle_key(
         Mode::AddNoteName => handle_add_note_name(app, key),
         Mode::AddNoteTags => handle_add_note_tags(app, key, terminal),
         Mode::EditTagsAdd | Mode::EditTagsRemove => handle_edit_tags(app, key),
+        Mode::ConfirmDelete => handle_confirm_delete(app, key),
+        Mode::ResolveConflict => handle_resolve_conflict(app, key),
+        Mode::Recover => handle_recover(app, key),
+        Mode::Inbox => handle_inbox(app, key),
+        Mode::InboxTitle | Mode::InboxTags | Mode::InboxMerge => handle_inbox_prompt(app, key),
+        Mode::ExtractNote => handle_extract_note(app, key),
+        Mode::Unlock => handle_unlock(app, key),
+        Mode::EditNormal => handle_edit_normal(app, key),
+        Mode::EditInsert => handle_edit_insert(app, key),
+        Mode::PreviewSearch => handle_preview_search(app, key),
+        Mode::Help => handle_help(app, key),
         Mode::VisualLine => Ok(()), // Handled by preview focus guard above
     }
 }
@@ -40,15 +252,19 @@ fn handle_normal(app: &mut App, key: KeyEvent, terminal: &mut Terminal<Crossterm
         KeyCode::Char('q') => app.should_quit = true,
         KeyCode::Char('j') | KeyCode::Down => {
             app.move_selection(1);
+            app.active_tab = None;
             clear_summary(app);
             app.preview_scroll = 0;
             app.preview_cursor = 0;
+            app.preview_col = 0;
         }
         KeyCode::Char('k') | KeyCode::Up => {
             app.move_selection(-1);
+            app.active_tab = None;
             clear_summary(app);
             app.preview_scroll = 0;
             app.preview_cursor = 0;
+            app.preview_col = 0;
         }
         KeyCode::Char('/') => {
             app.mode = Mode::Search;
@@ -58,8 +274,7 @@ fn handle_normal(app: &mut App, key: KeyEvent, terminal: &mut Terminal<Crossterm
             app.status_message = None;
         }
         KeyCode::Char(':') => {
-            app.mode = Mode::Command;
-            app.status_message = None;
+            enter_command_mode(app);
         }
         KeyCode::Char('c') => {
             app.input_buffer.clear();
@@ -69,6 +284,51 @@ fn handle_normal(app: &mut App, key: KeyEvent, terminal: &mut Terminal<Crossterm
         KeyCode::Enter => {
             open_selected_note(app, terminal)?;
         }
+        KeyCode::Char('e') => {
+            start_inline_edit(app);
+        }
+        KeyCode::Char('p') => {
+            toggle_pinned(app)?;
+        }
+        KeyCode::Char('A') => {
+            toggle_archived(app)?;
+        }
+        KeyCode::Char('u') => {
+            undo_last_action(app)?;
+        }
+        KeyCode::Char('m') => {
+            app.show_details = !app.show_details;
+        }
+        KeyCode::Char('o') => {
+            toggle_outline(app);
+        }
+        KeyCode::Char('T') => {
+            open_tab(app);
+        }
+        KeyCode::Char('I') => {
+            open_inbox(app);
+        }
+        KeyCode::Char('D') => {
+            set_reminder(app, None)?;
+        }
+        KeyCode::Char('?') => {
+            open_help(app);
+        }
+        KeyCode::Char('S') => {
+            app.sort_key = app.sort_key.next();
+            app.apply_sort();
+        }
+        KeyCode::Char('R') => {
+            app.sort_reverse = !app.sort_reverse;
+            app.apply_sort();
+        }
+        KeyCode::Char('H') => {
+            app.show_archived = !app.show_archived;
+            app.apply_filter();
+            let state = if app.show_archived { "shown" } else { "hidden" };
+            app.status_message = Some(format!("Archived notes {}", state));
+            app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+        }
         KeyCode::Esc => {
             clear_summary(app);
         }
@@ -84,6 +344,77 @@ fn handle_normal(app: &mut App, key: KeyEvent, terminal: &mut Terminal<Crossterm
     Ok(())
 }
 
+fn toggle_pinned(app: &mut App) -> Result<()> {
+    let (id, title, pinned) = match app.selected_note() {
+        Some(n) => (n.id, n.title.clone(), !n.pinned),
+        None => {
+            app.status_message = Some("No note selected".to_string());
+            return Ok(());
+        }
+    };
+    db::set_pinned(&app.conn, id, pinned)?;
+    app.refresh_notes()?;
+    app.select_note_id(id);
+    let action = if pinned { "pinned" } else { "unpinned" };
+    app.status_message = Some(format!("Note '{}' {}", title, action));
+    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+    Ok(())
+}
+
+/// Set the selected note's reminder to `when`, or dismiss it.
+fn set_reminder(app: &mut App, when: Option<&str>) -> Result<()> {
+    let (id, title, current) = match app.selected_note() {
+        Some(n) => (n.id, n.title.clone(), n.remind_at.clone()),
+        None => {
+            app.status_message = Some("No note selected".to_string());
+            return Ok(());
+        }
+    };
+    let message = match when {
+        Some(when) => match remind::resolve(&app.conn, when) {
+            Ok(at) => {
+                db::set_reminder(&app.conn, id, Some(&at))?;
+                format!("Reminder for '{}' set ({})", title, utils::relative_time(&at))
+            }
+            Err(e) => {
+                app.status_message = Some(e.to_string());
+                return Ok(());
+            }
+        },
+        None if current.is_none() => {
+            app.status_message = Some(format!("'{}' has no reminder", title));
+            return Ok(());
+        }
+        None => {
+            db::set_reminder(&app.conn, id, None)?;
+            format!("Dismissed the reminder for '{}'", title)
+        }
+    };
+    app.refresh_notes()?;
+    app.select_note_id(id);
+    app.status_message = Some(message);
+    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+    Ok(())
+}
+
+fn toggle_archived(app: &mut App) -> Result<()> {
+    let (id, title, archived) = match app.selected_note() {
+        Some(n) => (n.id, n.title.clone(), !n.archived),
+        None => {
+            app.status_message = Some("No note selected".to_string());
+            return Ok(());
+        }
+    };
+    db::set_archived(&app.conn, id, archived)?;
+    app.refresh_notes()?;
+    app.select_note_id(id);
+    clear_summary(app);
+    let action = if archived { "archived" } else { "unarchived" };
+    app.status_message = Some(format!("Note '{}' {}", title, action));
+    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+    Ok(())
+}
+
 fn clear_summary(app: &mut App) {
     app.showing_summary = false;
     app.summary_content = None;
@@ -92,10 +423,17 @@ fn clear_summary(app: &mut App) {
 }
 
 fn handle_preview(app: &mut App, key: KeyEvent) -> Result<()> {
-    let content_len = app.preview_raw_lines().len();
+    let lines = app.preview_raw_lines();
+    let content_len = lines.len();
     if content_len == 0 {
+        if let Some(prefix) = app.pending_prefix.take() {
+            handle_preview_prefix(app, &lines, prefix, key.code, 1);
+            return Ok(());
+        }
         match key.code {
             KeyCode::Char('q') => app.should_quit = true,
+            KeyCode::Char('"') => app.pending_prefix = Some('"'),
+            KeyCode::Char('p') | KeyCode::Char('P') => put_into_preview(app, false, 1)?,
             KeyCode::Esc => {
                 app.focus = Focus::NoteList;
                 app.preview_cursor = 0;
@@ -108,17 +446,45 @@ fn handle_preview(app: &mut App, key: KeyEvent) -> Result<()> {
     }
 
     let half_page = (app.preview_content_height / 2).max(1) as usize;
+    let last_line = content_len.saturating_sub(1);
 
     // Handle 'gg' sequence
     if app.pending_g {
         app.pending_g = false;
-        if key.code == KeyCode::Char('g') {
-            app.preview_cursor = 0;
-            ensure_cursor_visible(app);
-            return Ok(());
+        match key.code {
+            KeyCode::Char('g') => {
+                app.preview_cursor = app.preview_count.take().map(|n| n.saturating_sub(1).min(last_line)).unwrap_or(0);
+                app.preview_col = 0;
+                ensure_cursor_visible(app);
+                return Ok(());
+            }
+            KeyCode::Char('t') | KeyCode::Char('T') => {
+                app.preview_count = None;
+                cycle_tab(app, key.code == KeyCode::Char('t'));
+                return Ok(());
+            }
+            _ => {}
         }
     }
 
+    // Two-key sequences: z{z,t,b}, m{a-z}, '{a-z}, `{a-z}, ]], [[, "{register}
+    if let Some(prefix) = app.pending_prefix.take() {
+        let count = app.preview_count.take().unwrap_or(1);
+        handle_preview_prefix(app, &lines, prefix, key.code, count);
+        return Ok(());
+    }
+
+    // {count} prefix; a leading 0 is the line-start motion instead
+    if let KeyCode::Char(c @ '0'..='9') = key.code
+        && (c != '0' || app.preview_count.is_some())
+    {
+        let digit = c.to_digit(10).unwrap_or(0) as usize;
+        app.preview_count = Some(app.preview_count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+        return Ok(());
+    }
+    let explicit_count = app.preview_count.take();
+    let count = explicit_count.unwrap_or(1);
+
     match key.code {
         KeyCode::Char('q') => {
             if app.mode == Mode::VisualLine {
@@ -129,15 +495,15 @@ fn handle_preview(app: &mut App, key: KeyEvent) -> Result<()> {
             }
         }
         KeyCode::Char('j') | KeyCode::Down => {
-            app.preview_cursor = (app.preview_cursor + 1).min(content_len.saturating_sub(1));
+            app.preview_cursor = (app.preview_cursor + count).min(last_line);
             ensure_cursor_visible(app);
         }
         KeyCode::Char('k') | KeyCode::Up => {
-            app.preview_cursor = app.preview_cursor.saturating_sub(1);
+            app.preview_cursor = app.preview_cursor.saturating_sub(count);
             ensure_cursor_visible(app);
         }
         KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            app.preview_cursor = (app.preview_cursor + half_page).min(content_len.saturating_sub(1));
+            app.preview_cursor = (app.preview_cursor + half_page).min(last_line);
             ensure_cursor_visible(app);
         }
         KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
@@ -145,12 +511,74 @@ fn handle_preview(app: &mut App, key: KeyEvent) -> Result<()> {
             ensure_cursor_visible(app);
         }
         KeyCode::Char('g') => {
+            app.preview_count = explicit_count;
             app.pending_g = true;
         }
         KeyCode::Char('G') => {
-            app.preview_cursor = content_len.saturating_sub(1);
+            app.preview_cursor = explicit_count.map(|n| n.saturating_sub(1).min(last_line)).unwrap_or(last_line);
+            app.preview_col = 0;
+            ensure_cursor_visible(app);
+        }
+        KeyCode::Char('w') | KeyCode::Char('b') | KeyCode::Char('E') => {
+            let mut pos = (app.preview_cursor, app.preview_col);
+            for _ in 0..count {
+                pos = match key.code {
+                    KeyCode::Char('w') => motions::next_word_start(&lines, pos.0, pos.1),
+                    KeyCode::Char('b') => motions::prev_word_start(&lines, pos.0, pos.1),
+                    _ => motions::word_end(&lines, pos.0, pos.1),
+                };
+            }
+            (app.preview_cursor, app.preview_col) = pos;
+            ensure_cursor_visible(app);
+        }
+        KeyCode::Char('0') | KeyCode::Home => app.preview_col = 0,
+        KeyCode::Char('^') => {
+            let line = lines.get(app.preview_cursor).map_or("", |l| l.as_str());
+            app.preview_col = line.chars().take_while(|c| c.is_whitespace()).count();
+        }
+        KeyCode::Char('$') | KeyCode::End => {
+            let line = lines.get(app.preview_cursor).map_or("", |l| l.as_str());
+            app.preview_col = line.chars().count().saturating_sub(1);
+        }
+        KeyCode::Char('}') => {
+            for _ in 0..count {
+                app.preview_cursor = motions::next_paragraph(&lines, app.preview_cursor);
+            }
+            app.preview_col = 0;
             ensure_cursor_visible(app);
         }
+        KeyCode::Char('{') => {
+            for _ in 0..count {
+                app.preview_cursor = motions::prev_paragraph(&lines, app.preview_cursor);
+            }
+            app.preview_col = 0;
+            ensure_cursor_visible(app);
+        }
+        KeyCode::Char(c @ ('z' | 'm' | '\'' | '`' | ']' | '[' | '"')) => {
+            app.preview_count = explicit_count;
+            app.pending_prefix = Some(c);
+        }
+        KeyCode::Char('/') | KeyCode::Char('?') if app.mode != Mode::VisualLine => {
+            app.preview_search_backward = key.code == KeyCode::Char('?');
+            app.preview_search_input.clear();
+            app.mode = Mode::PreviewSearch;
+            app.status_message = None;
+        }
+        KeyCode::Char('n') | KeyCode::Char('N') => {
+            let reverse = key.code == KeyCode::Char('N');
+            for _ in 0..count {
+                jump_to_search_match(app, &lines, reverse);
+            }
+        }
+        KeyCode::Char('o') if app.mode != Mode::VisualLine => {
+            toggle_outline(app);
+        }
+        KeyCode::Char('T') if app.mode != Mode::VisualLine => {
+            open_tab(app);
+        }
+        KeyCode::Char('X') if app.mode != Mode::VisualLine => {
+            close_tab(app);
+        }
         KeyCode::Char('V') => {
             if app.mode == Mode::VisualLine {
                 app.visual_anchor = None;
@@ -163,11 +591,29 @@ fn handle_preview(app: &mut App, key: KeyEvent) -> Result<()> {
         KeyCode::Char('y') if app.mode == Mode::VisualLine => {
             yank_selection(app);
         }
+        KeyCode::Char('x') if app.mode == Mode::VisualLine && app.preview_tab == PreviewTab::Note => {
+            // Suggest a title from the first line of the selection
+            let anchor = app.visual_anchor.unwrap_or(app.preview_cursor);
+            let end = anchor.max(app.preview_cursor).min(last_line);
+            let start = anchor.min(app.preview_cursor).min(end);
+            let title = utils::title_from(&lines[start..=end].join("\n")).unwrap_or_default();
+            app.input_buffer = db::unique_title(&app.conn, &title)?;
+            app.mode = Mode::ExtractNote;
+        }
+        KeyCode::Char('x') if app.mode != Mode::VisualLine => {
+            toggle_task(app)?;
+        }
+        KeyCode::Char('p') | KeyCode::Char('P') if app.mode != Mode::VisualLine => {
+            put_into_preview(app, key.code == KeyCode::Char('P'), count)?;
+        }
+        KeyCode::Char('e') if app.mode != Mode::VisualLine => {
+            start_inline_edit(app);
+        }
         KeyCode::Tab if app.mode != Mode::VisualLine => {
             match app.preview_tab {
                 PreviewTab::Note => {
                     if app.summary_content.is_none() {
-                        if let Some(note) = app.selected_note() {
+                        if let Some(note) = app.preview_note() {
                             if let Ok(Some((summary, stale))) = db::get_summary(&app.conn, note.id) {
                                 app.summary_content = Some(summary);
                                 app.showing_summary = true;
@@ -199,6 +645,8 @@ fn handle_preview(app: &mut App, key: KeyEvent) -> Result<()> {
             if app.mode == Mode::VisualLine {
                 app.visual_anchor = None;
                 app.mode = Mode::Normal;
+            } else if app.preview_search.is_some() {
+                app.preview_search = None;
             } else {
                 app.focus = Focus::NoteList;
                 app.preview_scroll = 0;
@@ -212,174 +660,1951 @@ fn handle_preview(app: &mut App, key: KeyEvent) -> Result<()> {
                 app.mode = Mode::Normal;
             }
             app.focus = Focus::NoteList;
-            app.mode = Mode::Command;
-            app.status_message = None;
+            enter_command_mode(app);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Open the selected note in a new preview tab, or switch to its tab if already open.
+fn open_tab(app: &mut App) {
+    let note_id = match app.selected_note() {
+        Some(n) => n.id,
+        None => {
+            app.status_message = Some("No note selected".to_string());
+            return;
+        }
+    };
+    let index = match app.tabs.iter().position(|t| t.note_id == note_id) {
+        Some(i) => i,
+        None => {
+            app.tabs.push(NoteTab {
+                note_id,
+                cursor: 0,
+                scroll: 0,
+            });
+            app.tabs.len() - 1
+        }
+    };
+    switch_tab(app, Some(index));
+    app.focus = Focus::Preview;
+    app.mode = Mode::Normal;
+}
+
+/// Show tab `target` in the preview (`None` follows the list selection),
+/// saving the reading position of the tab being left.
+fn switch_tab(app: &mut App, target: Option<usize>) {
+    if target == app.active_tab {
+        return;
+    }
+    if let Some(tab) = app.active_tab.and_then(|i| app.tabs.get_mut(i)) {
+        tab.cursor = app.preview_cursor;
+        tab.scroll = app.preview_scroll;
+    }
+    let (cursor, scroll) = target
+        .and_then(|i| app.tabs.get(i))
+        .map(|t| (t.cursor, t.scroll))
+        .unwrap_or((0, 0));
+    app.active_tab = target;
+    app.preview_cursor = cursor;
+    app.preview_scroll = scroll;
+    app.preview_col = 0;
+    app.visual_anchor = None;
+    clear_summary(app);
+}
+
+/// Move to the next (or previous) tab. The list preview counts as the first tab.
+fn cycle_tab(app: &mut App, forward: bool) {
+    if app.tabs.is_empty() {
+        app.status_message = Some("No tabs open (T opens one)".to_string());
+        app.status_expires = Some(Instant::now() + Duration::from_secs(2));
+        return;
+    }
+    let count = app.tabs.len() + 1;
+    let current = app.active_tab.map(|i| i + 1).unwrap_or(0);
+    let next = if forward { (current + 1) % count } else { (current + count - 1) % count };
+    switch_tab(app, next.checked_sub(1));
+}
+
+fn close_tab(app: &mut App) {
+    let Some(index) = app.active_tab else {
+        app.status_message = Some("The list preview can't be closed".to_string());
+        app.status_expires = Some(Instant::now() + Duration::from_secs(2));
+        return;
+    };
+    switch_tab(app, index.checked_sub(1));
+    app.tabs.remove(index);
+}
+
+/// Handle the key after Ctrl-w: split, close and move between panes.
+fn handle_window_command(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Char('v') | KeyCode::Char('s') => {
+            let Some(note_id) = app.preview_note().map(|n| n.id) else {
+                app.status_message = Some("No note selected".to_string());
+                return;
+            };
+            app.split = Some(SplitPane {
+                note_id,
+                vertical: key.code == KeyCode::Char('v'),
+                cursor: app.preview_cursor,
+                scroll: app.preview_scroll,
+                content_height: 0,
+            });
+        }
+        KeyCode::Char('w') | KeyCode::Char('l') | KeyCode::Char('j') => cycle_focus(app, true),
+        KeyCode::Char('W') | KeyCode::Char('h') | KeyCode::Char('k') => cycle_focus(app, false),
+        KeyCode::Char('c') | KeyCode::Char('q') => {
+            app.split = None;
+            if app.focus == Focus::Split {
+                app.focus = Focus::Preview;
+            }
+        }
+        KeyCode::Char('o') => {
+            app.split = None;
+            app.show_outline = false;
+            if app.focus == Focus::Split || app.focus == Focus::Outline {
+                app.focus = Focus::Preview;
+            }
+        }
+        KeyCode::Char('x') => {
+            let Some(note_id) = app.preview_note().map(|n| n.id) else {
+                return;
+            };
+            if let Some(split) = &mut app.split {
+                let other = std::mem::replace(&mut split.note_id, note_id);
+                (split.cursor, app.preview_cursor) = (app.preview_cursor, split.cursor);
+                (split.scroll, app.preview_scroll) = (app.preview_scroll, split.scroll);
+                if let Some(i) = app.tabs.iter().position(|t| t.note_id == other) {
+                    app.active_tab = Some(i);
+                } else {
+                    app.tabs.push(NoteTab {
+                        note_id: other,
+                        cursor: 0,
+                        scroll: 0,
+                    });
+                    app.active_tab = Some(app.tabs.len() - 1);
+                }
+                app.preview_col = 0;
+                clear_summary(app);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Move focus to the next (or previous) visible pane.
+fn cycle_focus(app: &mut App, forward: bool) {
+    let mut panes = vec![Focus::NoteList, Focus::TagPanel, Focus::Preview];
+    if app.split.is_some() {
+        panes.push(Focus::Split);
+    }
+    if app.show_outline {
+        panes.push(Focus::Outline);
+    }
+    let current = panes.iter().position(|f| *f == app.focus).unwrap_or(0);
+    let next = if forward {
+        (current + 1) % panes.len()
+    } else {
+        (current + panes.len() - 1) % panes.len()
+    };
+    app.focus = panes[next].clone();
+    app.mode = if app.focus == Focus::TagPanel { Mode::TagBrowse } else { Mode::Normal };
+    app.visual_anchor = None;
+    app.pending_g = false;
+}
+
+fn handle_split(app: &mut App, key: KeyEvent) -> Result<()> {
+    let len = split_line_count(app);
+    let pending_g = std::mem::take(&mut app.pending_g);
+    let Some(split) = &mut app.split else {
+        app.focus = Focus::Preview;
+        return Ok(());
+    };
+    let last_line = len.saturating_sub(1);
+    let half_page = (split.content_height / 2).max(1) as usize;
+    match key.code {
+        KeyCode::Char('q') => app.should_quit = true,
+        KeyCode::Char('j') | KeyCode::Down => split.cursor = (split.cursor + 1).min(last_line),
+        KeyCode::Char('k') | KeyCode::Up => split.cursor = split.cursor.saturating_sub(1),
+        KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            split.cursor = (split.cursor + half_page).min(last_line);
+        }
+        KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            split.cursor = split.cursor.saturating_sub(half_page);
+        }
+        KeyCode::Char('g') if pending_g => split.cursor = 0,
+        KeyCode::Char('g') => app.pending_g = true,
+        KeyCode::Char('G') => split.cursor = last_line,
+        KeyCode::Esc | KeyCode::Tab => app.focus = Focus::Preview,
+        _ => {}
+    }
+    // Keep the cursor inside the viewport
+    let height = split.content_height.max(1) as usize;
+    let scroll = split.scroll as usize;
+    if split.cursor < scroll {
+        split.scroll = split.cursor as u16;
+    } else if split.cursor >= scroll + height {
+        split.scroll = (split.cursor + 1 - height) as u16;
+    }
+    Ok(())
+}
+
+/// Show the outline and focus it on the current section, or hide it if shown.
+fn toggle_outline(app: &mut App) {
+    if app.show_outline {
+        app.show_outline = false;
+        if app.focus == Focus::Outline {
+            app.focus = Focus::Preview;
+        }
+        return;
+    }
+    let outline = markdown::outline(&app.preview_raw_lines());
+    if outline.is_empty() {
+        app.status_message = Some("No headings in this note".to_string());
+        app.status_expires = Some(Instant::now() + Duration::from_secs(2));
+        return;
+    }
+    app.show_outline = true;
+    app.outline_selected = motions::current_section(&outline, app.preview_cursor).unwrap_or(0);
+    app.focus = Focus::Outline;
+    app.visual_anchor = None;
+    app.status_message = None;
+}
+
+/// Move the preview cursor to a heading and scroll it to the top of the viewport.
+fn jump_to_heading(app: &mut App, row: usize) {
+    app.preview_cursor = row;
+    app.preview_col = 0;
+    app.preview_scroll = row as u16;
+}
+
+fn handle_outline(app: &mut App, key: KeyEvent) -> Result<()> {
+    let outline = markdown::outline(&app.preview_raw_lines());
+    if outline.is_empty() {
+        app.show_outline = false;
+        app.focus = Focus::Preview;
+        return Ok(());
+    }
+    let last = outline.len() - 1;
+    match key.code {
+        KeyCode::Char('q') => app.should_quit = true,
+        KeyCode::Char('j') | KeyCode::Down => {
+            app.outline_selected = (app.outline_selected + 1).min(last);
+            jump_to_heading(app, outline[app.outline_selected].0);
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.outline_selected = app.outline_selected.saturating_sub(1).min(last);
+            jump_to_heading(app, outline[app.outline_selected].0);
+        }
+        KeyCode::Char('g') => {
+            app.outline_selected = 0;
+            jump_to_heading(app, outline[0].0);
+        }
+        KeyCode::Char('G') => {
+            app.outline_selected = last;
+            jump_to_heading(app, outline[last].0);
+        }
+        KeyCode::Enter => {
+            jump_to_heading(app, outline[app.outline_selected.min(last)].0);
+            app.focus = Focus::Preview;
+        }
+        KeyCode::Esc | KeyCode::Tab => {
+            app.focus = Focus::Preview;
+        }
+        KeyCode::Char('o') => {
+            toggle_outline(app);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Complete a two-key preview sequence started by `prefix`.
+fn handle_preview_prefix(app: &mut App, lines: &[String], prefix: char, code: KeyCode, count: usize) {
+    let note_id = app.preview_note().map(|n| n.id).unwrap_or_default();
+    match (prefix, code) {
+        ('z', KeyCode::Char('z')) => {
+            let half = app.preview_content_height as usize / 2;
+            app.preview_scroll = app.preview_cursor.saturating_sub(half) as u16;
+        }
+        ('z', KeyCode::Char('t')) => {
+            app.preview_scroll = app.preview_cursor as u16;
+        }
+        ('z', KeyCode::Char('b')) => {
+            let height = app.preview_content_height as usize;
+            app.preview_scroll = (app.preview_cursor + 1).saturating_sub(height) as u16;
+        }
+        ('"', KeyCode::Char(c)) if Registers::is_valid(c) => {
+            app.pending_register = Some(c);
+            // A count typed before the register still applies to the put
+            app.preview_count = Some(count).filter(|&n| n > 1);
+        }
+        ('m', KeyCode::Char(c)) if c.is_ascii_lowercase() => {
+            app.marks.insert((note_id, c), (app.preview_cursor, app.preview_col));
+            app.status_message = Some(format!("Mark '{}' set", c));
+            app.status_expires = Some(Instant::now() + Duration::from_secs(2));
+        }
+        ('\'' | '`', KeyCode::Char(c)) => match app.marks.get(&(note_id, c)).copied() {
+            Some((row, col)) => {
+                app.preview_cursor = row.min(lines.len().saturating_sub(1));
+                app.preview_col = if prefix == '`' { col } else { 0 };
+                ensure_cursor_visible(app);
+            }
+            None => {
+                app.status_message = Some(format!("Mark '{}' not set", c));
+                app.status_expires = Some(Instant::now() + Duration::from_secs(2));
+            }
+        },
+        (']', KeyCode::Char(']')) | ('[', KeyCode::Char('[')) => {
+            let mut row = app.preview_cursor;
+            for _ in 0..count {
+                let next = if prefix == ']' {
+                    motions::next_heading(lines, row)
+                } else {
+                    motions::prev_heading(lines, row)
+                };
+                match next {
+                    Some(r) => row = r,
+                    None => break,
+                }
+            }
+            app.preview_cursor = row;
+            app.preview_col = 0;
+            ensure_cursor_visible(app);
+        }
+        _ => {}
+    }
+}
+
+/// Move to the next match of the last preview search. `reverse` flips its direction (`N`).
+fn jump_to_search_match(app: &mut App, lines: &[String], reverse: bool) {
+    let query = match &app.preview_search {
+        Some(q) if !q.is_empty() => q.clone(),
+        _ => {
+            app.status_message = Some("No previous search".to_string());
+            app.status_expires = Some(Instant::now() + Duration::from_secs(2));
+            return;
+        }
+    };
+    let backward = app.preview_search_backward != reverse;
+    match motions::find_match(lines, &query, app.preview_cursor, app.preview_col, backward) {
+        Some(((row, col), wrapped)) => {
+            app.preview_cursor = row;
+            app.preview_col = col;
+            if wrapped {
+                let msg = if backward {
+                    "Search hit TOP, continuing at BOTTOM"
+                } else {
+                    "Search hit BOTTOM, continuing at TOP"
+                };
+                app.status_message = Some(msg.to_string());
+                app.status_expires = Some(Instant::now() + Duration::from_secs(2));
+            }
+            ensure_cursor_visible(app);
+        }
+        None => {
+            app.status_message = Some(format!("Pattern not found: {}", query));
+            app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+        }
+    }
+}
+
+fn handle_preview_search(app: &mut App, key: KeyEvent) -> Result<()> {
+    match key.code {
+        KeyCode::Esc => {
+            app.mode = Mode::Normal;
+        }
+        KeyCode::Enter => {
+            app.mode = Mode::Normal;
+            if !app.preview_search_input.is_empty() {
+                app.preview_search = Some(app.preview_search_input.clone());
+            }
+            let lines = app.preview_raw_lines();
+            jump_to_search_match(app, &lines, false);
+        }
+        KeyCode::Backspace if app.preview_search_input.is_empty() => {
+            app.mode = Mode::Normal;
+        }
+        KeyCode::Backspace => {
+            app.preview_search_input.pop();
+        }
+        KeyCode::Char(c) => {
+            app.preview_search_input.push(c);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Scroll the viewport so the cursor line is visible.
+fn ensure_cursor_visible(app: &mut App) {
+    let scroll = app.preview_scroll as usize;
+    let viewport_height = app.preview_content_height as usize;
+    let scrolloff: usize = 2;
+
+    if viewport_height == 0 {
+        return;
+    }
+
+    if app.preview_cursor < scroll + scrolloff {
+        app.preview_scroll = app.preview_cursor.saturating_sub(scrolloff) as u16;
+    } else if app.preview_cursor >= scroll + viewport_height.saturating_sub(scrolloff) {
+        app.preview_scroll = (app.preview_cursor + scrolloff + 1).saturating_sub(viewport_height) as u16;
+    }
+}
+
+/// Yank the visually selected lines into the register named with `"x`, or by
+/// default into the unnamed register and the system clipboard.
+fn yank_selection(app: &mut App) {
+    let anchor = match app.visual_anchor {
+        Some(a) => a,
+        None => return,
+    };
+
+    let lines = app.preview_raw_lines();
+    if lines.is_empty() {
+        return;
+    }
+
+    let end = anchor.max(app.preview_cursor).min(lines.len() - 1);
+    let start = anchor.min(app.preview_cursor).min(end);
+
+    let selected_text: String = lines[start..=end].join("\n");
+    let line_count = end - start + 1;
+
+    let register = app.pending_register.take();
+    let stored = app.registers.store(register.unwrap_or(CLIPBOARD), &selected_text);
+    app.status_message = Some(match (register, stored) {
+        (_, Err(_)) => format!("{} lines yanked (clipboard unavailable)", line_count),
+        (Some(name), Ok(())) if name != CLIPBOARD => format!("{} lines yanked into \"{}", line_count, name),
+        _ => format!("{} lines yanked", line_count),
+    });
+    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+
+    // Exit visual mode
+    app.visual_anchor = None;
+    app.mode = Mode::Normal;
+}
+
+/// The text in the register named with `"x` (the unnamed one by default), or
+/// None with a status message if there's nothing to put.
+fn take_register(app: &mut App) -> Option<String> {
+    let name = app.pending_register.take().unwrap_or(UNNAMED);
+    let message = match app.registers.get(name) {
+        Ok(Some(text)) => return Some(text),
+        Ok(None) => format!("Register \"{} is empty", name),
+        Err(e) => e.to_string(),
+    };
+    app.status_message = Some(message);
+    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+    None
+}
+
+/// Tick or clear the checkbox on the preview cursor line and save the note.
+fn toggle_task(app: &mut App) -> Result<()> {
+    if app.preview_tab != PreviewTab::Note {
+        return Ok(());
+    }
+    let Some(note) = app.preview_note().cloned() else {
+        return Ok(());
+    };
+    let mut lines: Vec<String> = note.note.lines().map(|l| l.to_string()).collect();
+    let Some(toggled) = lines.get(app.preview_cursor).and_then(|l| tasks::toggle(l)) else {
+        app.status_message = Some("No task on this line".to_string());
+        app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+        return Ok(());
+    };
+    let done = tasks::parse(&toggled).first().is_some_and(|t| t.done);
+    lines[app.preview_cursor] = toggled;
+    let mut contents = lines.join("\n");
+    if note.note.ends_with('\n') {
+        contents.push('\n');
+    }
+
+    if !db::update_note_if_unchanged(&app.conn, note.id, &contents, note.revision)? {
+        app.status_message = Some(format!("'{}' changed outside scrap; try again", note.title));
+        app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+        return Ok(());
+    }
+    db::mark_summary_stale(&app.conn, note.id)?;
+    app.undo_stack.push(UndoAction::Edit {
+        id: note.id,
+        title: note.title.clone(),
+        previous: note.note,
+        revision: note.revision + 1,
+    });
+    app.refresh_notes()?;
+    if app.active_tab.is_none() {
+        app.select_note_id(note.id);
+    }
+    app.status_message = Some(if done { "Task done" } else { "Task reopened" }.to_string());
+    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+    Ok(())
+}
+
+/// Put register lines below (or above) the preview cursor and save the note.
+fn put_into_preview(app: &mut App, above: bool, count: usize) -> Result<()> {
+    if app.preview_tab != PreviewTab::Note {
+        return Ok(());
+    }
+    let Some(note) = app.preview_note().cloned() else {
+        app.status_message = Some("No note selected".to_string());
+        return Ok(());
+    };
+    if note.is_locked() {
+        start_unlock(app);
+        return Ok(());
+    }
+    let Some(text) = take_register(app) else {
+        return Ok(());
+    };
+
+    let mut lines: Vec<&str> = note.note.lines().collect();
+    let at = if lines.is_empty() || above {
+        app.preview_cursor.min(lines.len())
+    } else {
+        (app.preview_cursor + 1).min(lines.len())
+    };
+    let put: Vec<&str> = std::iter::repeat_n(registers::lines(&text), count).flatten().collect();
+    let put_count = put.len();
+    lines.splice(at..at, put);
+    let mut contents = lines.join("\n");
+    if note.note.ends_with('\n') || note.note.is_empty() {
+        contents.push('\n');
+    }
+
+    if !db::update_note_if_unchanged(&app.conn, note.id, &contents, note.revision)? {
+        app.status_message = Some(format!("'{}' changed outside scrap; try again", note.title));
+        app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+        return Ok(());
+    }
+    db::mark_summary_stale(&app.conn, note.id)?;
+    app.undo_stack.push(UndoAction::Edit {
+        id: note.id,
+        title: note.title.clone(),
+        previous: note.note,
+        revision: note.revision + 1,
+    });
+    app.refresh_notes()?;
+    if app.active_tab.is_none() {
+        app.select_note_id(note.id);
+    }
+    app.preview_cursor = at;
+    app.preview_col = 0;
+    ensure_cursor_visible(app);
+    app.status_message = Some(format!("{} lines put into '{}'", put_count, note.title));
+    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+    Ok(())
+}
+
+/// The title prompt for lines extracted from the preview. Esc goes back to
+/// the selection.
+fn handle_extract_note(app: &mut App, key: KeyEvent) -> Result<()> {
+    match key.code {
+        KeyCode::Esc => app.mode = Mode::VisualLine,
+        KeyCode::Enter => {
+            let title = app.input_buffer.trim().to_string();
+            if let Err(e) = utils::validate_name(&title) {
+                app.status_message = Some(format!("Invalid name: {}", e));
+                return Ok(());
+            }
+            if db::get_note(&app.conn, &title)?.is_some() {
+                app.status_message = Some(format!("Note '{}' already exists", title));
+                return Ok(());
+            }
+            extract_selection(app, &title)?;
+        }
+        KeyCode::Backspace => {
+            app.input_buffer.pop();
+        }
+        KeyCode::Char(c) => app.input_buffer.push(c),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Ask for the passphrase of the previewed encrypted note.
+fn start_unlock(app: &mut App) {
+    app.input_buffer.clear();
+    app.mode = Mode::Unlock;
+}
+
+fn handle_unlock(app: &mut App, key: KeyEvent) -> Result<()> {
+    match key.code {
+        KeyCode::Esc => {
+            app.input_buffer.clear();
+            app.mode = Mode::Normal;
+        }
+        KeyCode::Enter => {
+            let passphrase = std::mem::take(&mut app.input_buffer);
+            let Some((sample, title)) = app
+                .preview_note()
+                .filter(|n| n.is_locked())
+                .map(|n| (n.note.clone(), n.title.clone()))
+            else {
+                app.mode = Mode::Normal;
+                return Ok(());
+            };
+            match crypto::try_passphrase(&passphrase, &sample, &title) {
+                Ok(_) => {
+                    app.mode = Mode::Normal;
+                    app.refresh_notes()?;
+                    app.status_message = Some("Unlocked until you quit or :lock".to_string());
+                    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+                }
+                Err(e) => app.status_message = Some(e.to_string()),
+            }
+        }
+        KeyCode::Backspace => {
+            app.input_buffer.pop();
+        }
+        KeyCode::Char(c) => app.input_buffer.push(c),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Forget the passphrase. Undo history is dropped too, since it holds
+/// decrypted text.
+fn lock_notes(app: &mut App) -> Result<()> {
+    if app.editor.is_some() {
+        app.status_message = Some("Save or close the editor first".to_string());
+        return Ok(());
+    }
+    if !crypto::is_unlocked() {
+        app.status_message = Some("Encrypted notes are already locked".to_string());
+        return Ok(());
+    }
+    crypto::lock();
+    app.undo_stack.clear();
+    app.refresh_notes()?;
+    app.status_message = Some("Encrypted notes locked".to_string());
+    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+    Ok(())
+}
+
+/// Add note `title` made from note `source`: encrypted if that is.
+fn insert_like(app: &App, source: i64, title: &str, contents: &str, tags: &[String]) -> Result<i64> {
+    match app.notes.iter().find(|n| n.id == source) {
+        Some(n) if n.encrypted => db::insert_encrypted_note(&app.conn, title, contents, tags),
+        _ => db::insert_note(&app.conn, title, contents, tags),
+    }
+}
+
+/// Move the visually selected lines out of the previewed note into a new note
+/// titled `title`, with the same tags.
+fn extract_selection(app: &mut App, title: &str) -> Result<()> {
+    let (Some(anchor), Some(note)) = (app.visual_anchor, app.preview_note().cloned()) else {
+        app.mode = Mode::Normal;
+        return Ok(());
+    };
+    app.visual_anchor = None;
+    app.mode = Mode::Normal;
+    if note.is_locked() {
+        start_unlock(app);
+        return Ok(());
+    }
+
+    let mut lines: Vec<&str> = note.note.lines().collect();
+    if lines.is_empty() {
+        return Ok(());
+    }
+    let start = anchor.min(app.preview_cursor).min(lines.len() - 1);
+    let end = anchor.max(app.preview_cursor).min(lines.len() - 1);
+    let extracted: Vec<&str> = lines.drain(start..=end).collect();
+    if crypto::is_encrypted(extracted[0]) {
+        app.status_message = Some(format!("Notes can't start with '{}'", crypto::PREFIX));
+        app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+        return Ok(());
+    }
+    let mut contents = lines.join("\n");
+    if note.note.ends_with('\n') && !contents.is_empty() {
+        contents.push('\n');
+    }
+
+    if !db::update_note_if_unchanged(&app.conn, note.id, &contents, note.revision)? {
+        app.status_message = Some(format!("'{}' changed outside scrap; try again", note.title));
+        app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+        return Ok(());
+    }
+    db::mark_summary_stale(&app.conn, note.id)?;
+    let extracted_text = format!("{}\n", extracted.join("\n"));
+    let id = insert_like(app, note.id, title, &extracted_text, &note.tags)?;
+    db::copy_attachments(&app.conn, note.id, id, &extracted_text)?;
+    app.undo_stack.push(UndoAction::Extract {
+        id,
+        title: title.to_string(),
+        source: note.id,
+        source_title: note.title.clone(),
+        previous: note.note.clone(),
+        revision: note.revision + 1,
+    });
+
+    app.refresh_notes()?;
+    if app.active_tab.is_none() {
+        app.select_note_id(note.id);
+    }
+    app.preview_cursor = start.min(lines.len().saturating_sub(1));
+    app.preview_col = 0;
+    ensure_cursor_visible(app);
+    app.status_message = Some(format!("Extracted {} lines into '{}'", extracted.len(), title));
+    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+    Ok(())
+}
+
+fn handle_tag_browse(app: &mut App, key: KeyEvent) -> Result<()> {
+    match key.code {
+        KeyCode::Char('q') => app.should_quit = true,
+        KeyCode::Char('j') | KeyCode::Down => app.move_tag_selection(1),
+        KeyCode::Char('k') | KeyCode::Up => app.move_tag_selection(-1),
+        KeyCode::Enter => toggle_selected_tag(app),
+        KeyCode::Esc => {
+            app.active_tag_filters.clear();
+            app.apply_filter();
+            app.selected = 0;
+            app.focus = Focus::NoteList;
+            app.mode = Mode::Normal;
+            app.status_message = None;
+        }
+        KeyCode::Tab => {
+            app.focus = Focus::Preview;
+            app.mode = Mode::Normal;
+            app.preview_scroll = 0;
+            app.preview_cursor = 0;
+        }
+        KeyCode::Char('?') => {
+            open_help(app);
+        }
+        KeyCode::Char(':') => {
+            app.focus = Focus::NoteList;
+            enter_command_mode(app);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Add or remove the selected tag from the note list filter.
+fn toggle_selected_tag(app: &mut App) {
+    if let Some(tag_name) = app.visible_tags.get(app.selected_tag).map(|t| t.name.clone()) {
+        if let Some(pos) = app.active_tag_filters.iter().position(|t| t == &tag_name) {
+            app.active_tag_filters.remove(pos);
+        } else {
+            app.active_tag_filters.push(tag_name);
+        }
+        app.apply_filter();
+        app.selected = 0;
+        if app.active_tag_filters.is_empty() {
+            app.status_message = None;
+        } else {
+            app.status_message = Some(format!("Filtered by: {}", app.active_tag_filters.join(", ")));
+        }
+    }
+}
+
+fn handle_search(app: &mut App, key: KeyEvent) -> Result<()> {
+    match key.code {
+        KeyCode::Esc => {
+            app.search_query.clear();
+            app.apply_filter();
+            app.selected = 0;
+            app.mode = Mode::Normal;
+        }
+        KeyCode::Enter => {
+            app.mode = Mode::Normal;
+        }
+        KeyCode::Backspace => {
+            app.search_query.pop();
+            app.apply_filter();
+            app.selected = 0;
+        }
+        KeyCode::Char(c) => {
+            app.search_query.push(c);
+            app.apply_filter();
+            app.selected = 0;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn enter_command_mode(app: &mut App) {
+    app.mode = Mode::Command;
+    app.command_input.clear();
+    app.history_index = None;
+    app.completions.clear();
+    app.completion_index = None;
+    app.status_message = None;
+}
+
+fn handle_command(
+    app: &mut App,
+    key: KeyEvent,
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+) -> Result<()> {
+    if !matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+        app.completions.clear();
+        app.completion_index = None;
+    }
+    match key.code {
+        KeyCode::Esc => {
+            app.mode = Mode::Normal;
+        }
+        KeyCode::Enter => {
+            app.mode = Mode::Normal;
+            let line = app.command_input.trim().to_string();
+            if line.is_empty() {
+                return Ok(());
+            }
+            if app.command_history.last() != Some(&line) {
+                app.command_history.push(line.clone());
+            }
+            execute_command(app, &line, terminal)?;
+        }
+        KeyCode::Tab | KeyCode::BackTab => {
+            complete_command(app, key.code == KeyCode::Tab);
+        }
+        KeyCode::Up => {
+            if app.command_history.is_empty() {
+                return Ok(());
+            }
+            let index = match app.history_index {
+                Some(i) => i.saturating_sub(1),
+                None => app.command_history.len() - 1,
+            };
+            app.history_index = Some(index);
+            app.command_input = app.command_history[index].clone();
+        }
+        KeyCode::Down => {
+            if let Some(i) = app.history_index {
+                if i + 1 < app.command_history.len() {
+                    app.history_index = Some(i + 1);
+                    app.command_input = app.command_history[i + 1].clone();
+                } else {
+                    app.history_index = None;
+                    app.command_input.clear();
+                }
+            }
+        }
+        KeyCode::Backspace if app.command_input.is_empty() => {
+            app.mode = Mode::Normal;
+        }
+        KeyCode::Backspace => {
+            app.command_input.pop();
+        }
+        KeyCode::Char(c) => {
+            app.command_input.push(c);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Complete the word under the cursor. A single candidate is accepted outright;
+/// with several, repeated Tab (or Shift-Tab) cycles through them.
+fn complete_command(app: &mut App, forward: bool) {
+    if app.completion_index.is_none() {
+        let tags: Vec<String> = app.all_tags.iter().map(|t| t.name.clone()).collect();
+        app.completions = palette::complete(&app.command_input, &tags);
+        match app.completions.len() {
+            0 => {}
+            1 => {
+                let mut line = app.completions.remove(0);
+                if !line.ends_with('/') {
+                    line.push(' ');
+                }
+                app.command_input = line;
+            }
+            _ => {
+                app.completion_index = Some(0);
+                app.command_input = app.completions[0].clone();
+            }
+        }
+        return;
+    }
+    let len = app.completions.len();
+    let index = app.completion_index.unwrap_or(0);
+    let next = if forward { (index + 1) % len } else { (index + len - 1) % len };
+    app.completion_index = Some(next);
+    app.command_input = app.completions[next].clone();
+}
+
+/// Run a line typed at the `:` prompt.
+fn execute_command(
+    app: &mut App,
+    line: &str,
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+) -> Result<()> {
+    let (name, args) = palette::parse(line);
+    let Some(spec) = palette::find(name) else {
+        app.status_message = Some(format!("Unknown command: {} (? for help)", name));
+        app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+        return Ok(());
+    };
+    let usage = format!("Usage: :{}", palette::usage(spec));
+
+    match spec.name {
+        "open" => open_selected_note(app, terminal)?,
+        "edit" => start_inline_edit(app),
+        "add" => {
+            let mut words = args.split_whitespace().map(|s| s.to_string());
+            let Some(title) = words.next() else {
+                app.input_buffer.clear();
+                app.tags_buffer.clear();
+                app.mode = Mode::AddNoteName;
+                return Ok(());
+            };
+            let tags: Vec<String> = words.collect();
+            if let Err(e) = utils::validate_name(&title).and_then(|_| utils::validate_tags(&tags)) {
+                app.status_message = Some(e.to_string());
+                return Ok(());
+            }
+            if db::get_note(&app.conn, &title)?.is_some() {
+                app.status_message = Some(format!("Note '{}' already exists", title));
+                return Ok(());
+            }
+            create_note(app, terminal, &title, &tags)?;
+        }
+        "rename" => {
+            if args.is_empty() {
+                app.status_message = Some(usage);
+                return Ok(());
+            }
+            rename_selected_note(app, args)?;
+        }
+        "tag" => {
+            if args.is_empty() {
+                app.input_buffer.clear();
+                app.mode = Mode::EditTagsAdd;
+                return Ok(());
+            }
+            retag_selected_note(app, args)?;
+        }
+        "pin" => toggle_pinned(app)?,
+        "archive" => toggle_archived(app)?,
+        "delete" => {
+            if app.selected_note().is_some() {
+                app.mode = Mode::ConfirmDelete;
+            } else {
+                app.status_message = Some("No note selected".to_string());
+            }
+        }
+        "undo" => undo_last_action(app)?,
+        "summarize" => summarize_selected_note(app)?,
+        "search" => {
+            app.search_query = args.to_string();
+            app.apply_filter();
+            app.selected = 0;
+        }
+        "sort" => {
+            let mut reverse = false;
+            let mut sort_key = None;
+            for word in args.split_whitespace() {
+                if word == "reverse" {
+                    reverse = true;
+                } else {
+                    match SortKey::from_str(word, true) {
+                        Ok(k) => sort_key = Some(k),
+                        Err(_) => {
+                            app.status_message = Some(format!("Unknown sort key '{}'. {}", word, usage));
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+            match sort_key {
+                Some(k) => {
+                    app.sort_key = k;
+                    app.sort_reverse = reverse;
+                }
+                None if reverse => app.sort_reverse = !app.sort_reverse,
+                None => {
+                    app.status_message = Some(usage);
+                    return Ok(());
+                }
+            }
+            app.apply_sort();
+        }
+        "export" => {
+            if args.is_empty() {
+                app.status_message = Some(usage);
+                return Ok(());
+            }
+            let path = palette::expand_home(args);
+            match commands::export::export_to(&app.conn, &path) {
+                Ok(count) => app.status_message = Some(format!("Exported {} notes to {}", count, path)),
+                Err(e) => app.status_message = Some(format!("Error: {:#}", e)),
+            }
+            app.status_expires = Some(Instant::now() + Duration::from_secs(5));
+        }
+        "inbox" => open_inbox(app),
+        "lock" => lock_notes(app)?,
+        "remind" => match args {
+            "" => app.status_message = Some(usage),
+            "off" => set_reminder(app, None)?,
+            when => set_reminder(app, Some(when))?,
+        },
+        "help" => open_help(app),
+        "quit" => app.should_quit = true,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn rename_selected_note(app: &mut App, title: &str) -> Result<()> {
+    let (id, previous) = match app.selected_note() {
+        Some(n) => (n.id, n.title.clone()),
+        None => {
+            app.status_message = Some("No note selected".to_string());
+            return Ok(());
+        }
+    };
+    if let Err(e) = utils::validate_name(title) {
+        app.status_message = Some(format!("Invalid name: {}", e));
+        return Ok(());
+    }
+    if db::get_note(&app.conn, title)?.is_some() {
+        app.status_message = Some(format!("Note '{}' already exists", title));
+        return Ok(());
+    }
+    if let Err(e) = db::rename_note(&app.conn, id, title) {
+        app.status_message = Some(e.to_string());
+        return Ok(());
+    }
+    app.undo_stack.push(UndoAction::Rename {
+        id,
+        title: title.to_string(),
+        previous: previous.clone(),
+    });
+    app.refresh_notes()?;
+    app.select_note_id(id);
+    app.status_message = Some(format!("Renamed '{}' to '{}'", previous, title));
+    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+    Ok(())
+}
+
+/// Apply `+tag`/`tag` (add) and `-tag` (remove) arguments to the selected note.
+fn retag_selected_note(app: &mut App, args: &str) -> Result<()> {
+    let (id, title, previous) = match app.selected_note() {
+        Some(n) => (n.id, n.title.clone(), n.tags.clone()),
+        None => {
+            app.status_message = Some("No note selected".to_string());
+            return Ok(());
+        }
+    };
+    let mut tags = previous.clone();
+    for word in args.split_whitespace() {
+        if let Some(tag) = word.strip_prefix('-') {
+            tags.retain(|t| t != tag);
+        } else {
+            let tag = word.strip_prefix('+').unwrap_or(word).to_string();
+            if let Err(e) = utils::validate_tags(std::slice::from_ref(&tag)) {
+                app.status_message = Some(format!("Invalid tags: {}", e));
+                return Ok(());
+            }
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    if tags == previous {
+        app.status_message = Some("Tags unchanged".to_string());
+        return Ok(());
+    }
+    db::update_tags(&app.conn, id, &tags)?;
+    app.undo_stack.push(UndoAction::Tags {
+        id,
+        title: title.clone(),
+        previous,
+    });
+    app.refresh_notes()?;
+    app.select_note_id(id);
+    app.status_message = Some(format!("Tags on '{}': {}", title, tags.join(", ")));
+    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+    Ok(())
+}
+
+fn open_help(app: &mut App) {
+    app.help_context = KeyContext::current(app);
+    app.mode = Mode::Help;
+    app.help_scroll = 0;
+    app.status_message = None;
+}
+
+fn handle_help(app: &mut App, key: KeyEvent) -> Result<()> {
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('?') => {
+            app.mode = if app.inbox_previous.is_some() {
+                Mode::Inbox
+            } else if app.focus == Focus::TagPanel {
+                Mode::TagBrowse
+            } else {
+                Mode::Normal
+            };
+        }
+        KeyCode::Char('j') | KeyCode::Down => app.help_scroll = app.help_scroll.saturating_add(1),
+        KeyCode::Char('k') | KeyCode::Up => app.help_scroll = app.help_scroll.saturating_sub(1),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Open the selected note in the embedded editor, starting at the preview cursor line.
+fn start_inline_edit(app: &mut App) {
+    if app.preview_note().is_some_and(|n| n.is_locked()) {
+        start_unlock(app);
+        return;
+    }
+    let editor = match app.preview_note() {
+        Some(n) => EditorState::new(n.id, &n.title, &n.note, n.revision),
+        None => {
+            app.status_message = Some("No note selected".to_string());
+            return;
+        }
+    };
+    clear_summary(app);
+    app.preview_tab = PreviewTab::Note;
+    app.focus = Focus::Preview;
+    app.visual_anchor = None;
+    app.pending_g = false;
+    app.preview_cursor = app.preview_cursor.min(editor.lines.len() - 1);
+    app.editor = Some(editor);
+    app.mode = Mode::EditNormal;
+    app.status_message = None;
+    ensure_cursor_visible(app);
+}
+
+/// Close the embedded editor, writing the buffer if it changed.
+fn save_inline_edit(app: &mut App) -> Result<()> {
+    let Some(editor) = &app.editor else {
+        return Ok(());
+    };
+
+    if !editor.is_dirty() {
+        close_inline_edit(app);
+        app.status_message = Some("No changes made".to_string());
+        app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+        return Ok(());
+    }
+
+    let (id, title, contents) = (editor.note_id, editor.title.clone(), editor.contents());
+    let (original, revision) = (editor.original.clone(), editor.revision);
+    save_edit(app, id, &title, &contents, original, revision)
+}
+
+/// Close the editor, keeping the preview cursor within the edited text.
+fn close_inline_edit(app: &mut App) {
+    clamp_preview_cursor(app);
+    app.editor = None;
+    app.mode = Mode::Normal;
+    app.visual_anchor = None;
+}
+
+/// Keep the preview cursor within the previewed text, which is the editor's
+/// while one is open.
+fn clamp_preview_cursor(app: &mut App) {
+    let last = app.preview_raw_lines().len().saturating_sub(1);
+    app.preview_cursor = app.preview_cursor.min(last);
+    app.preview_scroll = app.preview_scroll.min(last as u16);
+    app.visual_anchor = app.visual_anchor.map(|a| a.min(last));
+}
+
+/// Save an edit that started from `base` at `revision`. If the note was changed
+/// outside the TUI in the meantime nothing is written: the edit is held in
+/// `pending_save` and the user picks how to resolve the conflict.
+fn save_edit(app: &mut App, id: i64, title: &str, contents: &str, base: String, revision: i64) -> Result<()> {
+    if crypto::is_encrypted(contents) {
+        let kept = if app.editor.is_none() {
+            keep_unsaved_edit(app, contents);
+            "; your edit was copied to the clipboard"
+        } else {
+            ""
+        };
+        app.status_message = Some(format!("Notes can't start with '{}'{}", crypto::PREFIX, kept));
+        app.status_expires = Some(Instant::now() + Duration::from_secs(5));
+        return Ok(());
+    }
+    if db::update_note_if_unchanged(&app.conn, id, contents, revision)? {
+        close_inline_edit(app);
+        return finish_write(app, id, title, base, revision + 1);
+    }
+    match db::get_note_by_id(&app.conn, id)? {
+        Some(theirs) if theirs.note == contents => {
+            close_inline_edit(app);
+            app.refresh_notes()?;
+            app.status_message = Some(format!("'{}' already had these changes", title));
+            app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+        }
+        Some(theirs) => {
+            app.pending_save = Some(PendingSave {
+                id,
+                title: title.to_string(),
+                contents: contents.to_string(),
+                base,
+                theirs: theirs.note,
+                revision: theirs.revision,
+                tags: theirs.tags,
+                session: None,
+            });
+            app.mode = Mode::ResolveConflict;
+            app.status_message = None;
+        }
+        None => {
+            close_inline_edit(app);
+            let copy = db::copy_title(&app.conn, title)?;
+            insert_like(app, id, &copy, contents, &[])?;
+            app.refresh_notes()?;
+            select_note_titled(app, &copy);
+            app.status_message = Some(format!("'{}' was deleted outside scrap; saved your version as '{}'", title, copy));
+            app.status_expires = Some(Instant::now() + Duration::from_secs(5));
+        }
+    }
+    Ok(())
+}
+
+/// Bookkeeping after a note's contents were written, remembering `previous` for undo.
+fn finish_write(app: &mut App, id: i64, title: &str, previous: String, revision: i64) -> Result<()> {
+    db::mark_summary_stale(&app.conn, id)?;
+    app.undo_stack.push(UndoAction::Edit {
+        id,
+        title: title.to_string(),
+        previous,
+        revision,
+    });
+    app.refresh_notes()?;
+    app.select_note_id(id);
+    app.status_message = Some(format!("Note '{}' updated", title));
+    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+    Ok(())
+}
+
+fn select_note_titled(app: &mut App, title: &str) {
+    if let Some(id) = app.notes.iter().find(|n| n.title == title).map(|n| n.id) {
+        app.select_note_id(id);
+    }
+}
+
+/// Put an edit that wasn't saved in the unnamed register and the clipboard so it isn't lost.
+fn keep_unsaved_edit(app: &mut App, contents: &str) {
+    let _ = app.registers.store(CLIPBOARD, contents);
+}
+
+fn handle_resolve_conflict(app: &mut App, key: KeyEvent) -> Result<()> {
+    let Some(mut save) = app.pending_save.take() else {
+        app.mode = Mode::Normal;
+        return Ok(());
+    };
+    let session = save.session.take();
+    match key.code {
+        KeyCode::Char('m') => {
+            let merged = merge::merge3(&save.base, &save.contents, &save.theirs);
+            if merged.conflicts == 0 {
+                save_edit(app, save.id, &save.title, &merged.text, save.theirs, save.revision)?;
+                if app.mode == Mode::Normal {
+                    app.status_message = Some(format!("Merged your edit into '{}'", save.title));
+                }
+                return hold_session(app, session);
+            }
+            // Resolve the rest by hand in the built-in editor, starting from their version
+            let mut editor = EditorState::new(save.id, &save.title, &merged.text, save.revision);
+            editor.original = save.theirs;
+            app.preview_cursor = merge::first_conflict(&merged.text).unwrap_or(0);
+            app.editor = Some(editor);
+            app.focus = Focus::Preview;
+            app.mode = Mode::EditNormal;
+            app.visual_anchor = None;
+            ensure_cursor_visible(app);
+            app.status_message = Some(format!(
+                "{} conflict(s) marked with <<<<<<< and >>>>>>>; resolve them and Ctrl+s",
+                merged.conflicts
+            ));
+            app.status_expires = Some(Instant::now() + Duration::from_secs(8));
+        }
+        KeyCode::Char('c') => {
+            close_inline_edit(app);
+            let copy = db::copy_title(&app.conn, &save.title)?;
+            insert_like(app, save.id, &copy, &save.contents, &save.tags)?;
+            app.refresh_notes()?;
+            select_note_titled(app, &copy);
+            app.status_message = Some(format!("Saved your version as '{}'", copy));
+            app.status_expires = Some(Instant::now() + Duration::from_secs(5));
+        }
+        KeyCode::Char('o') | KeyCode::Char('y') => {
+            close_inline_edit(app);
+            match db::update_note(&app.conn, save.id, &save.contents)? {
+                Some(revision) => finish_write(app, save.id, &save.title, save.theirs, revision)?,
+                None => {
+                    keep_unsaved_edit(app, &save.contents);
+                    app.status_message =
+                        Some(format!("'{}' was deleted; your edit was copied to the clipboard", save.title));
+                    app.status_expires = Some(Instant::now() + Duration::from_secs(5));
+                }
+            }
+        }
+        KeyCode::Char('n') | KeyCode::Esc => {
+            if app.editor.is_some() {
+                // Back to the buffer to copy what's needed; Esc twice discards it
+                app.mode = Mode::EditNormal;
+                app.status_message = Some("Not saved. Ctrl+s to decide again, Esc twice to discard".to_string());
+            } else {
+                app.mode = Mode::Normal;
+                keep_unsaved_edit(app, &save.contents);
+                app.status_message = Some(format!(
+                    "Kept the other version of '{}'; your edit was copied to the clipboard",
+                    save.title
+                ));
+            }
+            app.status_expires = Some(Instant::now() + Duration::from_secs(5));
+        }
+        _ => app.pending_save = Some(save),
+    }
+    hold_session(app, session)
+}
+
+/// Keep an $EDITOR session's temp file while its edit still waits in
+/// `pending_save`; once the edit is resolved it's no longer needed.
+fn hold_session(app: &mut App, session: Option<Session>) -> Result<()> {
+    match (&mut app.pending_save, session) {
+        (Some(save), Some(session)) => save.session = Some(session),
+        (None, Some(session)) => session.finish(),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_recover(app: &mut App, key: KeyEvent) -> Result<()> {
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down => {
+            app.recovery_selected += 1;
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.recovery_selected = app.recovery_selected.saturating_sub(1);
+        }
+        KeyCode::Char('r') | KeyCode::Enter if app.recovery_selected < app.recovery.len() => {
+            let orphan = app.recovery.remove(app.recovery_selected);
+            let message = session::restore(&app.conn, orphan).unwrap_or_else(|e| format!("Error: {}", e));
+            app.refresh_notes()?;
+            app.status_message = Some(message);
+            app.status_expires = Some(Instant::now() + Duration::from_secs(5));
+        }
+        KeyCode::Char('d') if app.recovery_selected < app.recovery.len() => {
+            let orphan = app.recovery.remove(app.recovery_selected);
+            app.status_message = Some(format!("Discarded unsaved edit of '{}'", orphan.title));
+            app.status_expires = Some(Instant::now() + Duration::from_secs(5));
+            session::discard(orphan);
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            // Left on disk for `scrap recover` or the next start
+            app.recovery.clear();
+        }
+        _ => {}
+    }
+    if app.recovery.is_empty() {
+        app.mode = Mode::Normal;
+    }
+    app.recovery_selected = app.recovery_selected.min(app.recovery.len().saturating_sub(1));
+    Ok(())
+}
+
+/// Show only captured notes, so they can be triaged one after another.
+fn open_inbox(app: &mut App) {
+    let filters = std::mem::replace(&mut app.active_tag_filters, vec![INBOX_TAG.to_string()]);
+    let query = std::mem::take(&mut app.search_query);
+    app.inbox_previous = Some((filters, query));
+    app.apply_filter();
+    if app.filtered_notes.is_empty() {
+        close_inbox(app);
+        app.status_message = Some("Inbox is empty".to_string());
+        app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+        return;
+    }
+    app.selected = 0;
+    app.focus = Focus::NoteList;
+    app.active_tab = None;
+    app.visual_anchor = None;
+    clear_summary(app);
+    app.preview_scroll = 0;
+    app.preview_cursor = 0;
+    app.mode = Mode::Inbox;
+    app.status_message = None;
+}
+
+/// Leave the inbox, bringing back the filters that were active before it.
+fn close_inbox(app: &mut App) {
+    let selected_id = app.selected_note().map(|n| n.id);
+    if let Some((filters, query)) = app.inbox_previous.take() {
+        app.active_tag_filters = filters;
+        app.search_query = query;
+    }
+    app.apply_filter();
+    if let Some(id) = selected_id {
+        app.select_note_id(id);
+    }
+    app.mode = Mode::Normal;
+}
+
+/// After the capture at `index` left the inbox, select the one that took its
+/// place, or close the inbox if that was the last.
+fn next_capture(app: &mut App, index: usize) {
+    if app.filtered_notes.is_empty() {
+        close_inbox(app);
+        if let Some(message) = &mut app.status_message {
+            message.push_str(". Inbox is empty");
+        }
+    } else {
+        app.selected = index.min(app.filtered_notes.len() - 1);
+    }
+    app.preview_scroll = 0;
+    app.preview_cursor = 0;
+}
+
+fn handle_inbox(app: &mut App, key: KeyEvent) -> Result<()> {
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down => {
+            app.move_selection(1);
+            app.preview_scroll = 0;
+            app.preview_cursor = 0;
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.move_selection(-1);
+            app.preview_scroll = 0;
+            app.preview_cursor = 0;
+        }
+        KeyCode::Enter | KeyCode::Char('f') => file_capture(app, None)?,
+        KeyCode::Char('r') => {
+            if let Some(note) = app.selected_note() {
+                app.input_buffer = note.title.clone();
+                app.mode = Mode::InboxTitle;
+            }
+        }
+        KeyCode::Char('t') => {
+            if let Some(note) = app.selected_note() {
+                let tags: Vec<&str> = note.tags.iter().map(|t| t.as_str()).filter(|t| *t != INBOX_TAG).collect();
+                app.input_buffer = tags.iter().map(|t| format!("{} ", t)).collect();
+                app.tag_suggestions.clear();
+                app.selected_suggestion = 0;
+                app.mode = Mode::InboxTags;
+            }
+        }
+        KeyCode::Char('m') if app.selected_note().is_some() => {
+            app.input_buffer.clear();
+            app.update_merge_targets();
+            app.mode = Mode::InboxMerge;
+        }
+        KeyCode::Char('d') => delete_capture(app)?,
+        KeyCode::Char('u') => undo_last_action(app)?,
+        KeyCode::Char('?') => open_help(app),
+        KeyCode::Char('q') => app.should_quit = true,
+        KeyCode::Esc => close_inbox(app),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The retitle, tag and merge prompts of the inbox. Esc goes back to the inbox.
+fn handle_inbox_prompt(app: &mut App, key: KeyEvent) -> Result<()> {
+    let merging = app.mode == Mode::InboxMerge;
+    match key.code {
+        KeyCode::Esc => {
+            app.mode = Mode::Inbox;
+            app.tag_suggestions.clear();
+            app.merge_targets.clear();
+        }
+        KeyCode::Up if merging => {
+            app.merge_selected = app.merge_selected.saturating_sub(1);
+        }
+        KeyCode::Down if merging => {
+            app.merge_selected = (app.merge_selected + 1).min(app.merge_targets.len().saturating_sub(1));
+        }
+        KeyCode::Up => app.move_suggestion_selection(-1),
+        KeyCode::Down => app.move_suggestion_selection(1),
+        KeyCode::Tab if merging => {
+            if let Some((_, title)) = app.merge_targets.get(app.merge_selected) {
+                app.input_buffer = title.clone();
+                app.update_merge_targets();
+            }
+        }
+        KeyCode::Tab if app.mode == Mode::InboxTags => {
+            let mut buf = app.input_buffer.clone();
+            app.accept_tag_suggestion(&mut buf);
+            app.input_buffer = buf;
+        }
+        KeyCode::Enter => {
+            let prompt = std::mem::replace(&mut app.mode, Mode::Inbox);
+            let input = std::mem::take(&mut app.input_buffer);
+            app.tag_suggestions.clear();
+            match prompt {
+                Mode::InboxTitle => {
+                    let title = input.trim();
+                    if app.selected_note().is_some_and(|n| n.title != title) {
+                        rename_selected_note(app, title)?;
+                    }
+                }
+                Mode::InboxTags => {
+                    let tags: Vec<String> = input.split_whitespace().map(|s| s.to_string()).collect();
+                    if let Err(e) = utils::validate_tags(&tags) {
+                        app.status_message = Some(format!("Invalid tags: {}", e));
+                        app.input_buffer = input;
+                        app.mode = Mode::InboxTags;
+                        return Ok(());
+                    }
+                    file_capture(app, Some(tags))?;
+                }
+                _ => {
+                    let target = app.merge_targets.get(app.merge_selected).map(|(id, _)| *id);
+                    app.merge_targets.clear();
+                    match target {
+                        Some(target) => merge_capture(app, target)?,
+                        None => app.status_message = Some(format!("No note matches '{}'", input.trim())),
+                    }
+                }
+            }
+        }
+        KeyCode::Backspace => {
+            app.input_buffer.pop();
+            update_inbox_prompt(app);
+        }
+        KeyCode::Char(c) => {
+            app.input_buffer.push(c);
+            update_inbox_prompt(app);
         }
         _ => {}
     }
     Ok(())
 }
 
-/// Scroll the viewport so the cursor line is visible.
-fn ensure_cursor_visible(app: &mut App) {
-    let scroll = app.preview_scroll as usize;
-    let viewport_height = app.preview_content_height as usize;
-    let scrolloff: usize = 2;
+fn update_inbox_prompt(app: &mut App) {
+    match app.mode {
+        Mode::InboxTags => app.update_tag_suggestions(&app.input_buffer.clone()),
+        Mode::InboxMerge => app.update_merge_targets(),
+        _ => {}
+    }
+}
 
-    if viewport_height == 0 {
-        return;
+/// File the selected capture by dropping its inbox tag. With `tags`, they
+/// replace the capture's other tags.
+fn file_capture(app: &mut App, tags: Option<Vec<String>>) -> Result<()> {
+    let Some(note) = app.selected_note() else {
+        return Ok(());
+    };
+    let (id, title, previous) = (note.id, note.title.clone(), note.tags.clone());
+    let mut tags = tags.unwrap_or_else(|| previous.clone());
+    tags.retain(|t| t != INBOX_TAG);
+    tags.dedup();
+
+    db::update_tags(&app.conn, id, &tags)?;
+    app.undo_stack.push(UndoAction::Tags {
+        id,
+        title: title.clone(),
+        previous,
+    });
+    let index = app.selected;
+    app.refresh_notes()?;
+    app.status_message = Some(if tags.is_empty() {
+        format!("Filed '{}'", title)
+    } else {
+        format!("Filed '{}' under {}", title, tags.join(", "))
+    });
+    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+    next_capture(app, index);
+    Ok(())
+}
+
+/// Append the selected capture to note `target` and move the capture to the trash.
+fn merge_capture(app: &mut App, target: i64) -> Result<()> {
+    let Some(note) = app.selected_note() else {
+        return Ok(());
+    };
+    let (id, title, contents) = (note.id, note.title.clone(), note.note.clone());
+    let Some(into) = db::get_note_by_id(&app.conn, target)? else {
+        app.status_message = Some("Note not found".to_string());
+        return Ok(());
+    };
+    if into.encrypted || app.notes.iter().any(|n| n.id == id && n.encrypted) {
+        app.status_message = Some("Encrypted notes can't be merged; decrypt them first".to_string());
+        return Ok(());
     }
 
-    if app.preview_cursor < scroll + scrolloff {
-        app.preview_scroll = app.preview_cursor.saturating_sub(scrolloff) as u16;
-    } else if app.preview_cursor >= scroll + viewport_height.saturating_sub(scrolloff) {
-        app.preview_scroll = (app.preview_cursor + scrolloff + 1).saturating_sub(viewport_height) as u16;
+    // Appended as `db::append_note` would, but only to the text undo restores
+    if !db::update_note_if_unchanged(&app.conn, target, &format!("{}\n{}", into.note, contents), into.revision)? {
+        app.status_message = Some(format!("'{}' changed outside scrap; try again", into.title));
+        app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+        return Ok(());
     }
+    db::mark_summary_stale(&app.conn, target)?;
+    db::delete_note_by_id(&app.conn, id)?;
+    app.undo_stack.push(UndoAction::Merge {
+        id,
+        title: title.clone(),
+        target,
+        target_title: into.title.clone(),
+        previous: into.note,
+        revision: into.revision + 1,
+    });
+    let index = app.selected;
+    clear_summary(app);
+    app.refresh_notes()?;
+    app.status_message = Some(format!("Merged '{}' into '{}' (u to undo)", title, into.title));
+    app.status_expires = Some(Instant::now() + Duration::from_secs(5));
+    next_capture(app, index);
+    Ok(())
 }
 
-/// Yank the visually selected lines to clipboard and internal register.
-fn yank_selection(app: &mut App) {
-    let anchor = match app.visual_anchor {
-        Some(a) => a,
-        None => return,
+/// Move the selected capture to the trash without asking; `u` brings it back.
+fn delete_capture(app: &mut App) -> Result<()> {
+    let Some(note) = app.selected_note() else {
+        return Ok(());
     };
+    let (id, title) = (note.id, note.title.clone());
+    db::delete_note_by_id(&app.conn, id)?;
+    app.undo_stack.push(UndoAction::Delete { id, title: title.clone() });
+    let index = app.selected;
+    clear_summary(app);
+    app.refresh_notes()?;
+    app.status_message = Some(format!("Note '{}' moved to trash (u to undo)", title));
+    app.status_expires = Some(Instant::now() + Duration::from_secs(5));
+    next_capture(app, index);
+    Ok(())
+}
 
-    let lines = app.preview_raw_lines();
-    if lines.is_empty() {
-        return;
-    }
+fn discard_inline_edit(app: &mut App) {
+    app.editor = None;
+    app.mode = Mode::Normal;
+    app.visual_anchor = None;
+    // The note may be shorter than the discarded text
+    clamp_preview_cursor(app);
+    app.status_message = Some("Changes discarded".to_string());
+    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+}
 
-    let start = anchor.min(app.preview_cursor);
-    let end = anchor.max(app.preview_cursor);
-    let end = end.min(lines.len().saturating_sub(1));
+fn handle_edit_normal(app: &mut App, key: KeyEvent) -> Result<()> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    if ctrl && key.code == KeyCode::Char('s') {
+        return save_inline_edit(app);
+    }
 
-    let selected_text: String = lines[start..=end].join("\n");
-    let line_count = end - start + 1;
+    let Some(editor) = app.editor.as_mut() else {
+        app.mode = Mode::Normal;
+        return Ok(());
+    };
+    let mut row = app.preview_cursor;
+    let last_row = editor.lines.len() - 1;
 
-    // Store in internal register
-    app.yank_register = Some(selected_text.clone());
+    if key.code != KeyCode::Esc {
+        editor.pending_discard = false;
+    }
 
-    // Copy to system clipboard
-    match arboard::Clipboard::new() {
-        Ok(mut clipboard) => {
-            if clipboard.set_text(&selected_text).is_err() {
-                app.status_message = Some(format!("{} lines yanked (clipboard unavailable)", line_count));
-                app.status_expires = Some(Instant::now() + Duration::from_secs(3));
-            } else {
-                app.status_message = Some(format!("{} lines yanked", line_count));
-                app.status_expires = Some(Instant::now() + Duration::from_secs(3));
-            }
+    if editor.pending_d {
+        editor.pending_d = false;
+        if key.code == KeyCode::Char('d') {
+            editor.snapshot(row);
+            let deleted = editor.lines[row].clone();
+            let _ = app.registers.store(app.pending_register.take().unwrap_or(UNNAMED), &deleted);
+            app.preview_cursor = editor.delete_lines(row, row);
+            ensure_cursor_visible(app);
         }
-        Err(_) => {
-            app.status_message = Some(format!("{} lines yanked (clipboard unavailable)", line_count));
-            app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+        return Ok(());
+    }
+
+    if app.pending_prefix.take() == Some('"') {
+        if let KeyCode::Char(c) = key.code
+            && Registers::is_valid(c)
+        {
+            app.pending_register = Some(c);
         }
+        return Ok(());
     }
 
-    // Exit visual mode
-    app.visual_anchor = None;
-    app.mode = Mode::Normal;
-}
+    if app.pending_g {
+        app.pending_g = false;
+        if key.code == KeyCode::Char('g') {
+            app.preview_cursor = 0;
+            editor.clamp_col(0, false);
+            ensure_cursor_visible(app);
+            return Ok(());
+        }
+    }
 
-fn handle_tag_browse(app: &mut App, key: KeyEvent) -> Result<()> {
     match key.code {
-        KeyCode::Char('q') => app.should_quit = true,
-        KeyCode::Char('j') | KeyCode::Down => app.move_tag_selection(1),
-        KeyCode::Char('k') | KeyCode::Up => app.move_tag_selection(-1),
-        KeyCode::Enter => {
-            if let Some(tag_name) = app.visible_tags.get(app.selected_tag).map(|t| t.name.clone()) {
-                if let Some(pos) = app.active_tag_filters.iter().position(|t| t == &tag_name) {
-                    app.active_tag_filters.remove(pos);
-                } else {
-                    app.active_tag_filters.push(tag_name);
+        KeyCode::Esc => {
+            if app.visual_anchor.is_some() {
+                app.visual_anchor = None;
+            } else if !editor.is_dirty() || editor.pending_discard {
+                discard_inline_edit(app);
+            } else {
+                editor.pending_discard = true;
+                app.status_message =
+                    Some("Unsaved changes: Esc again to discard, Ctrl+s to save".to_string());
+            }
+            return Ok(());
+        }
+        KeyCode::Char('h') | KeyCode::Left => {
+            editor.col = editor.col.saturating_sub(1);
+        }
+        KeyCode::Char('l') | KeyCode::Right => {
+            editor.col += 1;
+            editor.clamp_col(row, false);
+        }
+        KeyCode::Char('j') | KeyCode::Down => {
+            row = (row + 1).min(last_row);
+            editor.clamp_col(row, false);
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            row = row.saturating_sub(1);
+            editor.clamp_col(row, false);
+        }
+        KeyCode::Char('0') | KeyCode::Home => editor.col = 0,
+        KeyCode::Char('$') | KeyCode::End => {
+            editor.col = editor.line_len(row).saturating_sub(1);
+        }
+        KeyCode::Char('g') => app.pending_g = true,
+        KeyCode::Char('G') => {
+            row = last_row;
+            editor.clamp_col(row, false);
+        }
+        KeyCode::Char('i') => {
+            editor.snapshot(row);
+            app.mode = Mode::EditInsert;
+        }
+        KeyCode::Char('a') => {
+            editor.snapshot(row);
+            editor.col = (editor.col + 1).min(editor.line_len(row));
+            app.mode = Mode::EditInsert;
+        }
+        KeyCode::Char('I') => {
+            editor.snapshot(row);
+            editor.col = 0;
+            app.mode = Mode::EditInsert;
+        }
+        KeyCode::Char('A') => {
+            editor.snapshot(row);
+            editor.col = editor.line_len(row);
+            app.mode = Mode::EditInsert;
+        }
+        KeyCode::Char('o') | KeyCode::Char('O') => {
+            editor.snapshot(row);
+            row = editor.open_line(row, key.code == KeyCode::Char('O'));
+            app.mode = Mode::EditInsert;
+        }
+        KeyCode::Char('x') if editor.line_len(row) > 0 => {
+            editor.snapshot(row);
+            editor.delete_char(row);
+            editor.clamp_col(row, false);
+        }
+        KeyCode::Char('d') => match app.visual_anchor.take() {
+            Some(anchor) => {
+                let (start, end) = (anchor.min(row), anchor.max(row).min(last_row));
+                editor.snapshot(row);
+                let deleted = editor.lines[start..=end].join("\n");
+                let _ = app.registers.store(app.pending_register.take().unwrap_or(UNNAMED), &deleted);
+                row = editor.delete_lines(start, end);
+            }
+            None => editor.pending_d = true,
+        },
+        KeyCode::Char('"') => app.pending_prefix = Some('"'),
+        KeyCode::Char('p') | KeyCode::Char('P') => {
+            let name = app.pending_register.take().unwrap_or(UNNAMED);
+            match app.registers.get(name) {
+                Ok(Some(text)) => {
+                    editor.snapshot(row);
+                    row = editor.put_lines(row, &text, key.code == KeyCode::Char('P'));
                 }
-                app.apply_filter();
-                app.selected = 0;
-                if app.active_tag_filters.is_empty() {
-                    app.status_message = None;
-                } else {
-                    app.status_message = Some(format!("Filtered by: {}", app.active_tag_filters.join(", ")));
+                Ok(None) => {
+                    app.status_message = Some(format!("Register \"{} is empty", name));
+                    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+                }
+                Err(e) => {
+                    app.status_message = Some(e.to_string());
+                    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
                 }
             }
         }
-        KeyCode::Esc => {
-            app.active_tag_filters.clear();
-            app.apply_filter();
-            app.selected = 0;
-            app.focus = Focus::NoteList;
-            app.mode = Mode::Normal;
-            app.status_message = None;
-        }
-        KeyCode::Tab => {
-            app.focus = Focus::Preview;
-            app.mode = Mode::Normal;
-            app.preview_scroll = 0;
-            app.preview_cursor = 0;
+        KeyCode::Char('u') => match editor.undo() {
+            Some(r) => {
+                row = r.min(editor.lines.len() - 1);
+                editor.clamp_col(row, false);
+            }
+            None => {
+                app.status_message = Some("Already at oldest change".to_string());
+                app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+            }
+        },
+        KeyCode::Char('V') => {
+            app.visual_anchor = match app.visual_anchor {
+                Some(_) => None,
+                None => Some(row),
+            };
         }
-        KeyCode::Char(':') => {
-            app.focus = Focus::NoteList;
-            app.mode = Mode::Command;
-            app.status_message = None;
+        KeyCode::Char('y') if app.visual_anchor.is_some() => {
+            yank_selection(app);
+            app.mode = Mode::EditNormal;
+            return Ok(());
         }
         _ => {}
     }
+
+    app.preview_cursor = row;
+    ensure_cursor_visible(app);
     Ok(())
 }
 
-fn handle_search(app: &mut App, key: KeyEvent) -> Result<()> {
+fn handle_edit_insert(app: &mut App, key: KeyEvent) -> Result<()> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    if ctrl && key.code == KeyCode::Char('s') {
+        return save_inline_edit(app);
+    }
+
+    let Some(editor) = app.editor.as_mut() else {
+        app.mode = Mode::Normal;
+        return Ok(());
+    };
+    let mut row = app.preview_cursor;
+    let last_row = editor.lines.len() - 1;
+
     match key.code {
         KeyCode::Esc => {
-            app.search_query.clear();
-            app.apply_filter();
-            app.selected = 0;
-            app.mode = Mode::Normal;
+            editor.col = editor.col.saturating_sub(1);
+            editor.clamp_col(row, false);
+            app.mode = Mode::EditNormal;
         }
-        KeyCode::Enter => {
-            app.mode = Mode::Normal;
+        KeyCode::Enter => row = editor.insert_newline(row),
+        KeyCode::Backspace => row = editor.backspace(row),
+        KeyCode::Delete => editor.delete_char(row),
+        KeyCode::Tab => {
+            for _ in 0..4 {
+                editor.insert_char(row, ' ');
+            }
         }
-        KeyCode::Backspace => {
-            app.search_query.pop();
-            app.apply_filter();
-            app.selected = 0;
+        KeyCode::Left => editor.col = editor.col.saturating_sub(1),
+        KeyCode::Right => {
+            editor.col += 1;
+            editor.clamp_col(row, true);
         }
-        KeyCode::Char(c) => {
-            app.search_query.push(c);
-            app.apply_filter();
-            app.selected = 0;
+        KeyCode::Up => {
+            row = row.saturating_sub(1);
+            editor.clamp_col(row, true);
+        }
+        KeyCode::Down => {
+            row = (row + 1).min(last_row);
+            editor.clamp_col(row, true);
         }
+        KeyCode::Home => editor.col = 0,
+        KeyCode::End => editor.col = editor.line_len(row),
+        KeyCode::Char(c) if !ctrl => editor.insert_char(row, c),
         _ => {}
     }
+
+    app.preview_cursor = row;
+    ensure_cursor_visible(app);
     Ok(())
 }
 
-fn handle_command(
-    app: &mut App,
-    key: KeyEvent,
-    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
-) -> Result<()> {
+fn handle_confirm_delete(app: &mut App, key: KeyEvent) -> Result<()> {
     match key.code {
-        KeyCode::Esc => {
+        KeyCode::Char('y') | KeyCode::Char('Y') => {
             app.mode = Mode::Normal;
+            let (id, title) = match app.selected_note() {
+                Some(n) => (n.id, n.title.clone()),
+                None => return Ok(()),
+            };
+            db::delete_note_by_id(&app.conn, id)?;
+            app.undo_stack.push(UndoAction::Delete { id, title: title.clone() });
+            clear_summary(app);
+            app.refresh_notes()?;
+            app.status_message = Some(format!("Note '{}' moved to trash (u to undo)", title));
+            app.status_expires = Some(Instant::now() + Duration::from_secs(5));
         }
-        KeyCode::Char('o') => {
+        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
             app.mode = Mode::Normal;
-            open_selected_note(app, terminal)?;
         }
-        KeyCode::Char('a') => {
-            app.input_buffer.clear();
-            app.tags_buffer.clear();
-            app.mode = Mode::AddNoteName;
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Revert the most recent destructive action taken in this session.
+fn undo_last_action(app: &mut App) -> Result<()> {
+    let action = match app.undo_stack.pop() {
+        Some(a) => a,
+        None => {
+            app.status_message = Some("Nothing to undo".to_string());
+            app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+            return Ok(());
         }
-        KeyCode::Char('t') => {
-            app.input_buffer.clear();
-            app.mode = Mode::EditTagsAdd;
+    };
+
+    let (id, message) = match action {
+        UndoAction::Delete { id, title } => {
+            if db::get_note(&app.conn, &title)?.is_some() {
+                app.status_message = Some(format!("Cannot restore '{}': name already in use", title));
+                app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+                return Ok(());
+            }
+            db::restore_note_by_id(&app.conn, id)?;
+            (id, format!("Restored '{}'", title))
         }
-        KeyCode::Char('s') => {
-            app.mode = Mode::Normal;
-            summarize_selected_note(app)?;
+        UndoAction::Edit { id, title, previous, revision } => {
+            if !db::update_note_if_unchanged(&app.conn, id, &previous, revision)? {
+                return changed_since_undo(app, &title);
+            }
+            db::mark_summary_stale(&app.conn, id)?;
+            (id, format!("Reverted edit to '{}'", title))
         }
-        _ => {}
-    }
+        UndoAction::Tags { id, title, previous } => {
+            db::update_tags(&app.conn, id, &previous)?;
+            (id, format!("Reverted tags on '{}'", title))
+        }
+        UndoAction::Rename { id, title, previous } => {
+            if db::get_note(&app.conn, &previous)?.is_some() {
+                app.status_message = Some(format!("Cannot rename back to '{}': name already in use", previous));
+                app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+                return Ok(());
+            }
+            if let Err(e) = db::rename_note(&app.conn, id, &previous) {
+                app.status_message = Some(e.to_string());
+                app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+                return Ok(());
+            }
+            (id, format!("Renamed '{}' back to '{}'", title, previous))
+        }
+        UndoAction::Extract { id, title, source, source_title, previous, revision } => {
+            if !db::update_note_if_unchanged(&app.conn, source, &previous, revision)? {
+                return changed_since_undo(app, &source_title);
+            }
+            db::delete_note_by_id(&app.conn, id)?;
+            db::mark_summary_stale(&app.conn, source)?;
+            (source, format!("Put '{}' back into '{}'", title, source_title))
+        }
+        UndoAction::Merge { id, title, target, target_title, previous, revision } => {
+            if db::get_note(&app.conn, &title)?.is_some() {
+                app.status_message = Some(format!("Cannot restore '{}': name already in use", title));
+                app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+                return Ok(());
+            }
+            if !db::update_note_if_unchanged(&app.conn, target, &previous, revision)? {
+                return changed_since_undo(app, &target_title);
+            }
+            db::restore_note_by_id(&app.conn, id)?;
+            db::mark_summary_stale(&app.conn, target)?;
+            (id, format!("Took '{}' back out of '{}'", title, target_title))
+        }
+    };
+
+    clear_summary(app);
+    app.refresh_notes()?;
+    app.select_note_id(id);
+    app.status_message = Some(message);
+    app.status_expires = Some(Instant::now() + Duration::from_secs(3));
+    Ok(())
+}
+
+/// Undo was refused because the note changed after the change being undone.
+fn changed_since_undo(app: &mut App, title: &str) -> Result<()> {
+    app.refresh_notes()?;
+    app.status_message = Some(format!("Can't undo: '{}' changed since, and undoing would lose that", title));
+    app.status_expires = Some(Instant::now() + Duration::from_secs(5));
     Ok(())
 }
 
@@ -412,6 +2637,49 @@ fn handle_add_note_name(app: &mut App, key: KeyEvent) -> Result<()> {
     Ok(())
 }
 
+/// Write a new note's contents in $EDITOR and save it.
+fn create_note(
+    app: &mut App,
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    name: &str,
+    tags: &[String],
+) -> Result<()> {
+    disable_raw_mode()?;
+    if app.mouse {
+        execute!(terminal.backend_mut(), DisableMouseCapture)?;
+    }
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    let contents = session::edit(&Target::new_note(name, tags), "");
+
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+    if app.mouse {
+        execute!(terminal.backend_mut(), EnableMouseCapture)?;
+    }
+    terminal.hide_cursor()?;
+    terminal.clear()?;
+
+    match contents {
+        Ok((contents, edit)) => match db::insert_note(&app.conn, name, &contents, tags) {
+            Ok(_) => {
+                edit.finish();
+                app.refresh_notes()?;
+                app.status_message = Some(format!("Note '{}' created", name));
+            }
+            Err(e) => {
+                keep_unsaved_edit(app, &contents);
+                app.status_message = Some(format!("Error: {}; your note was copied to the clipboard", e));
+            }
+        },
+        Err(e) => {
+            app.status_message = Some(format!("Error: {}", e));
+        }
+    }
+    Ok(())
+}
+
 fn handle_add_note_tags(
     app: &mut App,
     key: KeyEvent,
@@ -450,28 +2718,7 @@ fn handle_add_note_tags(
                 }
             }
             let name = app.input_buffer.clone();
-
-            disable_raw_mode()?;
-            execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-            terminal.show_cursor()?;
-
-            let contents = utils::get_user_input(&name);
-
-            enable_raw_mode()?;
-            execute!(terminal.backend_mut(), EnterAlternateScreen)?;
-            terminal.hide_cursor()?;
-            terminal.clear()?;
-
-            match contents {
-                Ok(contents) => {
-                    db::insert_note(&app.conn, &name, &contents, &tags)?;
-                    app.refresh_notes()?;
-                    app.status_message = Some(format!("Note '{}' created", name));
-                }
-                Err(e) => {
-                    app.status_message = Some(format!("Error: {}", e));
-                }
-            }
+            create_note(app, terminal, &name, &tags)?;
             app.mode = Mode::Normal;
             app.tag_suggestions.clear();
             app.selected_suggestion = 0;
@@ -548,6 +2795,12 @@ fn handle_edit_tags(app: &mut App, key: KeyEvent) -> Result<()> {
                 }
             };
 
+            app.undo_stack.push(UndoAction::Tags {
+                id,
+                title: selected_note.clone(),
+                previous: existing_tags.clone(),
+            });
+
             let is_add = app.mode == Mode::EditTagsAdd;
             if is_add {
                 for tag in &new_tags {
@@ -584,8 +2837,17 @@ fn open_selected_note(
     app: &mut App,
     terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
 ) -> Result<()> {
-    let (title, id, old_contents) = match app.selected_note() {
-        Some(n) => (n.title.clone(), n.id, n.note.clone()),
+    let (id, target) = match app.selected_note() {
+        // Decrypted text never goes to a temp file, so these use the built-in editor
+        Some(n) if n.is_locked() => {
+            start_unlock(app);
+            return Ok(());
+        }
+        Some(n) if n.encrypted => {
+            start_inline_edit(app);
+            return Ok(());
+        }
+        Some(n) => (n.id, Target::existing(n)),
         None => {
             app.status_message = Some("No note selected".to_string());
             return Ok(());
@@ -593,23 +2855,27 @@ fn open_selected_note(
     };
 
     disable_raw_mode()?;
+    if app.mouse {
+        execute!(terminal.backend_mut(), DisableMouseCapture)?;
+    }
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     terminal.show_cursor()?;
 
-    let result = utils::get_user_input_with_contents(&title, &old_contents);
+    let result = session::edit(&target, &target.base);
 
     enable_raw_mode()?;
     execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+    if app.mouse {
+        execute!(terminal.backend_mut(), EnableMouseCapture)?;
+    }
     terminal.hide_cursor()?;
     terminal.clear()?;
 
     match result {
-        Ok(new_contents) => {
-            if new_contents != old_contents {
-                db::update_note(&app.conn, id, &new_contents)?;
-                db::mark_summary_stale(&app.conn, id)?;
-                app.refresh_notes()?;
-                app.status_message = Some(format!("Note '{}' updated", title));
+        Ok((new_contents, edit)) => {
+            let Target { title, revision, base, .. } = target;
+            if new_contents != base {
+                save_edit(app, id, &title, &new_contents, base, revision)?;
                 // Clear displayed summary since content changed
                 if app.showing_summary {
                     app.showing_summary = false;
@@ -618,6 +2884,7 @@ fn open_selected_note(
                     app.summary_force_regen = false;
                 }
             }
+            hold_session(app, Some(edit))?;
         }
         Err(e) => {
             app.status_message = Some(format!("Error: {}", e));
@@ -634,6 +2901,10 @@ fn summarize_selected_note(app: &mut App) -> Result<()> {
             return Ok(());
         }
     };
+    if note.encrypted {
+        app.status_message = Some("Encrypted notes are never sent for summarizing".to_string());
+        return Ok(());
+    }
 
     // If already showing a stale summary, second :s forces regen
     if app.showing_summary && app.summary_stale && !app.summary_force_regen {
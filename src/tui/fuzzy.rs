@@ -0,0 +1,202 @@
+//! fzf-style fuzzy matching for the note list search.
+
+use crate::db::NoteEntry;
+
+use super::motions;
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_CONSECUTIVE: i64 = 6;
+const BONUS_FIRST_CHAR: i64 = 4;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP_EXTENSION: i64 = 1;
+
+/// Why a note matched the search query.
+#[derive(Debug, Clone, Default)]
+pub struct NoteMatch {
+    /// Title char positions matched by the query
+    pub title_positions: Vec<usize>,
+    /// A body line containing the query, with the char range of the match
+    pub snippet: Option<(String, (usize, usize))>,
+    tier: u8,
+    score: i64,
+}
+
+impl NoteMatch {
+    /// Title matches rank above tag matches, which rank above body matches.
+    pub fn rank(&self) -> (u8, i64) {
+        (self.tier, self.score)
+    }
+}
+
+/// Match a note against a lowercased query. An empty query matches everything.
+pub fn match_note(note: &NoteEntry, query: &str) -> Option<NoteMatch> {
+    if query.is_empty() {
+        return Some(NoteMatch::default());
+    }
+    // The contents of encrypted notes are never searched
+    let snippet = if note.encrypted { None } else { body_snippet(&note.note, query) };
+    if let Some((score, positions)) = fuzzy_match(&note.title, query) {
+        return Some(NoteMatch {
+            title_positions: positions,
+            snippet,
+            tier: 3,
+            score,
+        });
+    }
+    if note.tags.iter().any(|t| t.to_lowercase().contains(query)) {
+        return Some(NoteMatch {
+            snippet,
+            tier: 2,
+            ..Default::default()
+        });
+    }
+    snippet.map(|snippet| NoteMatch {
+        snippet: Some(snippet),
+        tier: 1,
+        ..Default::default()
+    })
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn is_boundary(prev: Option<char>, c: char) -> bool {
+    match prev {
+        None => true,
+        Some(p) => !p.is_alphanumeric() || (p.is_lowercase() && c.is_uppercase()),
+    }
+}
+
+/// Score `query` (lowercased) as a subsequence of `text`. Returns the score and
+/// the matched char positions, or None if not every query char is present in order.
+pub fn fuzzy_match(text: &str, query: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<char> = text.chars().collect();
+    let needle: Vec<char> = query.chars().collect();
+    if needle.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    // Forward pass finds where the first complete match ends
+    let mut qi = 0;
+    let mut end = None;
+    for (i, &c) in chars.iter().enumerate() {
+        if fold(c) == needle[qi] {
+            qi += 1;
+            if qi == needle.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    // Backward pass from there finds the tightest window ending at `end`
+    let mut positions = Vec::with_capacity(needle.len());
+    let mut qi = needle.len();
+    for i in (0..=end).rev() {
+        if fold(chars[i]) == needle[qi - 1] {
+            positions.push(i);
+            qi -= 1;
+            if qi == 0 {
+                break;
+            }
+        }
+    }
+    positions.reverse();
+
+    let mut score = 0;
+    let mut prev_pos: Option<usize> = None;
+    for (n, &pos) in positions.iter().enumerate() {
+        score += SCORE_MATCH;
+        let boundary = is_boundary(pos.checked_sub(1).map(|p| chars[p]), chars[pos]);
+        if boundary {
+            score += if n == 0 { BONUS_BOUNDARY + BONUS_FIRST_CHAR } else { BONUS_BOUNDARY };
+        }
+        match prev_pos {
+            Some(prev) if pos == prev + 1 => score += BONUS_CONSECUTIVE,
+            Some(prev) => {
+                let gap = (pos - prev - 1) as i64;
+                score -= PENALTY_GAP_START + PENALTY_GAP_EXTENSION * (gap - 1);
+            }
+            None => {}
+        }
+        prev_pos = Some(pos);
+    }
+    Some((score, positions))
+}
+
+/// The first body line containing `query`, cut to a window around the match.
+fn body_snippet(body: &str, query: &str) -> Option<(String, (usize, usize))> {
+    const CONTEXT: usize = 20;
+    const WIDTH: usize = 60;
+    body.lines().find_map(|line| {
+        let (start, end) = *motions::match_ranges(line, query).first()?;
+        let skip = start.saturating_sub(CONTEXT);
+        let mut text: String = line.chars().skip(skip).take(WIDTH).collect();
+        let mut offset = start - skip;
+        if skip > 0 {
+            text.insert(0, '…');
+            offset += 1;
+        }
+        let len = end - start;
+        Some((text, (offset, offset + len)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(title: &str, tags: &[&str], body: &str) -> NoteEntry {
+        NoteEntry {
+            title: title.to_string(),
+            note: body.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_subsequence() {
+        let (_, positions) = fuzzy_match("Meeting notes", "mtgnts").unwrap();
+        assert_eq!(positions, vec![0, 3, 6, 8, 10, 12]);
+        assert_eq!(fuzzy_match("Meeting notes", ""), Some((0, Vec::new())));
+    }
+
+    #[test]
+    fn test_no_match() {
+        assert_eq!(fuzzy_match("Meeting notes", "xyz"), None);
+        // Every char is there, but not in order
+        assert_eq!(fuzzy_match("Meeting notes", "tm"), None);
+    }
+
+    #[test]
+    fn test_multibyte_positions() {
+        // Positions count chars, not bytes
+        let (_, positions) = fuzzy_match("Café Über", "éü").unwrap();
+        assert_eq!(positions, vec![3, 5]);
+        let (_, positions) = fuzzy_match("日本語のノート", "ノー").unwrap();
+        assert_eq!(positions, vec![4, 5]);
+    }
+
+    #[test]
+    fn test_ranking() {
+        // Consecutive chars at word starts beat scattered ones
+        let (tight, _) = fuzzy_match("notes", "not").unwrap();
+        let (loose, _) = fuzzy_match("a new outline today", "not").unwrap();
+        assert!(tight > loose);
+        let (boundary, _) = fuzzy_match("Meeting notes", "mn").unwrap();
+        let (inside, _) = fuzzy_match("summon", "mn").unwrap();
+        assert!(boundary > inside);
+
+        // Title matches rank above tag matches, which rank above body matches
+        let title = match_note(&note("Meeting notes", &[], ""), "mtg").unwrap();
+        let tag = match_note(&note("Agenda", &["mtg"], ""), "mtg").unwrap();
+        let body = match_note(&note("Agenda", &[], "see mtg log\n"), "mtg").unwrap();
+        assert!(title.rank() > tag.rank() && tag.rank() > body.rank());
+        assert_eq!(body.snippet, Some(("see mtg log".to_string(), (4, 7))));
+        assert!(match_note(&note("Agenda", &[], ""), "mtg").is_none());
+    }
+}
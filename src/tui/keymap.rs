@@ -0,0 +1,532 @@
+//! User-configurable keybindings.
+//!
+//! The handlers in `events` match on their default keys. The `keymap` section of
+//! the config binds key sequences to named actions per context; `Keymap::translate`
+//! rewrites those sequences into the action's default keys before dispatch, so
+//! every binding not mentioned in the config keeps working as before.
+
+use anyhow::{bail, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::{BTreeMap, HashMap};
+
+use super::{App, Focus, Mode};
+
+/// Where a key is pressed. Text prompts (search, command line, tag input) are not remappable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyContext {
+    List,
+    Tags,
+    Preview,
+    Outline,
+    Split,
+    Editor,
+    Insert,
+    Inbox,
+}
+
+const CONTEXTS: &[KeyContext] = &[
+    KeyContext::List,
+    KeyContext::Tags,
+    KeyContext::Preview,
+    KeyContext::Outline,
+    KeyContext::Split,
+    KeyContext::Editor,
+    KeyContext::Insert,
+    KeyContext::Inbox,
+];
+
+/// Action name used in the config to unbind a key.
+const UNBOUND: &str = "none";
+
+impl KeyContext {
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyContext::List => "list",
+            KeyContext::Tags => "tags",
+            KeyContext::Preview => "preview",
+            KeyContext::Outline => "outline",
+            KeyContext::Split => "split",
+            KeyContext::Editor => "editor",
+            KeyContext::Insert => "insert",
+            KeyContext::Inbox => "inbox",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        CONTEXTS.iter().copied().find(|c| c.name() == name)
+    }
+
+    /// Actions available in this context with their default key sequences.
+    pub fn actions(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            KeyContext::List => &[
+                ("down", "j"),
+                ("up", "k"),
+                ("open", "enter"),
+                ("edit", "e"),
+                ("create", "c"),
+                ("search", "/"),
+                ("command", ":"),
+                ("help", "?"),
+                ("pin", "p"),
+                ("archive", "A"),
+                ("toggle-archived", "H"),
+                ("undo", "u"),
+                ("details", "m"),
+                ("outline", "o"),
+                ("open-tab", "T"),
+                ("inbox", "I"),
+                ("dismiss-reminder", "D"),
+                ("sort", "S"),
+                ("reverse-sort", "R"),
+                ("focus-tags", "tab"),
+                ("clear", "esc"),
+                ("window", "ctrl-w"),
+                ("quit", "q"),
+            ],
+            KeyContext::Tags => &[
+                ("down", "j"),
+                ("up", "k"),
+                ("toggle-filter", "enter"),
+                ("clear-filters", "esc"),
+                ("focus-preview", "tab"),
+                ("command", ":"),
+                ("help", "?"),
+                ("window", "ctrl-w"),
+                ("quit", "q"),
+            ],
+            KeyContext::Preview => &[
+                ("down", "j"),
+                ("up", "k"),
+                ("half-page-down", "ctrl-d"),
+                ("half-page-up", "ctrl-u"),
+                ("top", "g g"),
+                ("bottom", "G"),
+                ("word-forward", "w"),
+                ("word-backward", "b"),
+                ("word-end", "E"),
+                ("line-start", "0"),
+                ("first-non-blank", "^"),
+                ("line-end", "$"),
+                ("paragraph-forward", "}"),
+                ("paragraph-backward", "{"),
+                ("next-heading", "] ]"),
+                ("prev-heading", "[ ["),
+                ("search-forward", "/"),
+                ("search-backward", "?"),
+                ("search-next", "n"),
+                ("search-prev", "N"),
+                ("set-mark", "m"),
+                ("jump-mark-line", "'"),
+                ("jump-mark", "`"),
+                ("center", "z z"),
+                ("scroll-top", "z t"),
+                ("scroll-bottom", "z b"),
+                ("visual", "V"),
+                ("yank", "y"),
+                ("toggle-task", "x"),
+                ("put", "p"),
+                ("put-above", "P"),
+                ("register", "\""),
+                ("edit", "e"),
+                ("outline", "o"),
+                ("open-tab", "T"),
+                ("close-tab", "X"),
+                ("next-tab", "g t"),
+                ("prev-tab", "g T"),
+                ("toggle-summary", "tab"),
+                ("command", ":"),
+                ("back", "esc"),
+                ("window", "ctrl-w"),
+                ("quit", "q"),
+            ],
+            KeyContext::Outline => &[
+                ("down", "j"),
+                ("up", "k"),
+                ("first", "g"),
+                ("last", "G"),
+                ("jump", "enter"),
+                ("hide", "o"),
+                ("back", "esc"),
+                ("window", "ctrl-w"),
+                ("quit", "q"),
+            ],
+            KeyContext::Split => &[
+                ("down", "j"),
+                ("up", "k"),
+                ("half-page-down", "ctrl-d"),
+                ("half-page-up", "ctrl-u"),
+                ("top", "g g"),
+                ("bottom", "G"),
+                ("back", "esc"),
+                ("window", "ctrl-w"),
+                ("quit", "q"),
+            ],
+            KeyContext::Editor => &[
+                ("left", "h"),
+                ("down", "j"),
+                ("up", "k"),
+                ("right", "l"),
+                ("line-start", "0"),
+                ("line-end", "$"),
+                ("top", "g g"),
+                ("bottom", "G"),
+                ("insert", "i"),
+                ("append", "a"),
+                ("insert-line-start", "I"),
+                ("append-line-end", "A"),
+                ("open-below", "o"),
+                ("open-above", "O"),
+                ("delete-char", "x"),
+                ("delete-line", "d d"),
+                ("visual", "V"),
+                ("yank", "y"),
+                ("put", "p"),
+                ("put-above", "P"),
+                ("register", "\""),
+                ("undo", "u"),
+                ("save", "ctrl-s"),
+                ("close", "esc"),
+            ],
+            KeyContext::Insert => &[("save", "ctrl-s"), ("normal-mode", "esc")],
+            KeyContext::Inbox => &[
+                ("down", "j"),
+                ("up", "k"),
+                ("file", "f"),
+                ("retitle", "r"),
+                ("tag", "t"),
+                ("merge", "m"),
+                ("delete", "d"),
+                ("undo", "u"),
+                ("help", "?"),
+                ("back", "esc"),
+                ("quit", "q"),
+            ],
+        }
+    }
+
+    /// The context for the next key press, or None while typing into a prompt
+    /// or partway through a built-in key sequence.
+    pub fn current(app: &App) -> Option<Self> {
+        if app.pending_window || app.pending_g || app.pending_prefix.is_some() {
+            return None;
+        }
+        match (&app.mode, &app.focus) {
+            (Mode::Normal, Focus::NoteList) => Some(KeyContext::List),
+            (Mode::TagBrowse, _) => Some(KeyContext::Tags),
+            (Mode::Normal | Mode::VisualLine, Focus::Preview) => Some(KeyContext::Preview),
+            (Mode::Normal, Focus::Outline) => Some(KeyContext::Outline),
+            (Mode::Normal, Focus::Split) => Some(KeyContext::Split),
+            (Mode::EditNormal, _) if !app.editor.as_ref().is_some_and(|e| e.pending_d) => Some(KeyContext::Editor),
+            (Mode::EditInsert, _) => Some(KeyContext::Insert),
+            (Mode::Inbox, _) => Some(KeyContext::Inbox),
+            _ => None,
+        }
+    }
+}
+
+/// A user binding: the configured sequence and the keys it stands for.
+struct Binding {
+    keys: Vec<KeyEvent>,
+    action: String,
+    target: Vec<KeyEvent>,
+}
+
+#[derive(Default)]
+pub struct Keymap {
+    bindings: HashMap<KeyContext, Vec<Binding>>,
+    pending: Vec<KeyEvent>,
+}
+
+impl Keymap {
+    /// Build the keymap from the config's `keymap` section, reporting every
+    /// unknown name, unparsable key and conflicting sequence at once.
+    pub fn new(config: &BTreeMap<String, BTreeMap<String, String>>) -> Result<Self> {
+        let mut errors = Vec::new();
+        let mut bindings: HashMap<KeyContext, Vec<Binding>> = HashMap::new();
+
+        for (context_name, entries) in config {
+            let Some(context) = KeyContext::from_name(context_name) else {
+                let names: Vec<&str> = CONTEXTS.iter().map(|c| c.name()).collect();
+                errors.push(format!("unknown mode '{}' (expected one of: {})", context_name, names.join(", ")));
+                continue;
+            };
+            let defaults = context.actions();
+            let mut list: Vec<Binding> = Vec::new();
+            for (keys_text, action) in entries {
+                let keys = match parse_sequence(keys_text) {
+                    Ok(keys) => keys,
+                    Err(e) => {
+                        errors.push(format!("{}: {}", context_name, e));
+                        continue;
+                    }
+                };
+                let target = if action == UNBOUND {
+                    Vec::new()
+                } else {
+                    match defaults.iter().find(|(name, _)| name == action) {
+                        Some((_, default_keys)) => parse_sequence(default_keys).unwrap_or_default(),
+                        None => {
+                            errors.push(format!("{}: unknown action '{}' for '{}'", context_name, action, keys_text));
+                            continue;
+                        }
+                    }
+                };
+                if let Some(other) = list.iter().find(|b| same_sequence(&b.keys, &keys)) {
+                    errors.push(format!(
+                        "{}: '{}' is bound to both '{}' and '{}'",
+                        context_name, keys_text, other.action, action
+                    ));
+                    continue;
+                }
+                list.push(Binding {
+                    keys,
+                    action: action.clone(),
+                    target,
+                });
+            }
+
+            // A sequence that is a strict prefix of another can never complete
+            for binding in &list {
+                let text = format_sequence(&binding.keys);
+                for other in &list {
+                    if is_strict_prefix(&binding.keys, &other.keys) {
+                        errors.push(format!(
+                            "{}: '{}' ({}) hides '{}' ({})",
+                            context_name,
+                            text,
+                            binding.action,
+                            format_sequence(&other.keys),
+                            other.action
+                        ));
+                    }
+                }
+                for (name, default_keys) in defaults {
+                    let default_seq = parse_sequence(default_keys).unwrap_or_default();
+                    if is_strict_prefix(&binding.keys, &default_seq) || is_strict_prefix(&default_seq, &binding.keys) {
+                        errors.push(format!(
+                            "{}: '{}' ({}) overlaps the built-in '{}' ({})",
+                            context_name, text, binding.action, default_keys, name
+                        ));
+                    }
+                }
+            }
+            bindings.insert(context, list);
+        }
+
+        if !errors.is_empty() {
+            bail!("Invalid keymap in config:\n  {}", errors.join("\n  "));
+        }
+        Ok(Self {
+            bindings,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Rewrite a key press into the keys the handlers understand. Returns nothing
+    /// while a configured multi-key sequence is still being typed.
+    pub fn translate(&mut self, context: Option<KeyContext>, key: KeyEvent) -> Vec<KeyEvent> {
+        let Some(list) = context.and_then(|c| self.bindings.get(&c)) else {
+            let mut keys = std::mem::take(&mut self.pending);
+            keys.push(key);
+            return keys;
+        };
+
+        self.pending.push(key);
+        if let Some(binding) = list.iter().find(|b| same_sequence(&b.keys, &self.pending)) {
+            self.pending.clear();
+            return binding.target.clone();
+        }
+        if list.iter().any(|b| is_strict_prefix(&self.pending, &b.keys)) {
+            return Vec::new();
+        }
+
+        // No binding starts this way. Built-in sequences (`g g` while `g x` is bound)
+        // go through as typed; otherwise pass the earlier keys through and retry the last one alone
+        let builtin = context.is_some_and(|c| {
+            c.actions().iter().any(|(_, keys)| {
+                let seq = parse_sequence(keys).unwrap_or_default();
+                same_sequence(&self.pending, &seq) || is_strict_prefix(&self.pending, &seq)
+            })
+        });
+        if builtin {
+            return std::mem::take(&mut self.pending);
+        }
+        let last = self.pending.pop().unwrap_or(key);
+        let mut keys = std::mem::take(&mut self.pending);
+        if keys.is_empty() {
+            keys.push(last);
+        } else {
+            keys.extend(self.translate(context, last));
+        }
+        keys
+    }
+
+    /// Every action in `context` with the sequences that trigger it, for the help overlay.
+    pub fn describe(&self, context: KeyContext) -> Vec<(&'static str, Vec<String>)> {
+        let user = self.bindings.get(&context).map(|v| v.as_slice()).unwrap_or_default();
+        context
+            .actions()
+            .iter()
+            .map(|(name, default_keys)| {
+                let default_seq = parse_sequence(default_keys).unwrap_or_default();
+                let mut keys: Vec<String> = user
+                    .iter()
+                    .filter(|b| b.action == *name)
+                    .map(|b| format_sequence(&b.keys))
+                    .collect();
+                // The default still works unless a user binding took it over
+                if !user.iter().any(|b| same_sequence(&b.keys, &default_seq)) {
+                    keys.push(default_keys.to_string());
+                }
+                (*name, keys)
+            })
+            .collect()
+    }
+}
+
+fn same_key(a: &KeyEvent, b: &KeyEvent) -> bool {
+    let mods = KeyModifiers::CONTROL | KeyModifiers::ALT;
+    a.code == b.code && (a.modifiers & mods) == (b.modifiers & mods)
+}
+
+fn same_sequence(a: &[KeyEvent], b: &[KeyEvent]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| same_key(x, y))
+}
+
+fn is_strict_prefix(prefix: &[KeyEvent], seq: &[KeyEvent]) -> bool {
+    prefix.len() < seq.len() && same_sequence(prefix, &seq[..prefix.len()])
+}
+
+/// Parse a space-separated key sequence such as `ctrl-x ctrl-s` or `g g`.
+fn parse_sequence(text: &str) -> Result<Vec<KeyEvent>> {
+    let keys: Vec<KeyEvent> = text.split_whitespace().map(parse_key).collect::<Result<_>>()?;
+    if keys.is_empty() {
+        bail!("empty key sequence");
+    }
+    Ok(keys)
+}
+
+fn parse_key(text: &str) -> Result<KeyEvent> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = text;
+    loop {
+        if let Some(r) = rest.strip_prefix("ctrl-").filter(|r| !r.is_empty()) {
+            modifiers |= KeyModifiers::CONTROL;
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("alt-").filter(|r| !r.is_empty()) {
+            modifiers |= KeyModifiers::ALT;
+            rest = r;
+        } else {
+            break;
+        }
+    }
+    let code = match rest {
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" | "shift-tab" => KeyCode::BackTab,
+        "space" => KeyCode::Char(' '),
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        _ => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => bail!("unknown key '{}'", text),
+            }
+        }
+    };
+    Ok(KeyEvent::new(code, modifiers))
+}
+
+fn format_key(key: &KeyEvent) -> String {
+    let mut text = String::new();
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        text.push_str("ctrl-");
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        text.push_str("alt-");
+    }
+    match key.code {
+        KeyCode::Char(' ') => text.push_str("space"),
+        KeyCode::Char(c) => text.push(c),
+        KeyCode::BackTab => text.push_str("backtab"),
+        KeyCode::PageUp => text.push_str("pageup"),
+        KeyCode::PageDown => text.push_str("pagedown"),
+        code => text.push_str(&format!("{:?}", code).to_lowercase()),
+    }
+    text
+}
+
+fn format_sequence(keys: &[KeyEvent]) -> String {
+    keys.iter().map(format_key).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(context: &str, entries: &[(&str, &str)]) -> BTreeMap<String, BTreeMap<String, String>> {
+        let entries = entries.iter().map(|(k, a)| (k.to_string(), a.to_string())).collect();
+        BTreeMap::from([(context.to_string(), entries)])
+    }
+
+    fn error(context: &str, entries: &[(&str, &str)]) -> String {
+        match Keymap::new(&config(context, entries)) {
+            Ok(_) => panic!("keymap should be rejected"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    fn keys(text: &str) -> Vec<KeyEvent> {
+        parse_sequence(text).unwrap()
+    }
+
+    #[test]
+    fn test_conflicts() {
+        // Two spellings of the same sequence
+        assert!(error("preview", &[("g x", "top"), ("g  x", "bottom")]).contains("'g x' is bound to both 'bottom' and 'top'"));
+        assert!(error("list", &[("space", "down"), ("space j", "up")]).contains("'space' (down) hides 'space j' (up)"));
+        assert!(error("preview", &[("z", "center")]).contains("overlaps the built-in 'z z' (center)"));
+        assert!(error("nowhere", &[("j", "down")]).contains("unknown mode 'nowhere'"));
+        assert!(error("list", &[("j", "fly")]).contains("unknown action 'fly'"));
+        assert!(error("list", &[("hyper-j", "down")]).contains("unknown key 'hyper-j'"));
+        assert!(Keymap::new(&config("list", &[("space j", "down"), ("ctrl-x ctrl-s", "quit")])).is_ok());
+    }
+
+    #[test]
+    fn test_translate() {
+        let mut keymap = Keymap::new(&config(
+            "preview",
+            &[("space d", "half-page-down"), ("ctrl-x g", "top"), ("q", "none"), ("J", "down")],
+        ))
+        .unwrap();
+        let preview = Some(KeyContext::Preview);
+        let mut press = |text: &str| keys(text).into_iter().flat_map(|k| keymap.translate(preview, k)).collect::<Vec<_>>();
+
+        assert_eq!(press("J"), keys("j"));
+        assert_eq!(press("space"), vec![]);
+        assert_eq!(press("d"), keys("ctrl-d"));
+        assert_eq!(press("ctrl-x g"), keys("g g"));
+        assert_eq!(press("q"), vec![]);
+        // Unbound keys and built-in sequences pass through as typed
+        assert_eq!(press("k"), keys("k"));
+        assert_eq!(press("g g"), keys("g g"));
+        // A started sequence that goes nowhere gives back its keys
+        assert_eq!(press("space k"), keys("space k"));
+        assert_eq!(press("space J"), keys("space j"));
+
+        // Other contexts keep the defaults
+        assert_eq!(keymap.translate(Some(KeyContext::List), keys("J")[0]), keys("J"));
+        assert_eq!(keymap.translate(None, keys("q")[0]), keys("q"));
+    }
+}
@@ -1,19 +1,30 @@
+mod editor;
 mod events;
-
+mod fuzzy;
+mod keymap;
+pub mod motions;
+mod palette;
+mod registers;
+pub mod theme;
 mod ui;
 
+use std::collections::HashMap;
 use std::time::Instant;
 
 use anyhow::Result;
 use crossterm::{
-    event::{self, KeyEventKind},
+    event::{self, DisableMouseCapture, EnableMouseCapture, KeyEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::prelude::*;
 use rusqlite::Connection;
 
-use crate::db::{self, NoteEntry};
+use crate::config::{self, Config, NoteColumn};
+use crate::db::{self, AttachmentEntry, NoteEntry};
+use crate::session::{self, Orphan, Session};
+use crate::sort::{self, SortKey};
+use editor::EditorState;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Mode {
@@ -26,6 +37,19 @@ pub enum Mode {
     EditTagsRemove,
     TagBrowse,
     VisualLine,
+    ConfirmDelete,
+    EditNormal,
+    EditInsert,
+    PreviewSearch,
+    Help,
+    ResolveConflict,
+    Recover,
+    Inbox,
+    InboxTitle,
+    InboxTags,
+    InboxMerge,
+    ExtractNote,
+    Unlock,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -33,6 +57,8 @@ pub enum Focus {
     NoteList,
     TagPanel,
     Preview,
+    Outline,
+    Split,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -41,6 +67,81 @@ pub enum PreviewTab {
     Summary,
 }
 
+/// A destructive action that can be reverted with `u`. Changes to a note's
+/// contents keep the revision they wrote, so undoing them never overwrites a
+/// change made since.
+#[derive(Debug, Clone)]
+pub enum UndoAction {
+    Delete { id: i64, title: String },
+    Edit { id: i64, title: String, previous: String, revision: i64 },
+    Tags { id: i64, title: String, previous: Vec<String> },
+    Rename { id: i64, title: String, previous: String },
+    /// A capture appended to `target` and moved to the trash
+    Merge { id: i64, title: String, target: i64, target_title: String, previous: String, revision: i64 },
+    /// Lines moved out of `source` into the new note `id`
+    Extract { id: i64, title: String, source: i64, source_title: String, previous: String, revision: i64 },
+}
+
+/// An edit held back because the note changed outside the TUI while it was
+/// being edited, waiting for the user to merge, copy, overwrite or drop it.
+pub struct PendingSave {
+    pub id: i64,
+    pub title: String,
+    pub contents: String,
+    /// The note as it was when editing started
+    pub base: String,
+    /// The note as changed outside the TUI, with its revision and tags
+    pub theirs: String,
+    pub revision: i64,
+    pub tags: Vec<String>,
+    /// Temp file of the $EDITOR session the edit came from, kept until resolved
+    pub session: Option<Session>,
+}
+
+/// A note opened in its own preview tab, remembering where the reader was.
+pub struct NoteTab {
+    pub note_id: i64,
+    pub cursor: usize,
+    pub scroll: u16,
+}
+
+/// A second preview pane showing a fixed note next to the main preview.
+pub struct SplitPane {
+    pub note_id: i64,
+    pub vertical: bool,
+    pub cursor: usize,
+    pub scroll: u16,
+    pub content_height: u16,
+}
+
+/// Where the panes were drawn last frame, for mapping mouse positions.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PaneAreas {
+    /// Everything above the status bar
+    pub main: Rect,
+    /// Note list rows, and the index of the first visible note
+    pub note_list: Rect,
+    pub note_list_offset: usize,
+    /// Tag panel rows, and the index of the first visible tag
+    pub tag_panel: Rect,
+    pub tag_offset: usize,
+    /// Column of the border between the left panels and the preview
+    pub divider: u16,
+    /// Preview text below the metadata header
+    pub preview: Rect,
+    /// Split pane text
+    pub split: Rect,
+}
+
+/// What a held left mouse button is dragging.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MouseDrag {
+    /// The border between the left panels and the preview
+    Divider,
+    /// A line selection in the preview, from the line first pressed
+    Selection(usize),
+}
+
 #[derive(Clone)]
 pub struct TagEntry {
     pub name: String,
@@ -50,6 +151,7 @@ pub struct TagEntry {
 pub struct App {
     pub notes: Vec<NoteEntry>,
     pub filtered_notes: Vec<usize>,
+    pub search_matches: Vec<fuzzy::NoteMatch>,
     pub selected: usize,
     pub mode: Mode,
     pub search_query: String,
@@ -76,17 +178,70 @@ pub struct App {
     pub selected_suggestion: usize,
     pub preview_cursor: usize,
     pub visual_anchor: Option<usize>,
-    pub yank_register: Option<String>,
+    pub registers: registers::Registers,
+    /// Register named with `"x` for the next yank, delete or put
+    pub pending_register: Option<char>,
+    pub show_archived: bool,
+    pub undo_stack: Vec<UndoAction>,
+    pub sort_key: SortKey,
+    pub sort_reverse: bool,
+    pub show_details: bool,
+    pub list_columns: Vec<NoteColumn>,
+    pub show_preview_metadata: bool,
+    pub editor: Option<EditorState>,
+    pub preview_col: usize,
+    pub preview_count: Option<usize>,
+    pub pending_prefix: Option<char>,
+    pub preview_search: Option<String>,
+    pub preview_search_backward: bool,
+    pub preview_search_input: String,
+    pub marks: HashMap<(i64, char), (usize, usize)>,
+    pub show_outline: bool,
+    pub outline_selected: usize,
+    pub tabs: Vec<NoteTab>,
+    pub active_tab: Option<usize>,
+    pub split: Option<SplitPane>,
+    pub pending_window: bool,
+    pub command_input: String,
+    pub command_history: Vec<String>,
+    pub history_index: Option<usize>,
+    pub completions: Vec<String>,
+    pub completion_index: Option<usize>,
+    pub help_scroll: u16,
+    pub help_context: Option<keymap::KeyContext>,
+    pub keymap: keymap::Keymap,
+    pub theme: theme::Theme,
+    pub mouse: bool,
+    pub left_percent: u16,
+    pub areas: PaneAreas,
+    pub mouse_drag: Option<MouseDrag>,
+    pub data_version: i64,
+    pub pending_save: Option<PendingSave>,
+    /// Unsaved edits from earlier editor sessions, offered on startup
+    pub recovery: Vec<Orphan>,
+    pub recovery_selected: usize,
+    /// Tag filters and search from before the inbox was opened, restored on leaving it
+    pub inbox_previous: Option<(Vec<String>, String)>,
+    /// Notes matching the merge prompt, best match first, as (id, title)
+    pub merge_targets: Vec<(i64, String)>,
+    pub merge_selected: usize,
+    /// Notes whose reminder has come due, as of the last filter
+    pub reminders_due: usize,
+    /// Stored files by hash, for naming attachment references in the preview
+    pub attachments: HashMap<String, AttachmentEntry>,
 }
 
 impl App {
-    pub fn new(conn: Connection, notes: Vec<NoteEntry>) -> Self {
+    pub fn new(conn: Connection, notes: Vec<NoteEntry>, config: &Config) -> Self {
         let filtered_notes: Vec<usize> = (0..notes.len()).collect();
         let all_tags = compute_tags(&notes);
         let visible_tags = all_tags.clone();
+        let data_version = db::data_version(&conn).unwrap_or_default();
+        let attachments = db::all_attachments(&conn).unwrap_or_default();
         Self {
             notes,
             filtered_notes,
+            search_matches: Vec::new(),
             selected: 0,
             mode: Mode::Normal,
             search_query: String::new(),
@@ -113,7 +268,51 @@ impl App {
             selected_suggestion: 0,
             preview_cursor: 0,
             visual_anchor: None,
-            yank_register: None,
+            registers: registers::Registers::default(),
+            pending_register: None,
+            show_archived: false,
+            undo_stack: Vec::new(),
+            sort_key: SortKey::default(),
+            sort_reverse: false,
+            show_details: config.note_list_details,
+            list_columns: config.note_list_columns.clone(),
+            show_preview_metadata: config.preview_metadata,
+            editor: None,
+            preview_col: 0,
+            preview_count: None,
+            pending_prefix: None,
+            preview_search: None,
+            preview_search_backward: false,
+            preview_search_input: String::new(),
+            marks: std::collections::HashMap::new(),
+            show_outline: false,
+            outline_selected: 0,
+            tabs: Vec::new(),
+            active_tab: None,
+            split: None,
+            pending_window: false,
+            command_input: String::new(),
+            command_history: Vec::new(),
+            history_index: None,
+            completions: Vec::new(),
+            completion_index: None,
+            help_scroll: 0,
+            help_context: None,
+            keymap: keymap::Keymap::default(),
+            theme: theme::Theme::default(),
+            mouse: config.mouse,
+            left_percent: 30,
+            areas: PaneAreas::default(),
+            mouse_drag: None,
+            data_version,
+            pending_save: None,
+            recovery: Vec::new(),
+            recovery_selected: 0,
+            inbox_previous: None,
+            merge_targets: Vec::new(),
+            merge_selected: 0,
+            reminders_due: 0,
+            attachments,
         }
     }
 
@@ -123,9 +322,38 @@ impl App {
             .and_then(|&idx| self.notes.get(idx))
     }
 
+    pub fn note_by_id(&self, id: i64) -> Option<&NoteEntry> {
+        self.notes.iter().find(|n| n.id == id)
+    }
+
+    /// The note shown in the main preview: the active tab's note, or the list selection.
+    pub fn preview_note(&self) -> Option<&NoteEntry> {
+        match self.active_tab {
+            Some(i) => self.tabs.get(i).and_then(|t| self.note_by_id(t.note_id)),
+            None => self.selected_note(),
+        }
+    }
+
+    /// Drop tabs and split panes whose notes no longer exist.
+    fn prune_panes(&mut self) {
+        let active_id = self.active_tab.and_then(|i| self.tabs.get(i)).map(|t| t.note_id);
+        let ids: Vec<i64> = self.notes.iter().map(|n| n.id).collect();
+        self.tabs.retain(|t| ids.contains(&t.note_id));
+        self.active_tab = active_id.and_then(|id| self.tabs.iter().position(|t| t.note_id == id));
+        if self.split.as_ref().is_some_and(|s| !ids.contains(&s.note_id)) {
+            self.split = None;
+            if self.focus == Focus::Split {
+                self.focus = Focus::Preview;
+            }
+        }
+    }
+
     pub fn refresh_notes(&mut self) -> Result<()> {
         self.notes = db::list_notes(&self.conn)?;
+        sort::sort_notes(&mut self.notes, self.sort_key, self.sort_reverse);
         self.all_tags = compute_tags(&self.notes);
+        self.attachments = db::all_attachments(&self.conn)?;
+        self.prune_panes();
         self.apply_filter();
         if self.selected_tag >= self.all_tags.len() && !self.all_tags.is_empty() {
             self.selected_tag = self.all_tags.len() - 1;
@@ -133,30 +361,78 @@ impl App {
         Ok(())
     }
 
+    /// Reload the notes if another process wrote to the database since the last
+    /// check, keeping the selected note and the preview position.
+    pub fn reload_if_changed(&mut self) -> Result<()> {
+        // The selection decides what a confirmation applies to, so hold off until it's answered
+        if matches!(self.mode, Mode::ConfirmDelete | Mode::ResolveConflict | Mode::ExtractNote | Mode::Unlock) {
+            return Ok(());
+        }
+        let version = db::data_version(&self.conn)?;
+        if version == self.data_version {
+            return Ok(());
+        }
+
+        let selected_id = self.selected_note().map(|n| n.id);
+        let preview_id = self.preview_note().map(|n| n.id);
+        self.refresh_notes()?;
+        // Only now, so a failed reload is tried again
+        self.data_version = version;
+        if let Some(id) = selected_id {
+            self.select_note_id(id);
+        }
+
+        if let Some(editor) = &self.editor {
+            if self.note_by_id(editor.note_id).map(|n| n.revision) != Some(editor.revision) {
+                self.status_message = Some(format!(
+                    "'{}' changed outside scrap; saving will ask how to combine the changes",
+                    editor.title
+                ));
+                self.status_expires = Some(Instant::now() + std::time::Duration::from_secs(5));
+            }
+            return Ok(());
+        }
+        if self.preview_note().map(|n| n.id) == preview_id {
+            let last = self.preview_raw_lines().len().saturating_sub(1);
+            self.preview_cursor = self.preview_cursor.min(last);
+            self.preview_scroll = self.preview_scroll.min(last as u16);
+        } else {
+            self.preview_scroll = 0;
+            self.preview_cursor = 0;
+            self.preview_col = 0;
+        }
+        if self.status_message.is_none() {
+            self.status_message = Some("Reloaded notes changed outside scrap".to_string());
+            self.status_expires = Some(Instant::now() + std::time::Duration::from_secs(2));
+        }
+        Ok(())
+    }
+
     pub fn apply_filter(&mut self) {
         let query = self.search_query.to_lowercase();
-        self.filtered_notes = self
+        let mut results: Vec<(usize, fuzzy::NoteMatch)> = self
             .notes
             .iter()
             .enumerate()
             .filter(|(_, note)| {
-                // Tag filter — note must match at least one selected tag
-                if !self.active_tag_filters.is_empty() {
-                    if !note.tags.iter().any(|t| self.active_tag_filters.contains(t)) {
-                        return false;
-                    }
-                }
-                // Search query
-                if query.is_empty() {
-                    true
-                } else {
-                    note.title.to_lowercase().contains(&query)
-                        || note.note.to_lowercase().contains(&query)
-                        || note.tags.iter().any(|t| t.to_lowercase().contains(&query))
+                // Archived notes are hidden unless explicitly included
+                if note.archived && !self.show_archived {
+                    return false;
                 }
+                // Tag filter — note must match at least one selected tag
+                note.has_any_tag(&self.active_tag_filters)
             })
-            .map(|(i, _)| i)
+            .filter_map(|(i, note)| fuzzy::match_note(note, &query).map(|m| (i, m)))
             .collect();
+        // Best matches first while searching; the sort order breaks ties
+        // otherwise notes with a due reminder come first
+        if !query.is_empty() {
+            results.sort_by_key(|(_, m)| std::cmp::Reverse(m.rank()));
+        } else {
+            results.sort_by_key(|(i, _)| !self.notes[*i].reminder_due());
+        }
+        self.reminders_due = self.notes.iter().filter(|n| n.reminder_due()).count();
+        (self.filtered_notes, self.search_matches) = results.into_iter().unzip();
         if self.selected >= self.filtered_notes.len() {
             self.selected = 0;
         }
@@ -174,6 +450,38 @@ impl App {
         }
     }
 
+    /// Re-sort notes with the current sort settings, keeping the selected note selected.
+    pub fn apply_sort(&mut self) {
+        let selected_id = self.selected_note().map(|n| n.id);
+        sort::sort_notes(&mut self.notes, self.sort_key, self.sort_reverse);
+        self.apply_filter();
+        if let Some(id) = selected_id {
+            self.select_note_id(id);
+        }
+    }
+
+    /// Re-sort when a reminder has come due since the list was last filtered.
+    pub fn check_reminders(&mut self) {
+        if self.mode != Mode::Normal {
+            return;
+        }
+        let due = self.notes.iter().filter(|n| n.reminder_due()).count();
+        if due != self.reminders_due {
+            self.apply_sort();
+        }
+    }
+
+    /// Move the selection to the note with the given id, if it is visible.
+    pub fn select_note_id(&mut self, id: i64) {
+        if let Some(pos) = self
+            .filtered_notes
+            .iter()
+            .position(|&idx| self.notes[idx].id == id)
+        {
+            self.selected = pos;
+        }
+    }
+
     pub fn move_selection(&mut self, delta: i32) {
         let len = self.filtered_notes.len();
         if len == 0 {
@@ -226,6 +534,23 @@ impl App {
         }
     }
 
+    /// Rank the notes a capture could be merged into by the merge prompt's
+    /// input, leaving out the capture itself.
+    pub fn update_merge_targets(&mut self) {
+        let current = self.selected_note().map(|n| n.id);
+        let query = self.input_buffer.trim().to_lowercase();
+        let mut ranked: Vec<(i64, &NoteEntry)> = self
+            .notes
+            .iter()
+            .filter(|n| Some(n.id) != current)
+            .filter_map(|n| fuzzy::fuzzy_match(&n.title, &query).map(|(score, _)| (score, n)))
+            .collect();
+        // Stable, so equal scores keep the list's order
+        ranked.sort_by_key(|r| std::cmp::Reverse(r.0));
+        self.merge_targets = ranked.into_iter().take(8).map(|(_, n)| (n.id, n.title.clone())).collect();
+        self.merge_selected = 0;
+    }
+
     /// Accept the currently selected tag suggestion
     pub fn accept_tag_suggestion(&mut self, buffer: &mut String) {
         if let Some(suggestion) = self.tag_suggestions.get(self.selected_suggestion) {
@@ -257,12 +582,16 @@ impl App {
             PreviewTab::Summary if self.summary_content.is_some() => {
                 self.summary_content.clone()
             }
-            _ => self.selected_note().map(|n| n.note.clone()),
+            _ => self.preview_note().map(|n| n.note.clone()),
         }
     }
 
     /// Get the lines of raw content for the preview.
+    /// While the embedded editor is open these are the editor's buffer lines.
     pub fn preview_raw_lines(&self) -> Vec<String> {
+        if let Some(editor) = &self.editor {
+            return editor.lines.clone();
+        }
         self.preview_raw_content()
             .map(|c| c.lines().map(|l| l.to_string()).collect())
             .unwrap_or_default()
@@ -299,14 +628,33 @@ fn compute_tags_from_refs(notes: &[&NoteEntry]) -> Vec<TagEntry> {
     tags
 }
 
-pub fn run() -> Result<()> {
+/// Launch the TUI. `query` pre-populates the search and `tags` preselects tag filters.
+pub fn run(query: Option<&str>, tags: &[String]) -> Result<()> {
+    let config = config::load()?;
+    let keymap = keymap::Keymap::new(&config.keymap)?;
+    let theme = theme::Theme::load(&config)?;
     let conn = db::get_db()?;
+    db::purge_trash(&conn, config.trash_retention_days)?;
     let notes = db::list_notes(&conn)?;
-    let mut app = App::new(conn, notes);
+    let mut app = App::new(conn, notes, &config);
+    app.keymap = keymap;
+    app.theme = theme;
+    if let Some(query) = query {
+        app.search_query = query.to_string();
+    }
+    app.active_tag_filters = tags.to_vec();
+    app.apply_filter();
+    app.recovery = session::orphans()?;
+    if !app.recovery.is_empty() {
+        app.mode = Mode::Recover;
+    }
 
     enable_raw_mode()?;
     let mut stdout = std::io::stdout();
     execute!(stdout, EnterAlternateScreen)?;
+    if app.mouse {
+        execute!(stdout, EnableMouseCapture)?;
+    }
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
     terminal.hide_cursor()?;
@@ -314,6 +662,9 @@ pub fn run() -> Result<()> {
     let result = run_loop(&mut app, &mut terminal);
 
     disable_raw_mode()?;
+    if app.mouse {
+        execute!(terminal.backend_mut(), DisableMouseCapture)?;
+    }
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     terminal.show_cursor()?;
 
@@ -333,13 +684,22 @@ fn run_loop(
             }
         }
 
+        // A busy or locked database shouldn't end the session; try again next tick
+        if let Err(e) = app.reload_if_changed() {
+            app.status_message = Some(format!("Couldn't reload notes: {}", e));
+            app.status_expires = Some(Instant::now() + std::time::Duration::from_secs(3));
+        }
+        app.check_reminders();
+
         terminal.draw(|f| ui::draw(f, app))?;
 
         if event::poll(std::time::Duration::from_millis(250))? {
-            if let event::Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
+            match event::read()? {
+                event::Event::Key(key) if key.kind == KeyEventKind::Press => {
                     events::handle_key(app, key, terminal)?;
                 }
+                event::Event::Mouse(mouse) => events::handle_mouse(app, mouse)?,
+                _ => {}
             }
         }
 
@@ -0,0 +1,336 @@
+//! Cursor motions and in-note search over the raw lines shown in the preview.
+//! Positions are `(row, col)` with `col` counted in chars.
+
+use crate::markdown;
+
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+    Blank,
+    Word,
+    Punct,
+}
+
+fn class_of(c: char) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Blank
+    } else if c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punct
+    }
+}
+
+/// Every char in the buffer with its position, plus a '\n' at the end of each line.
+fn flatten(lines: &[String]) -> Vec<(usize, usize, char)> {
+    let mut chars = Vec::new();
+    for (row, line) in lines.iter().enumerate() {
+        let mut col = 0;
+        for c in line.chars() {
+            chars.push((row, col, c));
+            col += 1;
+        }
+        chars.push((row, col, '\n'));
+    }
+    chars
+}
+
+fn flat_index(chars: &[(usize, usize, char)], row: usize, col: usize) -> usize {
+    chars
+        .iter()
+        .position(|&(r, c, ch)| r == row && (c == col || ch == '\n'))
+        .unwrap_or(0)
+}
+
+/// An empty line counts as a word boundary, as in vim.
+fn is_empty_line(chars: &[(usize, usize, char)], i: usize) -> bool {
+    let (_, col, ch) = chars[i];
+    ch == '\n' && col == 0
+}
+
+/// Position of `chars[i]`. Past the end of the buffer stays on its last char, as in vim.
+fn position(chars: &[(usize, usize, char)], i: usize) -> (usize, usize) {
+    let (row, col, _) = chars[i];
+    if i + 1 == chars.len() && col > 0 {
+        (row, col - 1)
+    } else {
+        (row, col)
+    }
+}
+
+/// Start of the next word (`w`).
+pub fn next_word_start(lines: &[String], row: usize, col: usize) -> (usize, usize) {
+    let chars = flatten(lines);
+    if chars.is_empty() {
+        return (row, col);
+    }
+    let mut i = flat_index(&chars, row, col);
+    let start_class = class_of(chars[i].2);
+    if start_class != CharClass::Blank {
+        while i + 1 < chars.len() && class_of(chars[i].2) == start_class {
+            i += 1;
+        }
+    } else if i + 1 < chars.len() {
+        i += 1;
+    }
+    while i + 1 < chars.len() && class_of(chars[i].2) == CharClass::Blank && !is_empty_line(&chars, i) {
+        i += 1;
+    }
+    position(&chars, i)
+}
+
+/// End of the current or next word (`e`).
+pub fn word_end(lines: &[String], row: usize, col: usize) -> (usize, usize) {
+    let chars = flatten(lines);
+    if chars.is_empty() {
+        return (row, col);
+    }
+    let mut i = flat_index(&chars, row, col);
+    if i + 1 < chars.len() {
+        i += 1;
+    }
+    while i + 1 < chars.len() && class_of(chars[i].2) == CharClass::Blank {
+        i += 1;
+    }
+    let class = class_of(chars[i].2);
+    while i + 1 < chars.len() && class_of(chars[i + 1].2) == class && class != CharClass::Blank {
+        i += 1;
+    }
+    position(&chars, i)
+}
+
+/// Start of the previous word (`b`).
+pub fn prev_word_start(lines: &[String], row: usize, col: usize) -> (usize, usize) {
+    let chars = flatten(lines);
+    if chars.is_empty() {
+        return (row, col);
+    }
+    let mut i = flat_index(&chars, row, col);
+    i = i.saturating_sub(1);
+    while i > 0 && class_of(chars[i].2) == CharClass::Blank && !is_empty_line(&chars, i) {
+        i -= 1;
+    }
+    let class = class_of(chars[i].2);
+    while i > 0 && class_of(chars[i - 1].2) == class && class != CharClass::Blank {
+        i -= 1;
+    }
+    (chars[i].0, chars[i].1)
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// Next blank line after the current paragraph (`}`).
+pub fn next_paragraph(lines: &[String], row: usize) -> usize {
+    let last = lines.len().saturating_sub(1);
+    let mut r = row;
+    while r < last && is_blank(&lines[r]) {
+        r += 1;
+    }
+    while r < last && !is_blank(&lines[r]) {
+        r += 1;
+    }
+    r
+}
+
+/// Previous blank line before the current paragraph (`{`).
+pub fn prev_paragraph(lines: &[String], row: usize) -> usize {
+    let mut r = row;
+    while r > 0 && is_blank(&lines[r]) {
+        r -= 1;
+    }
+    while r > 0 && !is_blank(&lines[r]) {
+        r -= 1;
+    }
+    r
+}
+
+/// Index into `outline` of the section containing `row`.
+pub fn current_section(outline: &[(usize, usize, String)], row: usize) -> Option<usize> {
+    outline.iter().rposition(|&(r, _, _)| r <= row)
+}
+
+/// Next heading below `row` (`]]`).
+pub fn next_heading(lines: &[String], row: usize) -> Option<usize> {
+    markdown::heading_rows(lines).into_iter().find(|&r| r > row)
+}
+
+/// Previous heading above `row` (`[[`).
+pub fn prev_heading(lines: &[String], row: usize) -> Option<usize> {
+    markdown::heading_rows(lines).into_iter().rev().find(|&r| r < row)
+}
+
+/// Char ranges of `query` in `text`. Matching is case-insensitive unless the
+/// query contains an uppercase letter (smartcase).
+pub fn match_ranges(text: &str, query: &str) -> Vec<(usize, usize)> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let case_sensitive = query.chars().any(|c| c.is_uppercase());
+    let fold = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    };
+    let hay: Vec<char> = text.chars().map(fold).collect();
+    let needle: Vec<char> = query.chars().map(fold).collect();
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i + needle.len() <= hay.len() {
+        if hay[i..i + needle.len()] == needle[..] {
+            ranges.push((i, i + needle.len()));
+            i += needle.len();
+        } else {
+            i += 1;
+        }
+    }
+    ranges
+}
+
+/// Find the next match of `query` after (or before, if `backward`) the given position,
+/// wrapping around the buffer. Returns the match position and whether the search wrapped.
+pub fn find_match(
+    lines: &[String],
+    query: &str,
+    row: usize,
+    col: usize,
+    backward: bool,
+) -> Option<((usize, usize), bool)> {
+    let mut matches = Vec::new();
+    for (r, line) in lines.iter().enumerate() {
+        for (start, _) in match_ranges(line, query) {
+            matches.push((r, start));
+        }
+    }
+    if backward {
+        match matches.iter().rev().find(|&&m| m < (row, col)) {
+            Some(&m) => Some((m, false)),
+            None => matches.last().map(|&m| (m, true)),
+        }
+    } else {
+        match matches.iter().find(|&&m| m > (row, col)) {
+            Some(&m) => Some((m, false)),
+            None => matches.first().map(|&m| (m, true)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|l| l.to_string()).collect()
+    }
+
+    /// Positions visited by repeating `motion` from `start`.
+    fn walk(
+        lines: &[String],
+        motion: fn(&[String], usize, usize) -> (usize, usize),
+        start: (usize, usize),
+        steps: usize,
+    ) -> Vec<(usize, usize)> {
+        let mut pos = start;
+        (0..steps)
+            .map(|_| {
+                pos = motion(lines, pos.0, pos.1);
+                pos
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_next_word_start() {
+        let buf = lines(&["foo bar", "", "baz.qux"]);
+        // Stops on the empty line, then on each word and punctuation run
+        assert_eq!(walk(&buf, next_word_start, (0, 0), 6), [(0, 4), (1, 0), (2, 0), (2, 3), (2, 4), (2, 6)]);
+    }
+
+    #[test]
+    fn test_word_end() {
+        let buf = lines(&["foo bar", "", "baz.qux"]);
+        assert_eq!(walk(&buf, word_end, (0, 0), 6), [(0, 2), (0, 6), (2, 2), (2, 3), (2, 6), (2, 6)]);
+    }
+
+    #[test]
+    fn test_prev_word_start() {
+        let buf = lines(&["foo bar", "", "baz.qux"]);
+        assert_eq!(walk(&buf, prev_word_start, (2, 6), 6), [(2, 4), (2, 3), (2, 0), (1, 0), (0, 4), (0, 0)]);
+        assert_eq!(prev_word_start(&buf, 0, 0), (0, 0));
+    }
+
+    #[test]
+    fn test_word_motions_on_empty_buffers() {
+        for buf in [lines(&[]), lines(&[""])] {
+            assert_eq!(next_word_start(&buf, 0, 0), (0, 0));
+            assert_eq!(word_end(&buf, 0, 0), (0, 0));
+            assert_eq!(prev_word_start(&buf, 0, 0), (0, 0));
+        }
+    }
+
+    #[test]
+    fn test_word_motions_count_chars() {
+        let buf = lines(&["héllo wörld"]);
+        assert_eq!(next_word_start(&buf, 0, 0), (0, 6));
+        assert_eq!(word_end(&buf, 0, 0), (0, 4));
+        assert_eq!(word_end(&buf, 0, 6), (0, 10));
+        assert_eq!(prev_word_start(&buf, 0, 10), (0, 6));
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        let buf = lines(&["a", "b", "", "c", "", "", "d"]);
+        assert_eq!(next_paragraph(&buf, 0), 2);
+        assert_eq!(next_paragraph(&buf, 2), 4);
+        assert_eq!(next_paragraph(&buf, 4), 6);
+        assert_eq!(next_paragraph(&buf, 6), 6);
+        assert_eq!(prev_paragraph(&buf, 6), 5);
+        assert_eq!(prev_paragraph(&buf, 5), 2);
+        assert_eq!(prev_paragraph(&buf, 2), 0);
+        assert_eq!(next_paragraph(&[], 0), 0);
+        assert_eq!(prev_paragraph(&lines(&[""]), 0), 0);
+    }
+
+    #[test]
+    fn test_headings() {
+        let buf = lines(&["# A", "text", "```", "# not a heading", "```", "## B"]);
+        assert_eq!(next_heading(&buf, 0), Some(5));
+        assert_eq!(next_heading(&buf, 5), None);
+        assert_eq!(prev_heading(&buf, 5), Some(0));
+        assert_eq!(prev_heading(&buf, 0), None);
+
+        let outline = markdown::outline(&buf);
+        assert_eq!(outline, [(0, 1, "A".to_string()), (5, 2, "B".to_string())]);
+        assert_eq!(current_section(&outline, 3), Some(0));
+        assert_eq!(current_section(&outline, 5), Some(1));
+        assert_eq!(current_section(&outline[1..], 0), None);
+    }
+
+    #[test]
+    fn test_match_ranges() {
+        assert!(match_ranges("", "x").is_empty());
+        assert!(match_ranges("abc", "").is_empty());
+        assert!(match_ranges("ab", "abc").is_empty());
+        assert_eq!(match_ranges("aaaa", "aa"), [(0, 2), (2, 4)]);
+        // At the very end of the text
+        assert_eq!(match_ranges("abc", "c"), [(2, 3)]);
+        // Smartcase
+        assert_eq!(match_ranges("Foo foo", "foo"), [(0, 3), (4, 7)]);
+        assert_eq!(match_ranges("Foo foo", "Foo"), [(0, 3)]);
+        // Ranges are in chars, not bytes
+        assert_eq!(match_ranges("Über über", "über"), [(0, 4), (5, 9)]);
+    }
+
+    #[test]
+    fn test_find_match_wraps() {
+        let buf = lines(&["foo", "", "bar foo"]);
+        assert_eq!(find_match(&buf, "foo", 0, 0, false), Some(((2, 4), false)));
+        assert_eq!(find_match(&buf, "foo", 2, 4, false), Some(((0, 0), true)));
+        assert_eq!(find_match(&buf, "foo", 2, 4, true), Some(((0, 0), false)));
+        assert_eq!(find_match(&buf, "foo", 0, 0, true), Some(((2, 4), true)));
+        assert_eq!(find_match(&buf, "nope", 0, 0, false), None);
+        assert_eq!(find_match(&lines(&[""]), "x", 0, 0, false), None);
+    }
+}
@@ -0,0 +1,348 @@
+//! The `:` command line: the command registry, argument parsing and tab completion.
+//! The `?` help overlay is generated from the same registry.
+
+/// What the arguments of a command complete to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Completion {
+    None,
+    Tags,
+    SortKeys,
+    Path,
+}
+
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub args: &'static str,
+    /// Normal-mode key that does the same thing, if any
+    pub key: &'static str,
+    pub help: &'static str,
+    pub completion: Completion,
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "open",
+        aliases: &["o"],
+        args: "",
+        key: "Enter",
+        help: "Open the selected note in $EDITOR",
+        completion: Completion::None,
+    },
+    CommandSpec {
+        name: "edit",
+        aliases: &["e"],
+        args: "",
+        key: "e",
+        help: "Edit the selected note in the built-in editor",
+        completion: Completion::None,
+    },
+    CommandSpec {
+        name: "add",
+        aliases: &["a", "new"],
+        args: "[title] [tags...]",
+        key: "c",
+        help: "Create a note (prompts for a title if none is given)",
+        completion: Completion::Tags,
+    },
+    CommandSpec {
+        name: "rename",
+        aliases: &["mv"],
+        args: "<title>",
+        key: "",
+        help: "Rename the selected note",
+        completion: Completion::None,
+    },
+    CommandSpec {
+        name: "tag",
+        aliases: &["t"],
+        args: "[+tag] [-tag]...",
+        key: "",
+        help: "Add (+tag or tag) and remove (-tag) tags; no arguments opens the tag editor",
+        completion: Completion::Tags,
+    },
+    CommandSpec {
+        name: "pin",
+        aliases: &[],
+        args: "",
+        key: "p",
+        help: "Pin or unpin the selected note",
+        completion: Completion::None,
+    },
+    CommandSpec {
+        name: "archive",
+        aliases: &[],
+        args: "",
+        key: "A",
+        help: "Archive or unarchive the selected note",
+        completion: Completion::None,
+    },
+    CommandSpec {
+        name: "delete",
+        aliases: &["d"],
+        args: "",
+        key: "",
+        help: "Move the selected note to the trash",
+        completion: Completion::None,
+    },
+    CommandSpec {
+        name: "undo",
+        aliases: &["u"],
+        args: "",
+        key: "u",
+        help: "Undo the last delete, edit, tag change, rename, merge or extract",
+        completion: Completion::None,
+    },
+    CommandSpec {
+        name: "summarize",
+        aliases: &["s"],
+        args: "",
+        key: "",
+        help: "Summarize the selected note (requires API key)",
+        completion: Completion::None,
+    },
+    CommandSpec {
+        name: "search",
+        aliases: &["find"],
+        args: "<query>",
+        key: "/",
+        help: "Filter the note list",
+        completion: Completion::None,
+    },
+    CommandSpec {
+        name: "sort",
+        aliases: &[],
+        args: "<key> [reverse]",
+        key: "S",
+        help: "Sort the note list by updated, created, title, size or tag-count",
+        completion: Completion::SortKeys,
+    },
+    CommandSpec {
+        name: "export",
+        aliases: &[],
+        args: "<path>",
+        key: "",
+        help: "Export all notes to a JSON file",
+        completion: Completion::Path,
+    },
+    CommandSpec {
+        name: "inbox",
+        aliases: &["in"],
+        args: "",
+        key: "I",
+        help: "Triage notes captured with `scrap capture`",
+        completion: Completion::None,
+    },
+    CommandSpec {
+        name: "remind",
+        aliases: &[],
+        args: "<when|off>",
+        key: "D",
+        help: "Remind about the selected note at a time like 2h, tomorrow 9:00 or 2026-10-20; off (or D) dismisses",
+        completion: Completion::None,
+    },
+    CommandSpec {
+        name: "lock",
+        aliases: &[],
+        args: "",
+        key: "",
+        help: "Forget the passphrase, locking encrypted notes again",
+        completion: Completion::None,
+    },
+    CommandSpec {
+        name: "help",
+        aliases: &["h"],
+        args: "",
+        key: "?",
+        help: "Show this help",
+        completion: Completion::None,
+    },
+    CommandSpec {
+        name: "quit",
+        aliases: &["q"],
+        args: "",
+        key: "q",
+        help: "Quit",
+        completion: Completion::None,
+    },
+];
+
+/// Look up a command by name or alias.
+pub fn find(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS
+        .iter()
+        .find(|c| c.name == name || c.aliases.contains(&name))
+}
+
+/// One-line usage, e.g. `rename <title>`.
+pub fn usage(spec: &CommandSpec) -> String {
+    if spec.args.is_empty() {
+        spec.name.to_string()
+    } else {
+        format!("{} {}", spec.name, spec.args)
+    }
+}
+
+/// Split a command line into the command name and the rest of the line.
+pub fn parse(line: &str) -> (&str, &str) {
+    let line = line.trim();
+    match line.split_once(char::is_whitespace) {
+        Some((name, rest)) => (name, rest.trim()),
+        None => (line, ""),
+    }
+}
+
+/// Candidates for the last word of `line`. `tags` are the known tag names.
+/// Each candidate is the full line with the last word completed.
+pub fn complete(line: &str, tags: &[String]) -> Vec<String> {
+    let (head, word) = match line.rfind(' ') {
+        Some(i) => (&line[..=i], &line[i + 1..]),
+        None => ("", line),
+    };
+
+    let words: Vec<String> = if head.is_empty() {
+        COMMANDS
+            .iter()
+            .map(|c| c.name.to_string())
+            .filter(|n| n.starts_with(word))
+            .collect()
+    } else {
+        let (name, _) = parse(head);
+        let Some(spec) = find(name) else {
+            return Vec::new();
+        };
+        match spec.completion {
+            Completion::None => Vec::new(),
+            Completion::Tags => {
+                let (sign, partial) = match word.chars().next() {
+                    Some(c @ ('+' | '-')) if spec.name == "tag" => (c.to_string(), &word[1..]),
+                    _ => (String::new(), word),
+                };
+                tags.iter()
+                    .filter(|t| t.starts_with(partial))
+                    .map(|t| format!("{}{}", sign, t))
+                    .collect()
+            }
+            Completion::SortKeys => ["updated", "created", "title", "size", "tag-count", "reverse"]
+                .iter()
+                .filter(|k| k.starts_with(word))
+                .map(|k| k.to_string())
+                .collect(),
+            Completion::Path => complete_path(word),
+        }
+    };
+
+    words.into_iter().map(|w| format!("{}{}", head, w)).collect()
+}
+
+/// Files and directories starting with `partial`. Directories get a trailing `/`.
+fn complete_path(partial: &str) -> Vec<String> {
+    let expanded = expand_home(partial);
+    let (dir, prefix) = match expanded.rfind('/') {
+        Some(i) => (expanded[..=i].to_string(), expanded[i + 1..].to_string()),
+        None => ("./".to_string(), expanded.clone()),
+    };
+    let typed_dir = match partial.rfind('/') {
+        Some(i) => &partial[..=i],
+        None => "",
+    };
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    let mut matches: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            if !name.starts_with(&prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let suffix = if e.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", typed_dir, name, suffix))
+        })
+        .collect();
+    matches.sort();
+    matches
+}
+
+/// Expand a leading `~/` to the home directory.
+pub fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().to_string(),
+        _ => path.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags() -> Vec<String> {
+        ["work", "weekly", "home"].iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("rename  new title "), ("rename", "new title"));
+        assert_eq!(parse("  quit"), ("quit", ""));
+        assert_eq!(parse("sort\ttitle reverse"), ("sort", "title reverse"));
+        assert_eq!(parse(""), ("", ""));
+    }
+
+    #[test]
+    fn test_find_by_alias() {
+        assert_eq!(find("mv").map(|c| c.name), Some("rename"));
+        assert_eq!(find("new").map(|c| c.name), Some("add"));
+        assert!(find("nope").is_none());
+    }
+
+    #[test]
+    fn test_complete_command_names() {
+        assert_eq!(complete("re", &[]), ["rename", "remind"]);
+        assert_eq!(complete("h", &[]), ["help"]);
+        assert!(complete("zz", &[]).is_empty());
+        assert_eq!(complete("", &[]).len(), COMMANDS.len());
+        // Unknown commands and commands without arguments complete nothing
+        assert!(complete("nope w", &tags()).is_empty());
+        assert!(complete("pin w", &tags()).is_empty());
+    }
+
+    #[test]
+    fn test_complete_tags() {
+        assert_eq!(complete("tag w", &tags()), ["tag work", "tag weekly"]);
+        assert_eq!(complete("t +wo", &tags()), ["t +work"]);
+        assert_eq!(complete("tag +work -h", &tags()), ["tag +work -home"]);
+        assert_eq!(complete("tag -", &tags()), ["tag -work", "tag -weekly", "tag -home"]);
+        // Only `tag` takes signs
+        assert!(complete("add note +w", &tags()).is_empty());
+        assert_eq!(complete("add note h", &tags()), ["add note home"]);
+    }
+
+    #[test]
+    fn test_complete_sort_keys() {
+        assert_eq!(complete("sort t", &[]), ["sort title", "sort tag-count"]);
+        assert_eq!(complete("sort title r", &[]), ["sort title reverse"]);
+        assert_eq!(complete("sort ", &[]).len(), 6);
+    }
+
+    #[test]
+    fn test_complete_path() {
+        let dir = std::env::temp_dir().join(format!("scrap-palette-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("notes")).unwrap();
+        std::fs::write(dir.join("notes.json"), "").unwrap();
+        std::fs::write(dir.join(".hidden"), "").unwrap();
+        let typed = format!("{}/", dir.display());
+
+        // Directories get a slash; dot files only show when asked for
+        let both = [format!("{}notes.json", typed), format!("{}notes/", typed)];
+        assert_eq!(complete_path(&format!("{}no", typed)), both);
+        assert_eq!(complete_path(&typed), both);
+        assert_eq!(complete_path(&format!("{}.h", typed)), [format!("{}.hidden", typed)]);
+        assert!(complete_path(&format!("{}missing/x", typed)).is_empty());
+        assert_eq!(
+            complete(&format!("export {}notes.", typed), &[]),
+            [format!("export {}notes.json", typed)]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
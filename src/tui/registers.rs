@@ -0,0 +1,62 @@
+//! Vim-style registers for yanked and deleted lines. `"` is the unnamed
+//! register that every yank and delete also lands in, `a`-`z` are named
+//! registers (`A`-`Z` append to them), and `+` is the system clipboard.
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+use crate::clipboard;
+
+pub const UNNAMED: char = '"';
+pub const CLIPBOARD: char = '+';
+
+#[derive(Default)]
+pub struct Registers {
+    contents: BTreeMap<char, String>,
+}
+
+impl Registers {
+    pub fn is_valid(name: char) -> bool {
+        name == UNNAMED || name == CLIPBOARD || name.is_ascii_alphabetic()
+    }
+
+    /// Store lines in register `name` and in the unnamed register. The unnamed
+    /// register is set even if writing the clipboard fails.
+    pub fn store(&mut self, name: char, text: &str) -> Result<()> {
+        let unnamed = if name.is_ascii_uppercase() {
+            let register = self.contents.entry(name.to_ascii_lowercase()).or_default();
+            if !register.is_empty() {
+                register.push('\n');
+            }
+            register.push_str(text);
+            register.clone()
+        } else {
+            if name.is_ascii_lowercase() {
+                self.contents.insert(name, text.to_string());
+            }
+            text.to_string()
+        };
+        self.contents.insert(UNNAMED, unnamed);
+        if name == CLIPBOARD {
+            clipboard::copy(text)?;
+        }
+        Ok(())
+    }
+
+    /// The lines in register `name`, or None if it's empty.
+    pub fn get(&self, name: char) -> Result<Option<String>> {
+        if name == CLIPBOARD {
+            // Text copied from other programs usually ends in a newline, which
+            // isn't an extra empty line
+            let text = clipboard::paste()?;
+            let text = text.strip_suffix('\n').unwrap_or(&text);
+            return Ok(Some(text.to_string()).filter(|t| !t.is_empty()));
+        }
+        Ok(self.contents.get(&name.to_ascii_lowercase()).cloned())
+    }
+}
+
+/// The lines of register text.
+pub fn lines(text: &str) -> std::str::Split<'_, char> {
+    text.split('\n')
+}
@@ -0,0 +1,264 @@
+//! Colors for the TUI. Drawing code asks the theme for a slot (`accent`,
+//! `muted`, ...) instead of naming colors, so the built-in palettes, user
+//! themes from the config and `NO_COLOR` all apply everywhere at once.
+
+use anyhow::{bail, Result};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use std::collections::BTreeMap;
+
+use crate::config::Config;
+
+pub const BUILTIN_THEMES: &[&str] = &["dark", "light", "high-contrast"];
+
+/// Config key naming the built-in theme a user theme starts from.
+const BASE_KEY: &str = "base";
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// Focused borders, the selected note, headings
+    pub accent: Color,
+    /// Tag panel, searches and their matches, pinned marker, key hints
+    pub highlight: Color,
+    /// Active tag filters, the editor, add and edit modes
+    pub success: Color,
+    /// Delete confirmation, due reminders
+    pub danger: Color,
+    /// Command and visual modes, action names in help
+    pub special: Color,
+    /// Secondary text: metadata, archived notes, hints
+    pub muted: Color,
+    /// Text drawn on top of one of the colors above
+    pub on_accent: Color,
+    /// Background of the cursor line in the preview, split pane and editor
+    pub cursor_line: Color,
+    /// Background of visually selected lines
+    pub selection: Color,
+    /// Background of the cursor line inside a visual selection
+    pub selection_cursor: Color,
+    /// Draw with text attributes only, for `NO_COLOR`
+    monochrome: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    fn dark() -> Self {
+        Self {
+            accent: Color::Cyan,
+            highlight: Color::Yellow,
+            success: Color::Green,
+            danger: Color::Red,
+            special: Color::Magenta,
+            muted: Color::DarkGray,
+            on_accent: Color::Black,
+            cursor_line: Color::Rgb(40, 40, 60),
+            selection: Color::Rgb(50, 50, 100),
+            selection_cursor: Color::Rgb(80, 80, 140),
+            monochrome: false,
+        }
+    }
+
+    fn light() -> Self {
+        Self {
+            accent: Color::Blue,
+            highlight: Color::Rgb(175, 95, 0),
+            success: Color::Rgb(0, 125, 0),
+            danger: Color::Rgb(190, 0, 0),
+            special: Color::Rgb(135, 0, 175),
+            muted: Color::Rgb(120, 120, 120),
+            on_accent: Color::White,
+            cursor_line: Color::Rgb(228, 232, 242),
+            selection: Color::Rgb(205, 215, 245),
+            selection_cursor: Color::Rgb(170, 190, 240),
+            monochrome: false,
+        }
+    }
+
+    fn high_contrast() -> Self {
+        Self {
+            accent: Color::LightCyan,
+            highlight: Color::LightYellow,
+            success: Color::LightGreen,
+            danger: Color::LightRed,
+            special: Color::LightMagenta,
+            muted: Color::Gray,
+            on_accent: Color::Black,
+            cursor_line: Color::Rgb(0, 0, 110),
+            selection: Color::Rgb(0, 0, 170),
+            selection_cursor: Color::Rgb(0, 0, 230),
+            monochrome: false,
+        }
+    }
+
+    fn monochrome() -> Self {
+        Self {
+            monochrome: true,
+            ..Self::dark()
+        }
+    }
+
+    fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            _ => None,
+        }
+    }
+
+    fn slot_mut(&mut self, name: &str) -> Option<&mut Color> {
+        match name {
+            "accent" => Some(&mut self.accent),
+            "highlight" => Some(&mut self.highlight),
+            "success" => Some(&mut self.success),
+            "danger" => Some(&mut self.danger),
+            "special" => Some(&mut self.special),
+            "muted" => Some(&mut self.muted),
+            "on_accent" => Some(&mut self.on_accent),
+            "cursor_line" => Some(&mut self.cursor_line),
+            "selection" => Some(&mut self.selection),
+            "selection_cursor" => Some(&mut self.selection_cursor),
+            _ => None,
+        }
+    }
+
+    /// The theme named by the config's `theme` key, either built in or defined
+    /// under `themes`. Every user theme is checked, not just the selected one,
+    /// and all problems are reported at once. `NO_COLOR` wins over any theme.
+    pub fn load(config: &Config) -> Result<Self> {
+        let mut errors = Vec::new();
+        let mut user_themes = BTreeMap::new();
+        for (name, slots) in &config.themes {
+            match Self::from_slots(slots) {
+                Ok(theme) => {
+                    user_themes.insert(name.as_str(), theme);
+                }
+                Err(problems) => errors.extend(problems.into_iter().map(|p| format!("{}: {}", name, p))),
+            }
+        }
+
+        let selected = user_themes
+            .remove(config.theme.as_str())
+            .or_else(|| Self::builtin(&config.theme));
+        if selected.is_none() && !config.themes.contains_key(&config.theme) {
+            let mut names: Vec<&str> = BUILTIN_THEMES.to_vec();
+            names.extend(config.themes.keys().map(String::as_str));
+            errors.push(format!("unknown theme '{}' (expected one of: {})", config.theme, names.join(", ")));
+        }
+
+        if !errors.is_empty() {
+            bail!("Invalid theme in config:\n  {}", errors.join("\n  "));
+        }
+        if no_color() {
+            return Ok(Self::monochrome());
+        }
+        Ok(selected.unwrap_or_default())
+    }
+
+    /// A user theme: a built-in `base` with some slots replaced.
+    fn from_slots(slots: &BTreeMap<String, String>) -> std::result::Result<Self, Vec<String>> {
+        let mut errors = Vec::new();
+        let base = slots.get(BASE_KEY).map(String::as_str).unwrap_or("dark");
+        let mut theme = Self::builtin(base).unwrap_or_else(|| {
+            errors.push(format!("unknown base '{}' (expected one of: {})", base, BUILTIN_THEMES.join(", ")));
+            Self::dark()
+        });
+        for (slot, value) in slots.iter().filter(|(k, _)| *k != BASE_KEY) {
+            let color = value.parse::<Color>();
+            match (theme.slot_mut(slot), color) {
+                (Some(target), Ok(color)) => *target = color,
+                (Some(_), Err(_)) => errors.push(format!(
+                    "{}: '{}' is not a color (use a name like \"blue\", \"#rrggbb\" or 0-255)",
+                    slot, value
+                )),
+                (None, _) => errors.push(format!("unknown color '{}'", slot)),
+            }
+        }
+        if errors.is_empty() { Ok(theme) } else { Err(errors) }
+    }
+
+    /// Text in one of the theme colors.
+    pub fn fg(&self, color: Color) -> Style {
+        if self.monochrome {
+            Style::default()
+        } else {
+            Style::default().fg(color)
+        }
+    }
+
+    /// Secondary text.
+    pub fn muted(&self) -> Style {
+        if self.monochrome {
+            Style::default().add_modifier(Modifier::DIM)
+        } else {
+            Style::default().fg(self.muted)
+        }
+    }
+
+    /// Text on a colored background: selected rows, mode badges, active tabs.
+    pub fn badge(&self, color: Color) -> Style {
+        if self.monochrome {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default().fg(self.on_accent).bg(color)
+        }
+    }
+
+    /// Matches of the preview search.
+    pub fn search_match(&self) -> Style {
+        self.badge(self.highlight)
+    }
+
+    /// Matched characters in the note list search. Bold and underlined so they
+    /// still show on the highlighted row.
+    pub fn search_hit(&self) -> Style {
+        self.fg(self.highlight)
+            .add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+    }
+
+    pub fn cursor_line(&self) -> Style {
+        self.line(self.cursor_line, Modifier::UNDERLINED)
+    }
+
+    pub fn selection(&self) -> Style {
+        self.line(self.selection, Modifier::REVERSED)
+    }
+
+    pub fn selection_cursor(&self) -> Style {
+        self.line(self.selection_cursor, Modifier::REVERSED | Modifier::BOLD)
+    }
+
+    fn line(&self, bg: Color, fallback: Modifier) -> Style {
+        if self.monochrome {
+            Style::default().add_modifier(fallback)
+        } else {
+            Style::default().bg(bg)
+        }
+    }
+
+    /// Render markdown, dropping its colors when drawing without color.
+    pub fn markdown(&self, text: &str) -> Vec<Line<'static>> {
+        let mut lines = tui_md::render(text);
+        if self.monochrome {
+            for line in &mut lines {
+                line.style.fg = None;
+                line.style.bg = None;
+                for span in &mut line.spans {
+                    span.style.fg = None;
+                    span.style.bg = None;
+                }
+            }
+        }
+        lines
+    }
+}
+
+/// `NO_COLOR` set to anything non-empty turns colors off (https://no-color.org).
+fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
@@ -1,11 +1,23 @@
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
+    widgets::{
+        Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, TableState,
+        Wrap,
+    },
 };
 
-use super::{App, Focus, Mode, PreviewTab};
+use super::fuzzy::NoteMatch;
+use super::theme::Theme;
+use super::{motions, palette, App, Focus, Mode, PreviewTab};
+use crate::attachments;
+use crate::config::NoteColumn;
+use crate::db::NoteEntry;
+use crate::markdown;
+use crate::utils;
 
 pub fn draw(f: &mut Frame, app: &mut App) {
+    // Panes that aren't drawn this frame don't take mouse input
+    app.areas = Default::default();
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Min(1), Constraint::Length(2)])
@@ -13,8 +25,10 @@ pub fn draw(f: &mut Frame, app: &mut App) {
 
     let main_chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+        .constraints([Constraint::Percentage(app.left_percent), Constraint::Percentage(100 - app.left_percent)])
         .split(chunks[0]);
+    app.areas.main = chunks[0];
+    app.areas.divider = main_chunks[1].x;
 
     let left_chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -23,63 +37,190 @@ pub fn draw(f: &mut Frame, app: &mut App) {
 
     draw_note_list(f, app, left_chunks[0]);
     draw_tag_panel(f, app, left_chunks[1]);
-    draw_preview(f, app, main_chunks[1]);
+    draw_preview_area(f, app, main_chunks[1]);
     draw_status_bar(f, app, chunks[1]);
 
     match app.mode {
         Mode::Search => draw_search_popup(f, app),
-        Mode::AddNoteName | Mode::AddNoteTags | Mode::EditTagsAdd | Mode::EditTagsRemove => {
+        Mode::AddNoteName
+        | Mode::AddNoteTags
+        | Mode::EditTagsAdd
+        | Mode::EditTagsRemove
+        | Mode::InboxTitle
+        | Mode::InboxTags
+        | Mode::InboxMerge
+        | Mode::ExtractNote
+        | Mode::Unlock => {
             draw_input_modal(f, app);
         }
+        Mode::ConfirmDelete => draw_confirm_delete(f, app),
+        Mode::ResolveConflict => draw_resolve_conflict(f, app),
+        Mode::Recover => draw_recover(f, app),
+        Mode::Help => draw_help(f, app),
         _ => {}
     }
 }
 
-fn draw_note_list(f: &mut Frame, app: &App, area: Rect) {
-    let items: Vec<ListItem> = app
-        .filtered_notes
-        .iter()
-        .map(|&idx| {
-            let note = &app.notes[idx];
-            ListItem::new(note.title.clone())
-        })
-        .collect();
-
-    let title = if app.active_tag_filters.is_empty() {
+fn draw_note_list(f: &mut Frame, app: &mut App, area: Rect) {
+    let mut title = if app.active_tag_filters.is_empty() {
         format!("Notes ({})", app.filtered_notes.len())
     } else {
         format!("Notes [{}] ({})", app.active_tag_filters.join(", "), app.filtered_notes.len())
     };
+    if app.show_archived {
+        title.push_str(" +archived");
+    }
+    let due = app.filtered_notes.iter().filter(|&&i| app.notes[i].reminder_due()).count();
+    if due > 0 {
+        title.push_str(&format!(" · {} due", due));
+    }
+    let direction = if app.sort_reverse { "↑" } else { "↓" };
+    title.push_str(&format!(" · {} {}", app.sort_key.label(), direction));
 
-    let border_style = if app.focus == Focus::NoteList && (app.mode == Mode::Normal || app.mode == Mode::TagBrowse) {
-        Style::default().fg(Color::Cyan)
+    let theme = &app.theme;
+    let border_style = if app.focus == Focus::NoteList && matches!(app.mode, Mode::Normal | Mode::TagBrowse | Mode::Inbox) {
+        theme.fg(theme.accent)
     } else {
         Style::default()
     };
 
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_style(border_style);
+    let inner = block.inner(area);
+    let highlight_style = theme.badge(theme.accent).add_modifier(Modifier::BOLD);
+    let selected = if app.filtered_notes.is_empty() {
+        None
+    } else {
+        Some(app.selected)
+    };
+
+    if app.show_details && !app.list_columns.is_empty() {
+        let offset = draw_note_table(f, app, area, block, highlight_style, selected);
+        app.areas.note_list = inner;
+        app.areas.note_list_offset = offset;
+        return;
+    }
+
+    let items: Vec<ListItem> = app
+        .filtered_notes
+        .iter()
+        .enumerate()
+        .map(|(pos, &idx)| {
+            let m = app.search_matches.get(pos);
+            let mut lines = vec![note_title_line(theme, &app.notes[idx], m)];
+            lines.extend(snippet_line(app, m));
+            ListItem::new(lines)
+        })
+        .collect();
+
     let list = List::new(items)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title(title)
-                .border_style(border_style),
-        )
-        .highlight_style(
-            Style::default()
-                .fg(Color::Black)
-                .bg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        );
+        .block(block)
+        .highlight_style(highlight_style);
 
     let mut state = ListState::default();
-    if !app.filtered_notes.is_empty() {
-        state.select(Some(app.selected));
-    }
+    state.select(selected);
 
     f.render_stateful_widget(list, area, &mut state);
+    app.areas.note_list = inner;
+    app.areas.note_list_offset = state.offset();
+}
+
+/// Title with pinned marker and archived styling.
+fn note_title_line(theme: &Theme, note: &NoteEntry, search_match: Option<&NoteMatch>) -> Line<'static> {
+    let title_style = if note.archived {
+        theme.muted()
+    } else {
+        Style::default()
+    };
+    let mut title = Line::from(Span::styled(note.title.clone(), title_style));
+    if let Some(m) = search_match {
+        let ranges: Vec<(usize, usize)> = m.title_positions.iter().map(|&p| (p, p + 1)).collect();
+        title = restyle_ranges(title, &ranges, |s| s.patch(theme.search_hit()));
+    }
+
+    let mut spans = Vec::new();
+    if note.reminder_due() {
+        spans.push(Span::styled("◷ ", theme.fg(theme.danger)));
+    }
+    if note.pinned {
+        spans.push(Span::styled("★ ", theme.fg(theme.highlight)));
+    }
+    spans.extend(title.spans);
+    if note.archived {
+        spans.push(Span::styled(" (archived)", title_style));
+    }
+    Line::from(spans)
+}
+
+/// The body line that matched, shown under each result while typing a search.
+fn snippet_line(app: &App, search_match: Option<&NoteMatch>) -> Option<Line<'static>> {
+    if app.mode != Mode::Search {
+        return None;
+    }
+    let (text, range) = search_match?.snippet.as_ref()?;
+    let line = Line::from(Span::styled(format!("  {}", text), app.theme.muted()));
+    Some(restyle_ranges(line, &[(range.0 + 2, range.1 + 2)], |s| s.patch(app.theme.search_hit())))
 }
 
-fn draw_tag_panel(f: &mut Frame, app: &App, area: Rect) {
+/// Multi-column note list showing the configured metadata columns after the title.
+/// Returns the index of the first visible row.
+fn draw_note_table(
+    f: &mut Frame,
+    app: &App,
+    area: Rect,
+    block: Block,
+    highlight_style: Style,
+    selected: Option<usize>,
+) -> usize {
+    let dim = app.theme.muted();
+    let rows: Vec<Row> = app
+        .filtered_notes
+        .iter()
+        .enumerate()
+        .map(|(pos, &idx)| {
+            let note = &app.notes[idx];
+            let m = app.search_matches.get(pos);
+            let mut title = vec![note_title_line(&app.theme, note, m)];
+            title.extend(snippet_line(app, m));
+            let height = title.len() as u16;
+            let mut cells = vec![Cell::from(Text::from(title))];
+            for column in &app.list_columns {
+                let text = match column {
+                    NoteColumn::Tags => note.tags.join(","),
+                    NoteColumn::Updated => utils::relative_time(&note.updated_at),
+                    NoteColumn::Words if note.is_locked() => String::new(),
+                    NoteColumn::Words => format!("{}w", note.word_count()),
+                };
+                cells.push(Cell::from(Span::styled(text, dim)));
+            }
+            Row::new(cells).height(height)
+        })
+        .collect();
+
+    let mut widths = vec![Constraint::Min(10)];
+    for column in &app.list_columns {
+        widths.push(match column {
+            NoteColumn::Tags => Constraint::Max(16),
+            NoteColumn::Updated => Constraint::Length(8),
+            NoteColumn::Words => Constraint::Length(6),
+        });
+    }
+
+    let table = Table::new(rows, widths)
+        .block(block)
+        .row_highlight_style(highlight_style);
+
+    let mut state = TableState::default();
+    state.select(selected);
+
+    f.render_stateful_widget(table, area, &mut state);
+    state.offset()
+}
+
+fn draw_tag_panel(f: &mut Frame, app: &mut App, area: Rect) {
+    let theme = &app.theme;
     let items: Vec<ListItem> = app
         .visible_tags
         .iter()
@@ -87,11 +228,7 @@ fn draw_tag_panel(f: &mut Frame, app: &App, area: Rect) {
             let active = app.active_tag_filters.contains(&tag.name);
             let text = format!("{} ({})", tag.name, tag.count);
             if active {
-                ListItem::new(text).style(
-                    Style::default()
-                        .fg(Color::Green)
-                        .add_modifier(Modifier::BOLD),
-                )
+                ListItem::new(text).style(theme.fg(theme.success).add_modifier(Modifier::BOLD))
             } else {
                 ListItem::new(text)
             }
@@ -99,24 +236,19 @@ fn draw_tag_panel(f: &mut Frame, app: &App, area: Rect) {
         .collect();
 
     let border_style = if app.focus == Focus::TagPanel {
-        Style::default().fg(Color::Yellow)
+        theme.fg(theme.highlight)
     } else {
         Style::default()
     };
 
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Tags")
+        .border_style(border_style);
+    let inner = block.inner(area);
     let list = List::new(items)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Tags")
-                .border_style(border_style),
-        )
-        .highlight_style(
-            Style::default()
-                .fg(Color::Black)
-                .bg(Color::Yellow)
-                .add_modifier(Modifier::BOLD),
-        );
+        .block(block)
+        .highlight_style(theme.badge(theme.highlight).add_modifier(Modifier::BOLD));
 
     let mut state = ListState::default();
     if !app.visible_tags.is_empty() && app.focus == Focus::TagPanel {
@@ -124,11 +256,18 @@ fn draw_tag_panel(f: &mut Frame, app: &App, area: Rect) {
     }
 
     f.render_stateful_widget(list, area, &mut state);
+    app.areas.tag_panel = inner;
+    app.areas.tag_offset = state.offset();
 }
 
 fn draw_preview(f: &mut Frame, app: &mut App, area: Rect) {
+    if app.editor.is_some() {
+        draw_editor(f, app, area);
+        return;
+    }
+
     let note_title = app
-        .selected_note()
+        .preview_note()
         .map(|n| n.title.clone())
         .unwrap_or_default();
 
@@ -138,6 +277,7 @@ fn draw_preview(f: &mut Frame, app: &mut App, area: Rect) {
     };
 
     let is_focused = app.focus == Focus::Preview;
+    let theme = app.theme.clone();
 
     // Decide what content to show based on preview_tab
     let (title, lines, border_style) = match app.preview_tab {
@@ -148,28 +288,36 @@ fn draw_preview(f: &mut Frame, app: &mut App, area: Rect) {
                 format!("{} [{}]", note_title, tab_label)
             };
             let lines = match &app.summary_content {
-                Some(content) => tui_md::render(content),
+                Some(content) => theme.markdown(content),
                 None => vec![Line::from("Generating summary...")],
             };
             let border = if is_focused {
-                Style::default().fg(Color::Cyan)
+                theme.fg(theme.accent)
             } else if app.summary_stale {
-                Style::default().fg(Color::Yellow)
+                theme.fg(theme.highlight)
             } else {
-                Style::default().fg(Color::Green)
+                theme.fg(theme.success)
             };
             (title, lines, border)
         }
         _ => {
-            let (title, lines) = match app.selected_note() {
+            let (title, lines) = match app.preview_note() {
+                Some(note) if note.is_locked() => {
+                    let lines = vec![
+                        Line::from(Span::styled("This note is encrypted.", theme.fg(theme.danger))),
+                        Line::from(""),
+                        Line::from(Span::styled("Press Enter to unlock it.", theme.muted())),
+                    ];
+                    (format!("{} [{}]", note.title, tab_label), lines)
+                }
                 Some(note) => {
-                    let rendered = tui_md::render(&note.note);
+                    let rendered = theme.markdown(&attachments::placeholders(&note.note, &app.attachments));
                     (format!("{} [{}]", note.title, tab_label), rendered)
                 }
                 None => ("Preview".to_string(), vec![Line::from("No note selected")]),
             };
             let border = if is_focused {
-                Style::default().fg(Color::Cyan)
+                theme.fg(theme.accent)
             } else {
                 Style::default()
             };
@@ -177,9 +325,43 @@ fn draw_preview(f: &mut Frame, app: &mut App, area: Rect) {
         }
     };
 
-    // Track viewport height for cursor navigation (inner area minus borders)
-    let inner_height = area.height.saturating_sub(2);
-    app.preview_content_height = inner_height;
+    let showing_summary_tab = app.preview_tab == PreviewTab::Summary
+        && (app.summary_content.is_some() || app.showing_summary);
+    let header = if app.show_preview_metadata && !showing_summary_tab {
+        app.preview_note().map(|n| metadata_header(&theme, n, area.width.saturating_sub(2)))
+    } else {
+        None
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_style(border_style);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let content_area = match header {
+        Some(header) => {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(header.len() as u16), Constraint::Min(0)])
+                .split(inner);
+            f.render_widget(Paragraph::new(header), chunks[0]);
+            chunks[1]
+        }
+        None => inner,
+    };
+
+    // Track viewport height for cursor navigation
+    app.preview_content_height = content_area.height;
+    app.areas.preview = content_area;
+
+    let search = if app.mode == Mode::PreviewSearch {
+        Some(app.preview_search_input.as_str())
+    } else {
+        app.preview_search.as_deref()
+    };
+    let match_style = theme.search_match();
 
     // Apply cursor and selection highlights when preview is focused
     let styled_lines: Vec<Line> = if is_focused {
@@ -187,6 +369,14 @@ fn draw_preview(f: &mut Frame, app: &mut App, area: Rect) {
             .into_iter()
             .enumerate()
             .map(|(i, line)| {
+                let line = match search {
+                    Some(query) => {
+                        let text: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+                        let ranges = motions::match_ranges(&text, query);
+                        restyle_ranges(line, &ranges, |s| s.patch(match_style))
+                    }
+                    None => line,
+                };
                 let is_cursor = i == app.preview_cursor;
                 let is_selected = app.visual_anchor.is_some_and(|anchor| {
                     let start = anchor.min(app.preview_cursor);
@@ -196,13 +386,15 @@ fn draw_preview(f: &mut Frame, app: &mut App, area: Rect) {
 
                 if is_selected && is_cursor {
                     // Cursor within selection — brighter highlight
-                    apply_line_bg(line, Color::Rgb(80, 80, 140))
+                    apply_line_style(line, theme.selection_cursor())
                 } else if is_selected {
                     // Selected but not cursor — muted highlight
-                    apply_line_bg(line, Color::Rgb(50, 50, 100))
+                    apply_line_style(line, theme.selection())
                 } else if is_cursor {
-                    // Cursor line (no selection) — subtle highlight
-                    apply_line_bg(line, Color::Rgb(40, 40, 60))
+                    // Cursor line (no selection) — subtle highlight, with the column cursor
+                    let col = app.preview_col;
+                    let line = restyle_ranges(line, &[(col, col + 1)], |s| s.add_modifier(Modifier::REVERSED));
+                    apply_line_style(line, theme.cursor_line())
                 } else {
                     line
                 }
@@ -213,58 +405,371 @@ fn draw_preview(f: &mut Frame, app: &mut App, area: Rect) {
     };
 
     let paragraph = Paragraph::new(styled_lines)
+        .wrap(Wrap { trim: false })
+        .scroll((app.preview_scroll, 0));
+
+    f.render_widget(paragraph, content_area);
+}
+
+/// Tab bar, main preview, optional split pane and optional outline.
+fn draw_preview_area(f: &mut Frame, app: &mut App, area: Rect) {
+    let mut area = area;
+    if !app.tabs.is_empty() {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(area);
+        draw_tab_bar(f, app, chunks[0]);
+        area = chunks[1];
+    }
+    if app.show_outline {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(20), Constraint::Length(30)])
+            .split(area);
+        draw_outline(f, app, chunks[1]);
+        area = chunks[0];
+    }
+    match app.split.as_ref().map(|s| s.vertical) {
+        Some(vertical) => {
+            let direction = if vertical { Direction::Horizontal } else { Direction::Vertical };
+            let chunks = Layout::default()
+                .direction(direction)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(area);
+            draw_preview(f, app, chunks[0]);
+            draw_split(f, app, chunks[1]);
+        }
+        None => draw_preview(f, app, area),
+    }
+}
+
+/// One label per open tab, after the list preview which is always first.
+fn draw_tab_bar(f: &mut Frame, app: &App, area: Rect) {
+    let active = app.theme.badge(app.theme.accent).add_modifier(Modifier::BOLD);
+    let inactive = app.theme.muted();
+    let style = |is_active: bool| if is_active { active } else { inactive };
+
+    let mut spans = vec![Span::styled(" list ", style(app.active_tab.is_none()))];
+    for (i, tab) in app.tabs.iter().enumerate() {
+        let title = app.note_by_id(tab.note_id).map(|n| n.title.as_str()).unwrap_or("?");
+        let title: String = if title.chars().count() > 20 {
+            format!("{}…", title.chars().take(19).collect::<String>())
+        } else {
+            title.to_string()
+        };
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(format!(" {}:{} ", i + 1, title), style(app.active_tab == Some(i))));
+    }
+    f.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+/// Read-only view of the note held in the split pane.
+fn draw_split(f: &mut Frame, app: &mut App, area: Rect) {
+    let is_focused = app.focus == Focus::Split;
+    let theme = &app.theme;
+    let Some(split) = &app.split else {
+        return;
+    };
+    let (title, lines) = match app.note_by_id(split.note_id) {
+        Some(note) => (
+            format!("{} [Split]", note.title),
+            theme.markdown(&attachments::placeholders(&note.note, &app.attachments)),
+        ),
+        None => ("Split".to_string(), vec![Line::from("Note not found")]),
+    };
+    let lines: Vec<Line> = if is_focused {
+        lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| if i == split.cursor { apply_line_style(line, theme.cursor_line()) } else { line })
+            .collect()
+    } else {
+        lines
+    };
+    let border_style = if is_focused {
+        theme.fg(theme.accent)
+    } else {
+        Style::default()
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_style(border_style);
+    let inner = block.inner(area);
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false })
+        .scroll((split.scroll, 0));
+    f.render_widget(paragraph, area);
+
+    if let Some(split) = &mut app.split {
+        split.content_height = inner.height;
+    }
+    app.areas.split = inner;
+}
+
+/// Headings of the selected note, indented by level, with the section under
+/// the preview cursor marked.
+fn draw_outline(f: &mut Frame, app: &App, area: Rect) {
+    let outline = markdown::outline(&app.preview_raw_lines());
+    let current = motions::current_section(&outline, app.preview_cursor);
+    let is_focused = app.focus == Focus::Outline;
+    let theme = &app.theme;
+
+    let items: Vec<ListItem> = outline
+        .iter()
+        .enumerate()
+        .map(|(i, (_, level, text))| {
+            let indent = "  ".repeat(level.saturating_sub(1));
+            let marker = if Some(i) == current { "▸ " } else { "  " };
+            let style = if Some(i) == current {
+                theme.fg(theme.accent).add_modifier(Modifier::BOLD)
+            } else if *level == 1 {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(Span::styled(format!("{}{}{}", marker, indent, text), style)))
+        })
+        .collect();
+
+    let border_style = if is_focused {
+        theme.fg(theme.accent)
+    } else {
+        Style::default()
+    };
+    // Unfocused, the list state still tracks the current section so it stays scrolled into view
+    let (selected, highlight_style) = if is_focused && !outline.is_empty() {
+        (
+            Some(app.outline_selected.min(outline.len() - 1)),
+            theme.badge(theme.accent),
+        )
+    } else {
+        (current, Style::default())
+    };
+    let list = List::new(items)
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title(title)
+                .title(format!("Outline ({})", outline.len()))
                 .border_style(border_style),
         )
-        .wrap(Wrap { trim: false })
-        .scroll((app.preview_scroll, 0));
+        .highlight_style(highlight_style);
 
+    let mut state = ListState::default();
+    state.select(selected);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+/// Raw-text view of the embedded editor buffer with a block cursor.
+fn draw_editor(f: &mut Frame, app: &mut App, area: Rect) {
+    app.areas.preview = Rect::default();
+    let Some(editor) = &app.editor else {
+        return;
+    };
+    let inserting = app.mode == Mode::EditInsert;
+    let dirty = if editor.is_dirty() { " [+]" } else { "" };
+    let mode_label = if inserting { "INSERT" } else { "EDIT" };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!("{} [{}]{}", editor.title, mode_label, dirty))
+        .border_style(app.theme.fg(app.theme.success));
+    let inner = block.inner(area);
+
+    let row = app.preview_cursor;
+    let selection = app
+        .visual_anchor
+        .map(|anchor| (anchor.min(row), anchor.max(row)));
+
+    let lines: Vec<Line> = editor
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(i, text)| {
+            let line_style = match selection {
+                Some((start, end)) if i >= start && i <= end => app.theme.selection(),
+                _ if i == row => app.theme.cursor_line(),
+                _ => Style::default(),
+            };
+            if i != row {
+                return Line::from(Span::styled(text.clone(), line_style));
+            }
+            let before: String = text.chars().take(editor.col).collect();
+            let cursor: String = text.chars().nth(editor.col).map(String::from).unwrap_or(" ".to_string());
+            let after: String = text.chars().skip(editor.col + 1).collect();
+            Line::from(vec![
+                Span::styled(before, line_style),
+                Span::styled(cursor, line_style.add_modifier(Modifier::REVERSED)),
+                Span::styled(after, line_style),
+            ])
+        })
+        .collect();
+
+    app.preview_content_height = inner.height;
+    let h_scroll = editor.col.saturating_sub(inner.width.saturating_sub(1) as usize) as u16;
+
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .scroll((app.preview_scroll, h_scroll));
     f.render_widget(paragraph, area);
 }
 
-/// Apply a background color to all spans in a line.
-fn apply_line_bg(line: Line<'static>, bg: Color) -> Line<'static> {
+/// Timestamps, size, links and tags shown above the note body.
+fn metadata_header(theme: &Theme, note: &NoteEntry, width: u16) -> Vec<Line<'static>> {
+    let label = theme.muted();
+    let tags = if note.tags.is_empty() {
+        "none".to_string()
+    } else {
+        note.tags.join(", ")
+    };
+    let mut tags_line = vec![Span::styled("Tags ", label), Span::raw(tags)];
+    if let Some(at) = &note.remind_at {
+        let style = if note.reminder_due() { theme.fg(theme.danger) } else { Style::default() };
+        tags_line.push(Span::styled("  Reminder ", label));
+        tags_line.push(Span::styled(utils::relative_time(at), style));
+    }
+    vec![
+        Line::from(vec![
+            Span::styled("Updated ", label),
+            Span::raw(format!("{} ({})", utils::relative_time(&note.updated_at), note.updated_at)),
+            Span::styled("  Created ", label),
+            Span::raw(note.created_at.clone()),
+        ]),
+        match note.encrypted {
+            true if note.is_locked() => Line::from(Span::styled("Encrypted, locked", theme.fg(theme.danger))),
+            encrypted => {
+                let mut spans = vec![
+                    Span::styled("Words ", label),
+                    Span::raw(note.word_count().to_string()),
+                    Span::styled("  Size ", label),
+                    Span::raw(utils::format_size(note.note.len())),
+                    Span::styled("  Links ", label),
+                    Span::raw(note.link_count().to_string()),
+                ];
+                if encrypted {
+                    spans.push(Span::styled("  Encrypted", theme.fg(theme.danger)));
+                }
+                Line::from(spans)
+            }
+        },
+        Line::from(tags_line),
+        Line::from(Span::styled("─".repeat(width as usize), label)),
+    ]
+}
+
+/// Apply a line highlight to all spans in a line, keeping any background
+/// already set (such as search matches).
+fn apply_line_style(line: Line<'static>, line_style: Style) -> Line<'static> {
     Line::from(
         line.spans
             .into_iter()
-            .map(|span| Span::styled(span.content, span.style.bg(bg)))
+            .map(|span| {
+                let style = if span.style.bg.is_some() { span.style } else { line_style.patch(span.style) };
+                Span::styled(span.content, style)
+            })
             .collect::<Vec<_>>(),
     )
 }
 
+/// Restyle the chars of `line` that fall inside `ranges` (char offsets, sorted),
+/// splitting spans at the range boundaries.
+fn restyle_ranges(line: Line<'static>, ranges: &[(usize, usize)], restyle: impl Fn(Style) -> Style) -> Line<'static> {
+    if ranges.is_empty() {
+        return line;
+    }
+    let in_range = |pos: usize| ranges.iter().any(|&(start, end)| pos >= start && pos < end);
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut pos = 0;
+    for span in line.spans {
+        let mut current = String::new();
+        let mut current_hit = false;
+        for c in span.content.chars() {
+            let hit = in_range(pos);
+            if hit != current_hit && !current.is_empty() {
+                let style = if current_hit { restyle(span.style) } else { span.style };
+                spans.push(Span::styled(std::mem::take(&mut current), style));
+            }
+            current_hit = hit;
+            current.push(c);
+            pos += 1;
+        }
+        if !current.is_empty() {
+            let style = if current_hit { restyle(span.style) } else { span.style };
+            spans.push(Span::styled(current, style));
+        }
+    }
+    Line::from(spans).style(line.style)
+}
+
 fn draw_status_bar(f: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
     let (mode_text, mode_color) = match app.mode {
-        Mode::Normal if app.focus == Focus::Preview => (" PREVIEW ", Color::Cyan),
-        Mode::Normal => (" NORMAL ", Color::Cyan),
-        Mode::TagBrowse => (" TAGS ", Color::Yellow),
-        Mode::Search => (" SEARCH ", Color::Yellow),
-        Mode::Command => (" COMMAND ", Color::Magenta),
-        Mode::AddNoteName | Mode::AddNoteTags => (" ADD NOTE ", Color::Green),
-        Mode::EditTagsAdd => (" EDIT TAGS [+] ", Color::Green),
-        Mode::EditTagsRemove => (" EDIT TAGS [-] ", Color::Green),
-        Mode::VisualLine => (" VISUAL LINE ", Color::Magenta),
+        Mode::Normal if app.focus == Focus::Preview => (" PREVIEW ", theme.accent),
+        Mode::Normal if app.focus == Focus::Outline => (" OUTLINE ", theme.accent),
+        Mode::Normal if app.focus == Focus::Split => (" SPLIT ", theme.accent),
+        Mode::Normal => (" NORMAL ", theme.accent),
+        Mode::TagBrowse => (" TAGS ", theme.highlight),
+        Mode::Search => (" SEARCH ", theme.highlight),
+        Mode::Command => (" COMMAND ", theme.special),
+        Mode::AddNoteName | Mode::AddNoteTags => (" ADD NOTE ", theme.success),
+        Mode::EditTagsAdd => (" EDIT TAGS [+] ", theme.success),
+        Mode::EditTagsRemove => (" EDIT TAGS [-] ", theme.success),
+        Mode::VisualLine | Mode::ExtractNote => (" VISUAL LINE ", theme.special),
+        Mode::ConfirmDelete => (" DELETE ", theme.danger),
+        Mode::ResolveConflict => (" CONFLICT ", theme.danger),
+        Mode::Recover => (" RECOVER ", theme.highlight),
+        Mode::Inbox | Mode::InboxTitle | Mode::InboxTags | Mode::InboxMerge => (" INBOX ", theme.highlight),
+        Mode::EditNormal if app.visual_anchor.is_some() => (" EDIT VISUAL ", theme.special),
+        Mode::EditNormal => (" EDIT ", theme.success),
+        Mode::EditInsert => (" INSERT ", theme.success),
+        Mode::PreviewSearch => (" FIND ", theme.highlight),
+        Mode::Help => (" HELP ", theme.accent),
+        Mode::Unlock => (" UNLOCK ", theme.danger),
     };
 
-    let key_style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
-    let desc_style = Style::default().fg(Color::DarkGray);
+    let key_style = theme.fg(theme.highlight).add_modifier(Modifier::BOLD);
+    let desc_style = theme.muted();
     let sep = Span::styled("  ", desc_style);
 
     let help_spans: Vec<Span> = match &app.status_message {
-        Some(msg) => vec![Span::raw(" "), Span::styled(msg.clone(), Style::default().fg(Color::Yellow))],
+        _ if app.mode == Mode::PreviewSearch => {
+            let prefix = if app.preview_search_backward { "?" } else { "/" };
+            vec![Span::raw(format!(" {}{}", prefix, app.preview_search_input)), Span::styled("█", key_style)]
+        }
+        _ if app.mode == Mode::Command => {
+            vec![Span::raw(format!(" :{}", app.command_input)), Span::styled("█", key_style)]
+        }
+        Some(msg) => vec![Span::raw(" "), Span::styled(msg.clone(), theme.fg(theme.highlight))],
         None => {
             let bindings: &[(&str, &str)] = match app.mode {
-                Mode::Normal if app.focus == Focus::Preview => &[("j/k", "move"), ("V", "visual"), ("^d/^u", "½page"), ("gg/G", "top/bottom"), ("Tab", "toggle"), ("Esc", "back")],
-                Mode::Normal => &[("Enter", "open"), ("c", "create"), ("/", "search"), (":", "cmd"), ("Tab", "tags")],
-                Mode::TagBrowse => &[("Enter", "filter"), ("Esc", "clear & back"), ("Tab", "notes"), (":", "command")],
+                Mode::Normal if app.pending_window => &[("v/s", "split"), ("w/W", "next/prev pane"), ("c", "close split"), ("x", "swap"), ("o", "only")],
+                Mode::Normal if app.focus == Focus::Preview => &[("j/k", "move"), ("w/b/E", "word"), ("{/}", "paragraph"), ("]]/[[", "heading"), ("/", "find"), ("n/N", "next/prev"), ("m/'", "mark/jump"), ("zz", "center"), ("o", "outline"), ("T/gt/X", "tabs"), ("^w", "window"), ("V", "visual"), ("p", "put"), ("x", "toggle task"), ("Esc", "back")],
+                Mode::Normal if app.focus == Focus::Split => &[("j/k", "move"), ("^d/^u", "½page"), ("gg/G", "top/bottom"), ("^w", "window"), ("Esc", "preview")],
+                Mode::Normal if app.focus == Focus::Outline => &[("j/k", "move"), ("g/G", "first/last"), ("Enter", "jump"), ("o", "hide"), ("Esc", "preview")],
+                Mode::Normal => &[("Enter", "open"), ("c", "create"), ("/", "search"), ("p", "pin"), ("A", "archive"), ("H", "show archived"), ("u", "undo"), ("S/R", "sort/reverse"), ("m", "details"), ("o", "outline"), (":", "cmd"), ("?", "help"), ("Tab", "tags")],
+                Mode::TagBrowse => &[("Enter", "filter"), ("Esc", "clear & back"), ("Tab", "notes"), (":", "command"), ("?", "help")],
                 Mode::Search => &[("Enter", "confirm"), ("Esc", "cancel")],
-                Mode::Command => &[("o", "open"), ("a", "add"), ("t", "tags"), ("s", "summarize"), ("Esc", "cancel")],
+                Mode::Command => &[],
                 Mode::AddNoteName => &[("Enter", "next"), ("Esc", "cancel")],
                 Mode::AddNoteTags => &[("Tab", "complete"), ("↑/↓", "select"), ("Enter", "open editor"), ("Esc", "cancel")],
                 Mode::EditTagsAdd | Mode::EditTagsRemove => &[("Tab", "complete/toggle"), ("↑/↓", "select"), ("Enter", "apply"), ("Esc", "cancel")],
-                Mode::VisualLine => &[("j/k", "extend"), ("y", "yank"), ("V", "exit"), ("Esc", "cancel")],
+                Mode::VisualLine => &[("j/k", "extend"), ("y", "yank"), ("x", "extract to note"), ("V", "exit"), ("Esc", "cancel")],
+                Mode::ExtractNote => &[("Enter", "extract"), ("Esc", "back")],
+                Mode::Unlock => &[("Enter", "unlock"), ("Esc", "cancel")],
+                Mode::ConfirmDelete => &[("y", "move to trash"), ("n/Esc", "cancel")],
+                Mode::ResolveConflict if app.editor.is_some() => &[("m", "merge"), ("c", "save as copy"), ("o", "overwrite"), ("n/Esc", "back to editing")],
+                Mode::ResolveConflict => &[("m", "merge"), ("c", "save as copy"), ("o", "overwrite"), ("n/Esc", "keep theirs")],
+                Mode::Recover => &[("j/k", "select"), ("r/Enter", "restore"), ("d", "discard"), ("Esc", "later")],
+                Mode::Inbox => &[("j/k", "move"), ("f/Enter", "file"), ("r", "retitle"), ("t", "tag & file"), ("m", "merge"), ("d", "delete"), ("u", "undo"), ("Esc", "back")],
+                Mode::InboxTitle => &[("Enter", "rename"), ("Esc", "cancel")],
+                Mode::InboxTags => &[("Tab", "complete"), ("↑/↓", "select"), ("Enter", "tag & file"), ("Esc", "cancel")],
+                Mode::InboxMerge => &[("↑/↓", "select"), ("Tab", "complete"), ("Enter", "merge"), ("Esc", "cancel")],
+                Mode::EditNormal if app.visual_anchor.is_some() => &[("j/k", "extend"), ("d", "delete lines"), ("y", "yank"), ("Esc", "cancel")],
+                Mode::EditNormal => &[("i/a/o", "insert"), ("x/dd", "delete"), ("p", "put"), ("V", "visual"), ("u", "undo"), ("^s", "save"), ("Esc", "close")],
+                Mode::EditInsert => &[("Esc", "normal"), ("^s", "save")],
+                Mode::PreviewSearch => &[("Enter", "find"), ("Esc", "cancel")],
+                Mode::Help => &[("j/k", "scroll"), ("Esc/?", "close")],
             };
             let mut spans = vec![Span::raw(" ")];
             for (i, (key, desc)) in bindings.iter().enumerate() {
@@ -279,47 +784,147 @@ fn draw_status_bar(f: &mut Frame, app: &App, area: Rect) {
     };
 
     let mut bar_spans = vec![
-        Span::styled(
-            mode_text,
-            Style::default()
-                .fg(Color::Black)
-                .bg(mode_color)
-                .add_modifier(Modifier::BOLD),
-        ),
+        Span::styled(mode_text, theme.badge(mode_color).add_modifier(Modifier::BOLD)),
     ];
     bar_spans.extend(help_spans);
-    let bar = Line::from(bar_spans);
+    let mut lines = vec![Line::from(bar_spans)];
+    if app.mode == Mode::Command {
+        lines.push(command_hint_line(app));
+    }
 
-    let paragraph = Paragraph::new(bar);
+    let paragraph = Paragraph::new(lines);
     f.render_widget(paragraph, area);
 }
 
-fn draw_input_modal(f: &mut Frame, app: &App) {
-    let has_suggestions = !app.tag_suggestions.is_empty();
-    let height = if has_suggestions { 5 + app.tag_suggestions.len() as u16 } else { 5 };
-    let area = centered_rect(50, height, f.area());
+/// Second status line while typing a command: completion candidates, or the
+/// usage of the command being typed.
+fn command_hint_line(app: &App) -> Line<'static> {
+    let theme = &app.theme;
+    let dim = theme.muted();
+    if !app.completions.is_empty() {
+        let mut spans = vec![Span::raw(" ")];
+        for (i, candidate) in app.completions.iter().enumerate() {
+            let word = candidate.rsplit(' ').next().unwrap_or(candidate).to_string();
+            let style = if app.completion_index == Some(i) {
+                theme.badge(theme.highlight)
+            } else {
+                dim
+            };
+            spans.push(Span::styled(word, style));
+            spans.push(Span::raw(" "));
+        }
+        return Line::from(spans);
+    }
+    let (name, _) = palette::parse(&app.command_input);
+    match palette::find(name) {
+        Some(spec) => Line::from(vec![
+            Span::styled(format!(" :{}", palette::usage(spec)), theme.fg(theme.highlight)),
+            Span::styled(format!("  {}", spec.help), dim),
+        ]),
+        None => Line::from(Span::styled(" Tab complete  ↑/↓ history  Enter run  Esc cancel", dim)),
+    }
+}
+
+/// Every command in the registry with its aliases, arguments and key shortcut.
+fn draw_help(f: &mut Frame, app: &App) {
+    let area = f.area();
+    let width = (area.width * 4 / 5).max(40).min(area.width);
+    let height = (area.height * 4 / 5).max(10).min(area.height);
+    let popup = Rect::new((area.width - width) / 2, (area.height - height) / 2, width, height);
+    f.render_widget(Clear, popup);
 
-    let (title, input, show_suggestions) = match app.mode {
-        Mode::AddNoteName => ("Add Note - Name", &app.input_buffer, false),
-        Mode::AddNoteTags => ("Add Note - Tags (space-separated)", &app.tags_buffer, true),
-        Mode::EditTagsAdd => ("Edit Tags [Add] (space-separated)", &app.input_buffer, true),
-        Mode::EditTagsRemove => ("Edit Tags [Remove] (space-separated)", &app.input_buffer, true),
+    let theme = &app.theme;
+    let heading = theme.fg(theme.accent).add_modifier(Modifier::BOLD);
+    let key_style = theme.fg(theme.highlight);
+    let action_style = theme.fg(theme.special);
+    let dim = theme.muted();
+    let usage_width = palette::COMMANDS
+        .iter()
+        .map(|c| palette::usage(c).chars().count() + 1)
+        .max()
+        .unwrap_or(0);
+
+    let mut lines = vec![
+        Line::from(Span::styled("Commands (type : then a command; Tab completes)", heading)),
+        Line::from(""),
+    ];
+    for spec in palette::COMMANDS {
+        let mut spans = vec![
+            Span::styled(format!("  :{:<width$}", palette::usage(spec), width = usage_width), key_style),
+            Span::styled(format!("{:<7}", spec.key), action_style),
+            Span::raw(spec.help),
+        ];
+        if !spec.aliases.is_empty() {
+            spans.push(Span::styled(format!("  (also :{})", spec.aliases.join(", :")), dim));
+        }
+        lines.push(Line::from(spans));
+    }
+
+    if let Some(context) = app.help_context {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            format!("Keys in {} (rebind under \"keymap\".\"{}\" in config.json)", context.name(), context.name()),
+            heading,
+        )));
+        lines.push(Line::from(""));
+        for (action, keys) in app.keymap.describe(context) {
+            let keys = if keys.is_empty() { "unbound".to_string() } else { keys.join(", ") };
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {:<20}", action), action_style),
+                Span::styled(keys, key_style),
+            ]));
+        }
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Help ")
+                .border_style(theme.fg(theme.accent)),
+        )
+        .wrap(Wrap { trim: false })
+        .scroll((app.help_scroll, 0));
+    f.render_widget(paragraph, popup);
+}
+
+fn draw_input_modal(f: &mut Frame, app: &App) {
+    let merge_titles: Vec<String>;
+    let masked: String;
+    let (title, input, suggestions, selected) = match app.mode {
+        Mode::AddNoteName => ("Add Note - Name", &app.input_buffer, &[][..], 0),
+        Mode::AddNoteTags => ("Add Note - Tags (space-separated)", &app.tags_buffer, &app.tag_suggestions[..], app.selected_suggestion),
+        Mode::EditTagsAdd => ("Edit Tags [Add] (space-separated)", &app.input_buffer, &app.tag_suggestions[..], app.selected_suggestion),
+        Mode::EditTagsRemove => ("Edit Tags [Remove] (space-separated)", &app.input_buffer, &app.tag_suggestions[..], app.selected_suggestion),
+        Mode::InboxTitle => ("Retitle", &app.input_buffer, &[][..], 0),
+        Mode::ExtractNote => ("Extract to New Note - Name", &app.input_buffer, &[][..], 0),
+        Mode::Unlock => {
+            masked = "•".repeat(app.input_buffer.chars().count());
+            ("Passphrase", &masked, &[][..], 0)
+        }
+        Mode::InboxTags => ("Tag and File (space-separated)", &app.input_buffer, &app.tag_suggestions[..], app.selected_suggestion),
+        Mode::InboxMerge => {
+            merge_titles = app.merge_targets.iter().map(|(_, title)| title.clone()).collect();
+            ("Merge Into", &app.input_buffer, &merge_titles[..], app.merge_selected)
+        }
         _ => return,
     };
+    let height = if suggestions.is_empty() { 5 } else { 5 + suggestions.len() as u16 };
+    let area = centered_rect(50, height, f.area());
 
     f.render_widget(Clear, area);
 
     let block = Block::default().borders(Borders::ALL).title(title);
 
-    if show_suggestions && has_suggestions {
+    if !suggestions.is_empty() {
         // Build content with input and suggestions
         let mut lines = vec![Line::from(format!("> {}", input))];
         lines.push(Line::from("─".repeat(area.width.saturating_sub(2) as usize)));
-        for (i, suggestion) in app.tag_suggestions.iter().enumerate() {
-            let style = if i == app.selected_suggestion {
-                Style::default().fg(Color::Black).bg(Color::Cyan)
+        for (i, suggestion) in suggestions.iter().enumerate() {
+            let style = if i == selected {
+                app.theme.badge(app.theme.accent)
             } else {
-                Style::default().fg(Color::DarkGray)
+                app.theme.muted()
             };
             lines.push(Line::from(Span::styled(format!("  {}", suggestion), style)));
         }
@@ -331,6 +936,83 @@ fn draw_input_modal(f: &mut Frame, app: &App) {
     }
 }
 
+fn draw_confirm_delete(f: &mut Frame, app: &App) {
+    let title = app
+        .selected_note()
+        .map(|n| n.title.clone())
+        .unwrap_or_default();
+    let area = centered_rect(50, 5, f.area());
+
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Delete Note")
+        .border_style(app.theme.fg(app.theme.danger));
+    let text = vec![
+        Line::from(format!("Move '{}' to the trash?", title)),
+        Line::from(Span::styled("y: delete  n: cancel", app.theme.muted())),
+    ];
+    let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: false });
+    f.render_widget(paragraph, area);
+}
+
+fn draw_resolve_conflict(f: &mut Frame, app: &App) {
+    let Some(save) = &app.pending_save else {
+        return;
+    };
+    let area = centered_rect(50, 9, f.area());
+
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Note Changed")
+        .border_style(app.theme.fg(app.theme.danger));
+    let keep = if app.editor.is_some() { "n: back to editing" } else { "n: keep theirs, drop yours" };
+    let text = vec![
+        Line::from(format!("'{}' was changed outside scrap while you were editing.", save.title)),
+        Line::from(""),
+        Line::from(Span::styled("m: merge both edits  c: save yours as a copy", app.theme.muted())),
+        Line::from(Span::styled(format!("o: overwrite theirs  {}", keep), app.theme.muted())),
+    ];
+    let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: false });
+    f.render_widget(paragraph, area);
+}
+
+fn draw_recover(f: &mut Frame, app: &App) {
+    const MAX_ROWS: usize = 8;
+    let rows = app.recovery.len().min(MAX_ROWS);
+    let area = centered_rect(60, rows as u16 + 6, f.area());
+
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Recover Unsaved Edits")
+        .border_style(app.theme.fg(app.theme.highlight));
+    let mut text = vec![
+        Line::from("These edits were left behind by an editor that didn't save:"),
+        Line::from(""),
+    ];
+    let first = app.recovery_selected.saturating_sub(MAX_ROWS - 1);
+    for (i, orphan) in app.recovery.iter().enumerate().skip(first).take(MAX_ROWS) {
+        let label = format!("{}  ({}, {})", orphan.title, orphan.kind(), utils::relative_secs(orphan.modified));
+        if i == app.recovery_selected {
+            text.push(Line::from(Span::styled(label, app.theme.badge(app.theme.accent))));
+        } else {
+            text.push(Line::from(label));
+        }
+    }
+    text.push(Line::from(""));
+    text.push(Line::from(Span::styled(
+        "r: restore  d: discard  Esc: later (scrap recover)",
+        app.theme.muted(),
+    )));
+    let paragraph = Paragraph::new(text).block(block);
+    f.render_widget(paragraph, area);
+}
+
 fn draw_search_popup(f: &mut Frame, app: &App) {
     let area = f.area();
     let width = (area.width / 2).max(30).min(area.width.saturating_sub(4));
@@ -343,7 +1025,7 @@ fn draw_search_popup(f: &mut Frame, app: &App) {
     let block = Block::default()
         .borders(Borders::ALL)
         .title(" Search ")
-        .border_style(Style::default().fg(Color::Yellow));
+        .border_style(app.theme.fg(app.theme.highlight));
     let input = Paragraph::new(format!("/{}", app.search_query)).block(block);
     f.render_widget(input, popup);
 }
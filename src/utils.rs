@@ -15,12 +15,35 @@ pub fn validate_name(name: &str) -> Result<()> {
     Ok(())
 }
 
-fn sanitize_filename(name: &str) -> String {
+pub fn sanitize_filename(name: &str) -> String {
     name.chars()
         .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
         .collect()
 }
 
+/// Text longer than this many bytes is shortened to make a title, well under
+/// the 100 allowed in a note name.
+const MAX_TITLE_LEN: usize = 60;
+
+/// A title made from the first line with any text, without markdown heading
+/// marks or path separators. None if there's nothing usable.
+pub fn title_from(contents: &str) -> Option<String> {
+    let line = contents.lines().map(|l| l.trim_start_matches('#').trim()).find(|l| !l.is_empty())?;
+    let line: String = line.chars().map(|c| if c == '/' || c == '\\' { '-' } else { c }).collect();
+    if line.len() <= MAX_TITLE_LEN {
+        return Some(line);
+    }
+
+    // Cut at the last word break that fits, or mid-word if there is none
+    let end = (0..=MAX_TITLE_LEN).rev().find(|&i| line.is_char_boundary(i)).unwrap_or(0);
+    let cut = &line[..end];
+    let cut = match cut.rfind(char::is_whitespace) {
+        Some(i) if i > 0 => cut[..i].trim_end(),
+        _ => cut,
+    };
+    Some(format!("{}…", cut))
+}
+
 pub fn validate_tags(tags: &[String]) -> Result<()> {
     for tag in tags {
         let trimmed = tag.trim();
@@ -53,52 +76,88 @@ pub fn get_editor() -> Result<String> {
     bail!("No editor found. Set the $EDITOR environment variable.")
 }
 
-pub fn get_user_input(name: &str) -> Result<String> {
-    let home = dirs::home_dir().context("Could not determine home directory")?;
-    let temp_dir = home.join(".scrap/temp");
-    std::fs::create_dir_all(&temp_dir)?;
-    let safe_name = sanitize_filename(name);
-    let temp_file = temp_dir.join(format!("{}.md", safe_name));
-
-    if !temp_file.exists() {
-        std::fs::write(&temp_file, "")?;
+/// Ask on stderr until the answer starts with one of `choices`, and return that
+/// character. End of input counts as the last choice.
+pub fn prompt_choice(question: &str, choices: &[char]) -> Result<char> {
+    use std::io::Write;
+    loop {
+        eprint!("{} ", question);
+        std::io::stderr().flush()?;
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            return choices.last().copied().context("No choices to prompt for");
+        }
+        if let Some(c) = answer.trim().chars().next().map(|c| c.to_ascii_lowercase())
+            && choices.contains(&c)
+        {
+            return Ok(c);
+        }
     }
+}
 
-    let editor = get_editor()?;
-    let status = Command::new(&editor)
-        .arg(&temp_file)
-        .status()
-        .with_context(|| format!("Failed to open editor: {}", editor))?;
+/// Parse a SQLite `YYYY-MM-DD HH:MM:SS` (UTC) timestamp into Unix seconds.
+pub fn parse_timestamp(ts: &str) -> Option<i64> {
+    let (date, time) = ts.trim().split_once(' ').unwrap_or((ts.trim(), "00:00:00"));
+    let mut date_parts = date.splitn(3, '-').map(|p| p.parse::<i64>());
+    let (y, m, d) = (date_parts.next()?.ok()?, date_parts.next()?.ok()?, date_parts.next()?.ok()?);
+    let mut time_parts = time.splitn(3, ':').map(|p| p.parse::<i64>());
+    let h = time_parts.next().and_then(|p| p.ok()).unwrap_or(0);
+    let min = time_parts.next().and_then(|p| p.ok()).unwrap_or(0);
+    let s = time_parts.next().and_then(|p| p.ok()).unwrap_or(0);
 
-    if !status.success() {
-        bail!("Editor exited with non-zero status");
-    }
+    // Days since the Unix epoch (Howard Hinnant's days_from_civil)
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
 
-    let contents = std::fs::read_to_string(&temp_file)?;
-    std::fs::remove_file(&temp_file)?;
-    Ok(contents)
+    Some(days * 86400 + h * 3600 + min * 60 + s)
 }
 
-pub fn get_user_input_with_contents(name: &str, existing: &str) -> Result<String> {
-    let home = dirs::home_dir().context("Could not determine home directory")?;
-    let temp_dir = home.join(".scrap/temp");
-    std::fs::create_dir_all(&temp_dir)?;
-    let safe_name = sanitize_filename(name);
-    let temp_file = temp_dir.join(format!("{}.md", safe_name));
-
-    std::fs::write(&temp_file, existing)?;
+pub fn now_secs() -> i64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
 
-    let editor = get_editor()?;
-    let status = Command::new(&editor)
-        .arg(&temp_file)
-        .status()
-        .with_context(|| format!("Failed to open editor: {}", editor))?;
+/// Human-readable size, e.g. "12.3 KB".
+pub fn format_size(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
 
-    if !status.success() {
-        bail!("Editor exited with non-zero status");
+/// Format a timestamp relative to now, e.g. "3h ago".
+pub fn relative_time(ts: &str) -> String {
+    match parse_timestamp(ts) {
+        Some(then) => relative_secs(then),
+        None => ts.to_string(),
     }
+}
 
-    let contents = std::fs::read_to_string(&temp_file)?;
-    std::fs::remove_file(&temp_file)?;
-    Ok(contents)
+/// Format a Unix timestamp relative to now, e.g. "3h ago" or "in 2d".
+pub fn relative_secs(then: i64) -> String {
+    let elapsed = now_secs() - then;
+    let span = elapsed.abs();
+    let span = match span {
+        0..60 => return "just now".to_string(),
+        60..3600 => format!("{}m", span / 60),
+        3600..86400 => format!("{}h", span / 3600),
+        86400..2_592_000 => format!("{}d", span / 86400),
+        2_592_000..31_536_000 => format!("{}mo", span / 2_592_000),
+        _ => format!("{}y", span / 31_536_000),
+    };
+    if elapsed < 0 {
+        format!("in {}", span)
+    } else {
+        format!("{} ago", span)
+    }
 }